@@ -5,9 +5,9 @@ use crate::common::services::hashing::hashing;
 use crate::features::user::models::User;
 use crate::repository::database::Database;
 
-use actix_web::error::ErrorUnauthorized;
+use crate::common::models::errors::AppError;
 use actix_web::web::Data;
-use actix_web::{dev, Error, FromRequest, HttpRequest};
+use actix_web::{dev, FromRequest, HttpRequest};
 use futures::future::ready;
 use futures::Future;
 
@@ -17,7 +17,7 @@ use uuid::Uuid;
 pub struct AuthenticationService(pub User);
 
 impl FromRequest for AuthenticationService {
-    type Error = Error;
+    type Error = AppError;
     type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
 
     fn from_request(req: &HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
@@ -25,22 +25,38 @@ impl FromRequest for AuthenticationService {
 
         let db = match req.app_data::<Data<Database>>() {
             Some(c) => c.clone(),
-            None => return Box::pin(ready(Err(ErrorUnauthorized("blocked!")))),
+            None => {
+                return Box::pin(ready(Err(AppError::Internal(
+                    "database is not configured".to_string(),
+                ))))
+            }
         };
 
-        let token = match req.headers().get("Authorization") {
-            Some(auth) => auth.to_str().unwrap().replace("Bearer ", "").to_string(),
-            None => return Box::pin(ready(Err(ErrorUnauthorized("Invalid token")))),
+        let token = match req
+            .headers()
+            .get("Authorization")
+            .and_then(|auth| auth.to_str().ok())
+        {
+            Some(auth) => auth.replace("Bearer ", ""),
+            None => {
+                return Box::pin(ready(Err(AppError::Unauthorized(
+                    "auth:errors.invalidToken",
+                ))))
+            }
         };
 
         Box::pin(async move {
-            match crypto.verify_jwt(token).await {
-                Ok(v) => User::get_by_id(db, Uuid::from_str(&v.claims.sub).unwrap())
-                    .await
-                    .and_then(|u| Ok(AuthenticationService(u)))
-                    .map_err(|_| ErrorUnauthorized("DB error!").into()),
-                Err(_) => return Err(ErrorUnauthorized("blocked!")),
-            }
+            let claims = crypto.verify_jwt(token).await?.claims;
+            let user_id = Uuid::from_str(&claims.sub)
+                .map_err(|_| AppError::Unauthorized("auth:errors.invalidToken"))?;
+
+            User::get_by_id(db, user_id)
+                .await
+                .map(AuthenticationService)
+                .map_err(|err| match err {
+                    AppError::NotFound(_) => AppError::Unauthorized("auth:errors.invalidToken"),
+                    err => err,
+                })
         })
     }
 }
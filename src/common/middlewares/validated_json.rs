@@ -0,0 +1,49 @@
+use std::ops::Deref;
+use std::pin::Pin;
+
+use crate::common::models::errors::AppError;
+use actix_web::{dev, web, FromRequest, HttpRequest};
+use futures::Future;
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+// `web::Json` that also runs the payload's `Validate` rules, both malformed JSON and
+// failed rules are answered with `AppError::Validation`
+#[derive(Debug)]
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T> {
+    type Error = AppError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        let json = web::Json::<T>::from_request(req, payload);
+
+        Box::pin(async move {
+            let data = json
+                .await
+                .map_err(|err| {
+                    log::debug!("invalid request payload: {}", err);
+                    AppError::field("", "common:errors.invalidPayload")
+                })?
+                .into_inner();
+            data.validate()?;
+
+            Ok(ValidatedJson(data))
+        })
+    }
+}
@@ -2,9 +2,12 @@ use std::fmt;
 
 use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
 use serde::Serialize;
+use std::borrow::Cow;
+use validator::{ValidationErrors, ValidationErrorsKind};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 pub struct FormError<'a> {
     pub field: &'a str,
     pub message: &'a str,
@@ -20,3 +23,161 @@ impl ResponseError for FormError<'_> {
         HttpResponse::build(StatusCode::BAD_REQUEST).json(self)
     }
 }
+
+// Every error a handler can answer with. Codes are translation keys in the
+// `namespace:path.errors.key` form the frontend already uses, e.g.
+// `profile:username.errors.notFound`.
+#[derive(Debug)]
+pub enum AppError {
+    NotFound(&'static str),
+    Validation(Vec<FormError<'static>>),
+    Unauthorized(&'static str),
+    Forbidden(&'static str),
+    Conflict(&'static str),
+    Database(DieselError),
+    Internal(String),
+}
+
+impl AppError {
+    // Validation error of a single field, an empty field is a form-wide error
+    pub fn field(field: &'static str, message: &'static str) -> Self {
+        Self::Validation(vec![FormError { field, message }])
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound(code)
+            | Self::Unauthorized(code)
+            | Self::Forbidden(code)
+            | Self::Conflict(code) => code,
+            Self::Validation(_) => "common:errors.validation",
+            Self::Database(_) => "common:errors.database",
+            Self::Internal(_) => "common:errors.internal",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody<'a> {
+    code: &'static str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    errors: &'a [FormError<'static>],
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Validation(errors) => write!(f, "{}: {:?}", self.code(), errors),
+            Self::Database(err) => write!(f, "{}: {}", self.code(), err),
+            Self::Internal(err) => write!(f, "{}: {}", self.code(), err),
+            _ => write!(f, "{}", self.code()),
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Validation(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Database(_) | Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Database and internal details are logged, never sent to the client
+    fn error_response(&self) -> HttpResponse {
+        if let Self::Database(_) | Self::Internal(_) = self {
+            log::error!("{}", self);
+        }
+
+        let errors = match self {
+            Self::Validation(errors) => errors.as_slice(),
+            _ => &[],
+        };
+
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            code: self.code(),
+            errors,
+        })
+    }
+}
+
+impl From<DieselError> for AppError {
+    fn from(err: DieselError) -> Self {
+        match err {
+            DieselError::NotFound => Self::NotFound("common:errors.notFound"),
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                Self::Conflict("common:errors.alreadyExists")
+            }
+            err => Self::Database(err),
+        }
+    }
+}
+
+// Rule messages are translation keys, so they are always `'static`. Errors of nested
+// structs and lists are reported under the field that holds them, schema-level errors
+// are form-wide.
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        fn collect(
+            field: &'static str,
+            errors: &ValidationErrors,
+            out: &mut Vec<FormError<'static>>,
+        ) {
+            for (name, kind) in errors.errors() {
+                let name = match (*name, field) {
+                    ("__all__", _) => field,
+                    (name, "") => name,
+                    _ => field,
+                };
+                match kind {
+                    ValidationErrorsKind::Field(list) => {
+                        out.extend(list.iter().map(|error| FormError {
+                            field: name,
+                            message: match (&error.message, &error.code) {
+                                (Some(Cow::Borrowed(message)), _) => message,
+                                (None, Cow::Borrowed(code)) => code,
+                                _ => "common:errors.invalid",
+                            },
+                        }))
+                    }
+                    ValidationErrorsKind::Struct(nested) => collect(name, nested, out),
+                    ValidationErrorsKind::List(items) => {
+                        items.values().for_each(|nested| collect(name, nested, out))
+                    }
+                }
+            }
+        }
+
+        let mut form_errors = vec![];
+        collect("", &errors, &mut form_errors);
+        // Field errors come out of a hash map
+        form_errors.sort_by_key(|error| (error.field, error.message));
+        form_errors.dedup_by_key(|error| (error.field, error.message));
+
+        Self::Validation(form_errors)
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for AppError {
+    fn from(err: diesel::r2d2::PoolError) -> Self {
+        Self::Internal(err.to_string())
+    }
+}
+
+pub trait OrNotFound<T> {
+    // Maps a missing row to the given code, any other failure stays a database error
+    fn or_not_found(self, code: &'static str) -> Result<T, AppError>;
+}
+
+impl<T> OrNotFound<T> for Result<T, DieselError> {
+    fn or_not_found(self, code: &'static str) -> Result<T, AppError> {
+        self.map_err(|err| match err {
+            DieselError::NotFound => AppError::NotFound(code),
+            err => err.into(),
+        })
+    }
+}
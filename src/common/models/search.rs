@@ -0,0 +1,9 @@
+// `ILIKE` pattern matching `search` anywhere, with its wildcards taken literally
+pub fn contains_pattern(search: &str) -> String {
+    let escaped = search
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{}%", escaped)
+}
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use crate::common::models::errors::AppError;
 use actix_web::web::block;
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
@@ -15,24 +16,22 @@ pub struct CryptoService {
 }
 
 impl CryptoService {
-    pub async fn hash_password(&self, password: String) -> Result<String, ()> {
-        match bcrypt::hash_with(
+    pub async fn hash_password(&self, password: String) -> Result<String, AppError> {
+        bcrypt::hash_with(
             BcryptSetup {
                 salt: Some(&self.key.clone()),
                 ..Default::default()
             },
             password,
-        ) {
-            Ok(res) => Ok(res),
-            _ => Err(()),
-        }
+        )
+        .map_err(|err| AppError::Internal(err.to_string()))
     }
 
-    pub async fn verify_password(&self, password: &str, password_hash: &str) -> Result<bool, ()> {
-        Ok(bcrypt::verify(password, password_hash))
+    pub async fn verify_password(&self, password: &str, password_hash: &str) -> bool {
+        bcrypt::verify(password, password_hash)
     }
 
-    pub async fn generate_jwt(&self, user_id: Uuid) -> Result<String, String> {
+    pub async fn generate_jwt(&self, user_id: Uuid) -> Result<String, AppError> {
         let jwt_key = self.jwt_secret.clone();
         block(move || {
             let headers = Header::default();
@@ -42,21 +41,23 @@ impl CryptoService {
                 sub: user_id.to_string(),
                 exp: now.timestamp(),
             };
-            encode(&headers, &claims, &encoding_key).unwrap()
+            encode(&headers, &claims, &encoding_key)
         })
         .await
-        .map_err(|_err| "error generating jwt".to_string())
+        .map_err(|err| AppError::Internal(err.to_string()))?
+        .map_err(|err| AppError::Internal(err.to_string()))
     }
 
-    pub async fn verify_jwt(&self, token: String) -> Result<TokenData<Claims>, String> {
+    pub async fn verify_jwt(&self, token: String) -> Result<TokenData<Claims>, AppError> {
         let jwt_key = self.jwt_secret.clone();
         block(move || {
             let decoding_key = DecodingKey::from_secret(jwt_key.as_bytes());
             let validation = Validation::default();
-            decode::<Claims>(&token, &decoding_key, &validation).unwrap()
+            decode::<Claims>(&token, &decoding_key, &validation)
         })
         .await
-        .map_err(|_err| "error verifying jwt".to_string())
+        .map_err(|err| AppError::Internal(err.to_string()))?
+        .map_err(|_| AppError::Unauthorized("auth:errors.invalidToken"))
     }
 }
 
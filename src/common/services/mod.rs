@@ -1,2 +1,5 @@
 pub mod crypto;
 pub mod hashing;
+pub mod notifier;
+pub mod scheduler;
+pub mod storage;
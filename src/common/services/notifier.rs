@@ -0,0 +1,305 @@
+use crate::common::models::errors::AppError;
+use futures::future::BoxFuture;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub user_id: Uuid,
+    pub habit_id: Uuid,
+    pub reminder_id: Uuid,
+    pub title: String,
+    pub body: String,
+    #[serde(skip)]
+    pub email: String,
+    #[serde(skip)]
+    pub webhook_url: Option<String>,
+}
+
+// Delivers notifications through one channel. Failed deliveries are retried by
+// the caller, so a notifier doesn't retry on its own
+pub trait Notifier: Send + Sync {
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<(), AppError>>;
+}
+
+#[derive(Clone, Default)]
+pub struct Notifiers {
+    channels: HashMap<&'static str, Arc<dyn Notifier>>,
+}
+
+impl Notifiers {
+    pub fn register(mut self, channel: &'static str, notifier: Arc<dyn Notifier>) -> Self {
+        self.channels.insert(channel, notifier);
+        self
+    }
+
+    pub fn has_channel(&self, channel: &str) -> bool {
+        self.channels.contains_key(channel)
+    }
+
+    pub async fn send(&self, channel: &str, notification: &Notification) -> Result<(), AppError> {
+        let notifier = self
+            .channels
+            .get(channel)
+            .ok_or_else(|| AppError::Internal(format!("no notifier for channel {}", channel)))?;
+
+        notifier.send(notification).await
+    }
+}
+
+// Email is only available once SMTP is configured
+pub fn notifiers(push: PushNotifier) -> Notifiers {
+    let notifiers = Notifiers::default()
+        .register("push", Arc::new(push))
+        .register("webhook", Arc::new(WebhookNotifier));
+
+    match EmailNotifier::from_env() {
+        Some(email) => notifiers.register("email", Arc::new(email)),
+        None => notifiers,
+    }
+}
+
+// Pushes notifications to the open WebSocket connections of the user. Users
+// without a connection miss the notification
+#[derive(Clone, Default)]
+pub struct PushNotifier {
+    sessions: Arc<Mutex<HashMap<Uuid, Vec<mpsc::UnboundedSender<String>>>>>,
+}
+
+impl PushNotifier {
+    pub fn subscribe(&self, user_id: Uuid) -> mpsc::UnboundedReceiver<String> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.sessions
+            .lock()
+            .unwrap()
+            .entry(user_id)
+            .or_default()
+            .push(sender);
+
+        receiver
+    }
+}
+
+impl Notifier for PushNotifier {
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(async move {
+            let message = serde_json::to_string(notification)
+                .map_err(|err| AppError::Internal(err.to_string()))?;
+
+            let mut sessions = self.sessions.lock().unwrap();
+            if let Some(senders) = sessions.get_mut(&notification.user_id) {
+                // Closed connections are dropped on the way
+                senders.retain(|sender| sender.send(message.clone()).is_ok());
+                if senders.is_empty() {
+                    sessions.remove(&notification.user_id);
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
+
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl EmailNotifier {
+    pub fn from_env() -> Option<Self> {
+        let host = env::var("SMTP_HOST").ok()?;
+        let from = env::var("SMTP_FROM")
+            .expect("SMTP_FROM must be set")
+            .parse()
+            .expect("SMTP_FROM must be a valid mailbox");
+
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&host)
+            .expect("SMTP_HOST must be a valid host");
+        if let (Ok(username), Ok(password)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD"))
+        {
+            transport = transport.credentials(Credentials::new(username, password));
+        }
+
+        Some(Self {
+            transport: transport.build(),
+            from,
+        })
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(async move {
+            let to = notification
+                .email
+                .parse::<Mailbox>()
+                .map_err(|err| AppError::Internal(err.to_string()))?;
+            let message = Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(&notification.title)
+                .body(notification.body.clone())
+                .map_err(|err| AppError::Internal(err.to_string()))?;
+
+            self.transport
+                .send(message)
+                .await
+                .map(|_| ())
+                .map_err(|err| AppError::Internal(err.to_string()))
+        })
+    }
+}
+
+// Posts the notification as JSON to the URL of the reminder. The host is resolved
+// up front and the request pinned to the checked addresses, so that a name
+// pointing to a private network is never reached
+pub struct WebhookNotifier;
+
+impl Notifier for WebhookNotifier {
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(async move {
+            let url = notification
+                .webhook_url
+                .as_deref()
+                .ok_or_else(|| AppError::Internal("missing webhook url".to_string()))?;
+            if !is_allowed_webhook_url(url) {
+                return Err(AppError::Internal(format!(
+                    "webhook url {} is not allowed",
+                    url
+                )));
+            }
+
+            let url =
+                reqwest::Url::parse(url).map_err(|err| AppError::Internal(err.to_string()))?;
+            let host = url.host_str().unwrap_or_default();
+            let mut client = reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .redirect(reqwest::redirect::Policy::none());
+            // IP hosts were checked with the URL, only names need resolving
+            if !host.starts_with('[') && host.parse::<IpAddr>().is_err() {
+                let port = url.port_or_known_default().unwrap_or(443);
+                let addrs = tokio::net::lookup_host((host, port))
+                    .await
+                    .map_err(|err| AppError::Internal(err.to_string()))?
+                    .collect::<Vec<SocketAddr>>();
+                if addrs.is_empty() || !addrs.iter().all(|addr| is_public_ip(addr.ip())) {
+                    return Err(AppError::Internal(format!(
+                        "webhook host {} is not public",
+                        host
+                    )));
+                }
+                client = client.resolve_to_addrs(host, &addrs);
+            }
+
+            client
+                .build()
+                .map_err(|err| AppError::Internal(err.to_string()))?
+                .post(url)
+                .json(notification)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map(|_| ())
+                .map_err(|err| AppError::Internal(err.to_string()))
+        })
+    }
+}
+
+// Webhooks may only reach public HTTPS endpoints. Names are checked again once
+// resolved, when the notification is sent
+pub fn is_allowed_webhook_url(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    if url.scheme() != "https" {
+        return false;
+    }
+
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(ip) => is_public_ip(ip),
+        Err(_) => {
+            let host = host.trim_end_matches('.');
+            !host.is_empty() && host != "localhost" && !host.ends_with(".localhost")
+        }
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))) // shared address space
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || (first & 0xfe00) == 0xfc00 // unique local
+                    || (first & 0xffc0) == 0xfe80) // link-local
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webhooks_need_https() {
+        assert!(is_allowed_webhook_url(
+            "https://hooks.example.com/reminders"
+        ));
+        assert!(!is_allowed_webhook_url(
+            "http://hooks.example.com/reminders"
+        ));
+        assert!(!is_allowed_webhook_url("ftp://hooks.example.com/"));
+        assert!(!is_allowed_webhook_url("not a url"));
+    }
+
+    #[test]
+    fn webhooks_cannot_reach_local_hosts() {
+        assert!(!is_allowed_webhook_url("https://localhost/hook"));
+        assert!(!is_allowed_webhook_url("https://api.localhost./hook"));
+        assert!(!is_allowed_webhook_url("https://127.0.0.1/hook"));
+        assert!(!is_allowed_webhook_url("https://10.0.0.8/hook"));
+        assert!(!is_allowed_webhook_url("https://172.16.4.1/hook"));
+        assert!(!is_allowed_webhook_url("https://192.168.1.1/hook"));
+        assert!(!is_allowed_webhook_url(
+            "https://169.254.169.254/latest/meta-data"
+        ));
+        assert!(!is_allowed_webhook_url("https://100.64.0.1/hook"));
+        assert!(!is_allowed_webhook_url("https://0.0.0.0/hook"));
+        assert!(!is_allowed_webhook_url("https://[::1]/hook"));
+        assert!(!is_allowed_webhook_url("https://[fd00::1]/hook"));
+        assert!(!is_allowed_webhook_url("https://[fe80::1]/hook"));
+        assert!(!is_allowed_webhook_url("https://[::ffff:127.0.0.1]/hook"));
+    }
+
+    #[test]
+    fn webhooks_can_reach_public_addresses() {
+        assert!(is_allowed_webhook_url("https://93.184.216.34/hook"));
+        assert!(is_allowed_webhook_url(
+            "https://[2606:4700::1111]:8443/hook"
+        ));
+    }
+}
@@ -0,0 +1,64 @@
+use crate::common::services::notifier::Notifiers;
+use crate::features::account_terms::models::AccountTerms;
+use crate::features::balance_history::models::BalanceSnapshot;
+use crate::features::habit::models::HabitsAchievement;
+use crate::features::habit_reminder::models::HabitReminder;
+use crate::features::recurring_transaction::models::RecurringTransaction;
+use crate::features::savings_goal::models::SavingsGoal;
+use crate::repository::database::Database;
+use actix_web::web;
+use std::env;
+use std::time::Duration;
+
+// Runs periodic jobs in-process. Every job must be safe to run again after a
+// restart, the scheduler itself keeps no state.
+pub fn start(db: web::Data<Database>, notifiers: Notifiers) {
+    let interval_secs: u64 = env::var("SCHEDULER_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(300);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+
+        loop {
+            interval.tick().await;
+
+            match RecurringTransaction::post_due(db.clone()).await {
+                Ok(0) => {}
+                Ok(posted) => log::info!("posted {} recurring transactions", posted),
+                Err(err) => log::error!("failed to post recurring transactions: {}", err),
+            }
+
+            match AccountTerms::post_due_interest(db.clone()).await {
+                Ok(0) => {}
+                Ok(posted) => log::info!("posted {} interest transactions", posted),
+                Err(err) => log::error!("failed to post interest: {}", err),
+            }
+
+            match BalanceSnapshot::record_daily(db.clone()).await {
+                Ok(0) => {}
+                Ok(recorded) => log::info!("recorded {} balance snapshots", recorded),
+                Err(err) => log::error!("failed to record balance snapshots: {}", err),
+            }
+
+            match SavingsGoal::check_all(db.clone()).await {
+                Ok(0) => {}
+                Ok(completed) => log::info!("completed {} savings goals", completed),
+                Err(err) => log::error!("failed to check savings goals: {}", err),
+            }
+
+            match HabitsAchievement::check_negative_habits(db.clone()).await {
+                Ok(0) => {}
+                Ok(unlocked) => log::info!("unlocked {} habit achievements", unlocked),
+                Err(err) => log::error!("failed to check negative habits: {}", err),
+            }
+
+            match HabitReminder::send_due(db.clone(), &notifiers).await {
+                Ok(0) => {}
+                Ok(sent) => log::info!("sent {} habit reminders", sent),
+                Err(err) => log::error!("failed to send habit reminders: {}", err),
+            }
+        }
+    });
+}
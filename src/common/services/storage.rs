@@ -0,0 +1,73 @@
+use crate::common::models::errors::AppError;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+// Keeps binary blobs (check-in photos, ...) out of the database. Keys are
+// relative paths such as "targets/<user_id>/<target_id>.jpg"
+pub trait BlobStorage: Send + Sync {
+    fn put(&self, key: &str, data: &[u8]) -> Result<(), AppError>;
+    fn get(&self, key: &str) -> Result<Vec<u8>, AppError>;
+    fn delete(&self, key: &str) -> Result<(), AppError>;
+}
+
+pub fn storage() -> Arc<dyn BlobStorage> {
+    let root = env::var("STORAGE_PATH").unwrap_or_else(|_| "storage".to_string());
+    Arc::new(LocalStorage::new(root))
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, AppError> {
+        let key = Path::new(key);
+        // Keys must never point outside of the storage root
+        if key
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(AppError::Internal(format!(
+                "invalid storage key {}",
+                key.display()
+            )));
+        }
+
+        Ok(self.root.join(key))
+    }
+}
+
+impl BlobStorage for LocalStorage {
+    fn put(&self, key: &str, data: &[u8]) -> Result<(), AppError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|err| AppError::Internal(err.to_string()))?;
+        }
+
+        fs::write(path, data).map_err(|err| AppError::Internal(err.to_string()))
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>, AppError> {
+        fs::read(self.path(key)?).map_err(|err| match err.kind() {
+            ErrorKind::NotFound => AppError::NotFound("common:errors.notFound"),
+            _ => AppError::Internal(err.to_string()),
+        })
+    }
+
+    fn delete(&self, key: &str) -> Result<(), AppError> {
+        match fs::remove_file(self.path(key)?) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                Err(AppError::Internal(err.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
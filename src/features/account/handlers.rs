@@ -1,6 +1,9 @@
 use crate::common::middlewares::auth::AuthenticationService;
-use crate::features::account::models::ReorderAccountsData;
-use crate::features::transaction::models::Transaction;
+use crate::common::middlewares::validated_json::ValidatedJson;
+use crate::common::models::errors::AppError;
+use crate::features::account::models::{AccountsQuery, DeleteAccountQuery, ReorderAccountsData};
+use crate::features::account_terms::models::{AccountTerms, AccountTermsData};
+use crate::features::balance_history::models::{BalanceHistory, HistoryQuery};
 use crate::{
     features::account::models::{Account, AccountData},
     repository::database::Database,
@@ -14,39 +17,49 @@ pub fn routes() -> Scope {
         .service(create_account)
         .service(update_account)
         .service(reorder_accounts)
+        .service(archive_account)
+        .service(unarchive_account)
+        .service(get_account_history)
+        .service(get_account_terms)
+        .service(set_account_terms)
+        .service(delete_account_terms)
+        .service(get_account_schedule)
         .service(delete_account)
 }
 
 #[get("")]
-async fn get_accounts(user: AuthenticationService, db: web::Data<Database>) -> HttpResponse {
-    let accounts = Account::get_all(db.clone(), user.0.id).await.unwrap();
+async fn get_accounts(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<AccountsQuery>,
+) -> Result<HttpResponse, AppError> {
+    let include_archived = query.include_archived.unwrap_or(false);
+    let accounts = Account::get_all(db.clone(), user.0.id, include_archived).await?;
 
-    HttpResponse::Ok().json(accounts)
+    Ok(HttpResponse::Ok().json(accounts))
 }
 
 #[post("")]
 async fn create_account(
     user: AuthenticationService,
     db: web::Data<Database>,
-    form: web::Json<AccountData>,
-) -> HttpResponse {
-    match Account::create(db.clone(), form.into_inner(), user.0.id).await {
-        Ok(_) => HttpResponse::Ok().json(Account::get_all(db.clone(), user.0.id).await.unwrap()),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+    form: ValidatedJson<AccountData>,
+) -> Result<HttpResponse, AppError> {
+    Account::create(db.clone(), form.into_inner(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(Account::get_all(db.clone(), user.0.id, false).await?))
 }
 
 #[put("/{id}")]
 async fn update_account(
-    _user: AuthenticationService,
+    user: AuthenticationService,
     db: web::Data<Database>,
     path: web::Path<Uuid>,
-    form: web::Json<AccountData>,
-) -> HttpResponse {
-    match Account::update(db.clone(), path.clone(), form.into_inner()).await {
-        Ok(_) => HttpResponse::Ok().body("Account updated"),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+    form: ValidatedJson<AccountData>,
+) -> Result<HttpResponse, AppError> {
+    Account::update(db.clone(), user.0.id, path.into_inner(), form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("Account updated"))
 }
 
 #[post("/reorder")]
@@ -54,23 +67,102 @@ async fn reorder_accounts(
     user: AuthenticationService,
     db: web::Data<Database>,
     form: web::Json<Vec<ReorderAccountsData>>,
-) -> HttpResponse {
-    match Account::reorder(db.clone(), form.into_inner()).await {
-        Ok(_) => HttpResponse::Ok().json(Account::get_all(db.clone(), user.0.id).await.unwrap()),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+) -> Result<HttpResponse, AppError> {
+    Account::reorder(db.clone(), user.0.id, form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(Account::get_all(db.clone(), user.0.id, false).await?))
+}
+
+#[put("/{id}/archive")]
+async fn archive_account(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    Account::set_archived(db.clone(), user.0.id, path.into_inner(), true).await?;
+
+    Ok(HttpResponse::Ok().body("Account archived"))
+}
+
+#[put("/{id}/unarchive")]
+async fn unarchive_account(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    Account::set_archived(db.clone(), user.0.id, path.into_inner(), false).await?;
+
+    Ok(HttpResponse::Ok().body("Account unarchived"))
+}
+
+#[get("/{id}/history")]
+async fn get_account_history(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+    query: web::Query<HistoryQuery>,
+) -> Result<HttpResponse, AppError> {
+    let history =
+        BalanceHistory::for_account(db.clone(), user.0.id, path.into_inner(), query.into_inner())
+            .await?;
+
+    Ok(HttpResponse::Ok().json(history))
+}
+
+#[get("/{id}/terms")]
+async fn get_account_terms(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let terms = AccountTerms::get(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(terms))
+}
+
+#[put("/{id}/terms")]
+async fn set_account_terms(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+    form: web::Json<AccountTermsData>,
+) -> Result<HttpResponse, AppError> {
+    let terms =
+        AccountTerms::set(db.clone(), user.0.id, path.into_inner(), form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(terms))
+}
+
+#[delete("/{id}/terms")]
+async fn delete_account_terms(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    AccountTerms::delete(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("Interest terms deleted"))
+}
+
+#[get("/{id}/schedule")]
+async fn get_account_schedule(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let schedule = AccountTerms::schedule(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(schedule))
 }
 
 #[delete("/{id}")]
 async fn delete_account(
-    _user: AuthenticationService,
+    user: AuthenticationService,
     db: web::Data<Database>,
     path: web::Path<Uuid>,
-) -> HttpResponse {
-    Transaction::delete_by_account(db.clone(), path.clone())
-        .await
-        .unwrap();
-    Account::delete(db.clone(), path.clone()).await.unwrap();
+    query: web::Query<DeleteAccountQuery>,
+) -> Result<HttpResponse, AppError> {
+    Account::delete(db.clone(), user.0.id, path.into_inner(), query.move_to).await?;
 
-    HttpResponse::Ok().body("account deleted")
+    Ok(HttpResponse::Ok().body("Account deleted"))
 }
@@ -1,13 +1,16 @@
+use crate::common::models::errors::{AppError, OrNotFound};
+use crate::features::balance_history::models::BalanceSnapshot;
 use crate::features::user::models::User;
 use crate::repository::database::Database;
-use crate::schema::accounts;
+use crate::schema::{accounts, recurring_transactions, transactions};
 use actix_web::web;
 use chrono::DateTime;
+use chrono::NaiveDate;
 use chrono::Utc;
-use diesel::dsl::max;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use validator::{Validate, ValidationError};
 
 #[derive(
     Debug,
@@ -23,105 +26,243 @@ use uuid::Uuid;
 #[diesel(belongs_to(User, foreign_key = user_id))]
 #[diesel(table_name = accounts)]
 pub struct Account {
-    id: Uuid,
-    user_id: Uuid,
-    name: String,
-    currency: String,     // "RUB", "USD", "EUR", "AMD"
-    account_type: String, // "cash", "card", "deposit", "loan"
-    amount: f64,
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub currency: String,     // "RUB", "USD", "EUR", "AMD"
+    pub account_type: String, // "cash", "card", "deposit", "loan" (outstanding debt, negative)
+    pub amount: f64,
     created_date: DateTime<Utc>,
     a_order: i32,
+    pub archived: bool,
 }
 
 impl Account {
-    pub async fn get_all(db: web::Data<Database>, user_id: Uuid) -> Result<Vec<Account>, String> {
-        let accounts = accounts::table
+    pub async fn get_all(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        include_archived: bool,
+    ) -> Result<Vec<Account>, AppError> {
+        let mut query = accounts::table
             .filter(accounts::user_id.eq(user_id))
-            .order(accounts::a_order.asc())
-            .load::<Account>(&mut db.pool.get().unwrap())
-            .expect("Error loading accounts");
+            .into_boxed();
+        if !include_archived {
+            query = query.filter(accounts::archived.eq(false));
+        }
 
-        return Ok(accounts);
+        Ok(query
+            .order(accounts::a_order.asc())
+            .load::<Account>(&mut db.conn()?)?)
     }
 
     pub async fn create(
         db: web::Data<Database>,
         account_data: AccountData,
         user_id: Uuid,
-    ) -> Result<Uuid, String> {
+    ) -> Result<Uuid, AppError> {
+        let conn = &mut db.conn()?;
         let a_order: Option<i32> = accounts::table
-            .select(max(accounts::a_order))
-            .first(&mut db.pool.get().unwrap())
-            .unwrap();
+            .select(diesel::dsl::max(accounts::a_order))
+            .first(conn)?;
 
         let next_order_number = match a_order {
             Some(max_order) => max_order + 1,
             None => 0, // Default to 1 if there are no existing orders
         };
 
-        diesel::insert_into(accounts::table)
+        Ok(diesel::insert_into(accounts::table)
             .values(NewAccount::create(
                 &account_data,
                 user_id.clone(),
                 next_order_number,
             ))
-            .get_result::<Account>(&mut db.pool.get().unwrap())
-            .map(|t| t.id)
-            .map_err(|_| "Failed to create account".to_string())
+            .get_result::<Account>(conn)
+            .map(|t| t.id)?)
     }
 
+    // A balance set by hand isn't part of the ledger, so earlier snapshots no longer add up
     pub async fn update(
         db: web::Data<Database>,
+        user_id: Uuid,
         id: Uuid,
         data: AccountData,
-    ) -> Result<(), String> {
-        diesel::update(accounts::table)
-            .filter(accounts::id.eq(id.clone()))
-            .set(data)
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Failed to update account".to_string())
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_own(conn, user_id, id)?;
+
+        conn.transaction(|conn| {
+            diesel::update(accounts::table)
+                .filter(accounts::id.eq(id))
+                .set(data)
+                .execute(conn)?;
+            BalanceSnapshot::invalidate(conn, id, NaiveDate::MIN)?;
+
+            Ok(())
+        })
     }
 
-    pub async fn update_amount(
-        db: web::Data<Database>,
-        id: Uuid,
-        transaction_type: String,
-        amount: f64,
-    ) -> Result<(), String> {
+    pub fn balance_delta(transaction_type: &str, amount: f64) -> Result<f64, AppError> {
+        match transaction_type {
+            "income" => Ok(amount),
+            "expense" => Ok(-amount),
+            _ => Err(AppError::field(
+                "transaction_type",
+                "transaction:type.errors.invalid",
+            )),
+        }
+    }
+
+    pub fn adjust_amount(conn: &mut PgConnection, id: Uuid, delta: f64) -> QueryResult<()> {
         diesel::update(accounts::table)
-            .filter(accounts::id.eq(id.clone()))
-            .set(accounts::amount.eq(match transaction_type.as_str() {
-                "income" => accounts::amount + amount,
-                "expense" => accounts::amount + -amount,
-                _ => return Err("Invalid transaction type".to_string()),
-            }))
-            .execute(&mut db.pool.get().unwrap())
+            .filter(accounts::id.eq(id))
+            .set(accounts::amount.eq(accounts::amount + delta))
+            .execute(conn)
             .map(|_| ())
-            .map_err(|_| "Failed to update account amount".to_string())
     }
 
     pub async fn reorder(
         db: web::Data<Database>,
+        user_id: Uuid,
         data: Vec<ReorderAccountsData>,
-    ) -> Result<(), String> {
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+
         for d in data {
-            let _ = diesel::update(accounts::table)
+            diesel::update(accounts::table)
                 .filter(accounts::id.eq(d.id))
+                .filter(accounts::user_id.eq(user_id))
                 .set(accounts::a_order.eq(d.a_order))
-                .execute(&mut db.pool.get().unwrap())
-                .map(|_| ())
-                .map_err(|_| "Failed to update account".to_string());
+                .execute(conn)?;
         }
 
         Ok(())
     }
 
-    pub async fn delete(db: web::Data<Database>, id: Uuid) -> Result<(), String> {
-        diesel::delete(accounts::table.filter(accounts::id.eq(id.clone())))
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|err| err.to_string())
+    // Archiving hides the account and pauses its recurring transactions, while its
+    // transactions stay in history and reports
+    pub async fn set_archived(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+        archived: bool,
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_own(conn, user_id, id)?;
+
+        conn.transaction(|conn| {
+            diesel::update(accounts::table.filter(accounts::id.eq(id)))
+                .set(accounts::archived.eq(archived))
+                .execute(conn)?;
+
+            if archived {
+                diesel::update(
+                    recurring_transactions::table.filter(recurring_transactions::account_id.eq(id)),
+                )
+                .set(recurring_transactions::active.eq(false))
+                .execute(conn)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    // Without a target the account's transactions are deleted along with it, otherwise
+    // they move to the target account together with their effect on its balance
+    pub async fn delete(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+        move_to: Option<Uuid>,
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+
+        let account = Self::get_own(conn, user_id, id)?;
+        if let Some(target_id) = move_to {
+            if target_id == id {
+                return Err(AppError::field(
+                    "move_to",
+                    "account:moveTo.errors.sameAccount",
+                ));
+            }
+            let target = Self::get_own(conn, user_id, target_id).map_err(|err| match err {
+                AppError::NotFound(_) => {
+                    AppError::field("move_to", "account:moveTo.errors.notFound")
+                }
+                err => err,
+            })?;
+            if target.archived {
+                return Err(AppError::field("move_to", "account:moveTo.errors.archived"));
+            }
+            if target.currency != account.currency {
+                return Err(AppError::field(
+                    "move_to",
+                    "account:moveTo.errors.currencyMismatch",
+                ));
+            }
+        }
+
+        conn.transaction(|conn| {
+            match move_to {
+                Some(target_id) => {
+                    let moved = transactions::table
+                        .filter(transactions::account_id.eq(id))
+                        .select((transactions::transaction_type, transactions::amount))
+                        .load::<(String, f64)>(conn)?;
+                    let delta = moved
+                        .iter()
+                        .filter_map(|(t, amount)| Self::balance_delta(t, *amount).ok())
+                        .sum::<f64>();
+
+                    // Statement ids already imported into the target would clash
+                    let taken_ids = transactions::table
+                        .filter(transactions::account_id.eq(target_id))
+                        .filter(transactions::external_id.is_not_null())
+                        .select(transactions::external_id)
+                        .load::<Option<String>>(conn)?;
+                    diesel::update(
+                        transactions::table
+                            .filter(transactions::account_id.eq(id))
+                            .filter(transactions::external_id.eq_any(taken_ids)),
+                    )
+                    .set(transactions::external_id.eq(None::<String>))
+                    .execute(conn)?;
+
+                    diesel::update(transactions::table.filter(transactions::account_id.eq(id)))
+                        .set(transactions::account_id.eq(target_id))
+                        .execute(conn)?;
+                    diesel::update(
+                        recurring_transactions::table
+                            .filter(recurring_transactions::account_id.eq(id)),
+                    )
+                    .set(recurring_transactions::account_id.eq(target_id))
+                    .execute(conn)?;
+
+                    Self::adjust_amount(conn, target_id, delta)?;
+                    BalanceSnapshot::invalidate(conn, target_id, NaiveDate::MIN)?;
+                }
+                None => {
+                    diesel::delete(transactions::table.filter(transactions::account_id.eq(id)))
+                        .execute(conn)?;
+                    diesel::delete(
+                        recurring_transactions::table
+                            .filter(recurring_transactions::account_id.eq(id)),
+                    )
+                    .execute(conn)?;
+                }
+            }
+
+            diesel::delete(accounts::table.filter(accounts::id.eq(id))).execute(conn)?;
+
+            Ok(())
+        })
+    }
+
+    pub fn get_own(conn: &mut PgConnection, user_id: Uuid, id: Uuid) -> Result<Account, AppError> {
+        accounts::table
+            .filter(accounts::id.eq(id))
+            .filter(accounts::user_id.eq(user_id))
+            .first::<Account>(conn)
+            .or_not_found("account:errors.notFound")
     }
 }
 
@@ -149,15 +290,45 @@ impl NewAccount {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset)]
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset, Validate)]
 #[diesel(table_name = accounts)]
 pub struct AccountData {
+    #[validate(length(min = 1, max = 100, message = "account:name.errors.length"))]
     name: String,
+    #[validate(custom = "validate_currency")]
     currency: String,
+    #[validate(custom = "validate_account_type")]
     account_type: String,
     amount: f64,
 }
 
+// ISO 4217 code
+fn validate_currency(currency: &str) -> Result<(), ValidationError> {
+    if currency.len() == 3 && currency.chars().all(|c| c.is_ascii_uppercase()) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("account:currency.errors.invalid"))
+    }
+}
+
+fn validate_account_type(account_type: &str) -> Result<(), ValidationError> {
+    if ["cash", "card", "deposit", "loan"].contains(&account_type) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("account:type.errors.invalid"))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AccountsQuery {
+    pub include_archived: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeleteAccountQuery {
+    pub move_to: Option<Uuid>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset)]
 #[diesel(table_name = accounts)]
 pub struct UpdateAccountData {
@@ -0,0 +1,499 @@
+use crate::common::models::errors::{AppError, FormError, OrNotFound};
+use crate::features::account::models::Account;
+use crate::features::transaction::models::{NewTransaction, Transaction};
+use crate::repository::database::Database;
+use crate::schema::{account_terms, accounts, categories, transactions};
+use actix_web::web;
+use chrono::{Months, NaiveDate, NaiveTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    PartialEq,
+)]
+#[diesel(belongs_to(Account, foreign_key = account_id))]
+#[diesel(table_name = account_terms, primary_key(account_id))]
+pub struct AccountTerms {
+    pub account_id: Uuid,
+    pub interest_rate: f64,  // annual, in percent
+    pub compounding: String, // "monthly", "quarterly", "yearly"
+    pub term_months: i32,
+    pub start_date: NaiveDate,
+    pub auto_post: bool,
+    pub category_id: Option<Uuid>, // category of the posted interest transactions
+    pub last_posted_date: Option<NaiveDate>,
+}
+
+impl AccountTerms {
+    pub async fn get(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        account_id: Uuid,
+    ) -> Result<AccountTerms, AppError> {
+        let conn = &mut db.conn()?;
+        Account::get_own(conn, user_id, account_id)?;
+
+        account_terms::table
+            .find(account_id)
+            .first::<AccountTerms>(conn)
+            .or_not_found("accountTerms:errors.notFound")
+    }
+
+    pub async fn set(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        account_id: Uuid,
+        data: AccountTermsData,
+    ) -> Result<AccountTerms, AppError> {
+        let conn = &mut db.conn()?;
+        let account = Account::get_own(conn, user_id, account_id)?;
+        data.validate()?;
+
+        if account.account_type != "deposit" && account.account_type != "loan" {
+            return Err(AppError::field(
+                "",
+                "accountTerms:errors.unsupportedAccount",
+            ));
+        }
+
+        if let Some(category_id) = data.category_id {
+            let category_type = if account.account_type == "deposit" {
+                "income"
+            } else {
+                "expense"
+            };
+            categories::table
+                .filter(categories::id.eq(category_id))
+                .filter(categories::user_id.eq(user_id))
+                .filter(categories::category_type.eq(category_type))
+                .select(categories::id)
+                .first::<Uuid>(conn)
+                .optional()?
+                .ok_or(AppError::field(
+                    "category_id",
+                    "accountTerms:category.errors.wrongType",
+                ))?;
+        } else if data.auto_post {
+            return Err(AppError::field(
+                "category_id",
+                "accountTerms:category.errors.required",
+            ));
+        }
+
+        let terms = NewAccountTerms::create(&data, account_id);
+        diesel::insert_into(account_terms::table)
+            .values(&terms)
+            .on_conflict(account_terms::account_id)
+            .do_update()
+            .set(&terms)
+            .get_result::<AccountTerms>(conn)
+            .map_err(AppError::from)
+    }
+
+    pub async fn delete(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        account_id: Uuid,
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+        Account::get_own(conn, user_id, account_id)?;
+
+        diesel::delete(account_terms::table.find(account_id)).execute(conn)?;
+
+        Ok(())
+    }
+
+    // Projection from the current balance over the periods left in the term: an annuity
+    // amortization for loans, compound interest accrual for deposits
+    pub async fn schedule(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        account_id: Uuid,
+    ) -> Result<InterestSchedule, AppError> {
+        let conn = &mut db.conn()?;
+        let account = Account::get_own(conn, user_id, account_id)?;
+        let terms = account_terms::table
+            .find(account_id)
+            .first::<AccountTerms>(conn)
+            .or_not_found("accountTerms:errors.notFound")?;
+
+        let (payment, entries) = terms.project(
+            account.account_type == "loan",
+            account.amount,
+            Utc::now().date_naive(),
+        );
+
+        Ok(InterestSchedule {
+            account_id,
+            account_type: account.account_type,
+            interest_rate: terms.interest_rate,
+            compounding: terms.compounding.clone(),
+            maturity_date: terms.maturity_date(),
+            payment: payment.map(round2),
+            total_interest: round2(entries.iter().map(|e| e.interest).sum()),
+            entries,
+        })
+    }
+
+    // Posts the interest of every period that ended since the last run. Each posting is
+    // keyed by its period date, so a crash between posting and bookkeeping can't double it.
+    pub async fn post_due_interest(db: web::Data<Database>) -> Result<usize, AppError> {
+        let today = Utc::now().date_naive();
+        let conn = &mut db.conn()?;
+
+        let due = account_terms::table
+            .inner_join(accounts::table)
+            .filter(account_terms::auto_post.eq(true))
+            .filter(accounts::archived.eq(false))
+            .select((AccountTerms::as_select(), Account::as_select()))
+            .load::<(AccountTerms, Account)>(conn)?;
+
+        let mut posted = 0;
+
+        'accounts: for (terms, account) in due {
+            let Some(category_id) = terms.category_id else {
+                continue;
+            };
+            let after = terms.last_posted_date.unwrap_or(terms.start_date);
+
+            for date in terms
+                .period_dates()
+                .into_iter()
+                .filter(|date| *date > after && *date <= today)
+            {
+                let external_id = format!("interest-{}", date);
+                let already_posted = transactions::table
+                    .filter(transactions::account_id.eq(account.id))
+                    .filter(transactions::external_id.eq(&external_id))
+                    .count()
+                    .get_result::<i64>(conn)?
+                    > 0;
+
+                // Balances are read fresh so that each period compounds on the previous one
+                let balance = accounts::table
+                    .find(account.id)
+                    .select(accounts::amount)
+                    .first::<f64>(conn)?;
+                let (transaction_type, base) = if account.account_type == "loan" {
+                    ("expense", -balance)
+                } else {
+                    ("income", balance)
+                };
+                let interest = round2(base * terms.periodic_rate());
+
+                if !already_posted && interest > 0.0 {
+                    let posting = Transaction::post(
+                        conn,
+                        &NewTransaction {
+                            user_id: account.user_id,
+                            account_id: account.id,
+                            category_id,
+                            transaction_type: transaction_type.to_string(),
+                            note: Some("Interest".to_string()),
+                            amount: interest,
+                            created_date: date.and_time(NaiveTime::MIN).and_utc(),
+                            recurring_id: None,
+                            recurring_date: None,
+                            external_id: Some(external_id),
+                        },
+                    );
+                    match posting {
+                        Ok(_) => posted += 1,
+                        Err(err @ AppError::Validation(_)) => {
+                            log::warn!("skipping interest of account {}: {}", account.id, err);
+                            continue 'accounts;
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+
+                diesel::update(account_terms::table.find(account.id))
+                    .set(account_terms::last_posted_date.eq(date))
+                    .execute(conn)?;
+            }
+        }
+
+        Ok(posted)
+    }
+
+    // Periods left after `today` with the regular loan payment, if any
+    fn project(
+        &self,
+        is_loan: bool,
+        amount: f64,
+        today: NaiveDate,
+    ) -> (Option<f64>, Vec<ScheduleEntry>) {
+        let dates = self
+            .period_dates()
+            .into_iter()
+            .filter(|date| *date > today)
+            .collect::<Vec<NaiveDate>>();
+        let rate = self.periodic_rate();
+
+        let mut balance = if is_loan { (-amount).max(0.0) } else { amount };
+        let payment = match (is_loan, dates.len()) {
+            (false, _) | (true, 0) => None,
+            (true, n) if rate == 0.0 => Some(balance / n as f64),
+            (true, n) => Some(balance * rate / (1.0 - (1.0 + rate).powi(-(n as i32)))),
+        };
+
+        let mut entries = vec![];
+        for (i, date) in dates.iter().enumerate() {
+            let interest = balance * rate;
+            let principal = match payment {
+                // The last payment settles whatever rounding left over
+                Some(_) if i == dates.len() - 1 => balance,
+                Some(payment) => payment - interest,
+                None => 0.0,
+            };
+
+            balance = if is_loan {
+                balance - principal
+            } else {
+                balance + interest
+            };
+
+            entries.push(ScheduleEntry {
+                number: i as i32 + 1,
+                date: *date,
+                payment: round2(principal + if is_loan { interest } else { 0.0 }),
+                interest: round2(interest),
+                principal: round2(principal),
+                balance: round2(balance),
+            });
+        }
+
+        (payment, entries)
+    }
+
+    fn months_per_period(&self) -> u32 {
+        match self.compounding.as_str() {
+            "quarterly" => 3,
+            "yearly" => 12,
+            _ => 1,
+        }
+    }
+
+    fn periodic_rate(&self) -> f64 {
+        self.interest_rate / 100.0 * self.months_per_period() as f64 / 12.0
+    }
+
+    // End of every compounding period in the term, a shorter last period included
+    fn period_dates(&self) -> Vec<NaiveDate> {
+        let step = self.months_per_period();
+        let term = self.term_months.max(0) as u32;
+
+        (1..=term.div_ceil(step))
+            .filter_map(|period| {
+                self.start_date
+                    .checked_add_months(Months::new((period * step).min(term)))
+            })
+            .collect()
+    }
+
+    fn maturity_date(&self) -> Option<NaiveDate> {
+        self.start_date
+            .checked_add_months(Months::new(self.term_months.max(0) as u32))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset)]
+#[diesel(table_name = account_terms, treat_none_as_null = true)]
+pub struct NewAccountTerms {
+    pub account_id: Uuid,
+    pub interest_rate: f64,
+    pub compounding: String,
+    pub term_months: i32,
+    pub start_date: NaiveDate,
+    pub auto_post: bool,
+    pub category_id: Option<Uuid>,
+}
+
+impl NewAccountTerms {
+    pub fn create(data: &AccountTermsData, account_id: Uuid) -> Self {
+        Self {
+            account_id,
+            interest_rate: data.interest_rate,
+            compounding: data.compounding.clone(),
+            term_months: data.term_months,
+            start_date: data.start_date,
+            auto_post: data.auto_post,
+            category_id: data.category_id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AccountTermsData {
+    pub interest_rate: f64,
+    pub compounding: String,
+    pub term_months: i32,
+    pub start_date: NaiveDate,
+    #[serde(default)]
+    pub auto_post: bool,
+    pub category_id: Option<Uuid>,
+}
+
+impl AccountTermsData {
+    fn validate(&self) -> Result<(), AppError> {
+        let mut errors = vec![];
+        if !(0.0..100.0).contains(&self.interest_rate) {
+            errors.push(FormError {
+                field: "interest_rate",
+                message: "accountTerms:interestRate.errors.outOfRange",
+            });
+        }
+        if !["monthly", "quarterly", "yearly"].contains(&self.compounding.as_str()) {
+            errors.push(FormError {
+                field: "compounding",
+                message: "accountTerms:compounding.errors.invalid",
+            });
+        }
+        if !(1..=600).contains(&self.term_months) {
+            errors.push(FormError {
+                field: "term_months",
+                message: "accountTerms:termMonths.errors.outOfRange",
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(errors))
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScheduleEntry {
+    pub number: i32,
+    pub date: NaiveDate,
+    pub payment: f64,
+    pub interest: f64,
+    pub principal: f64,
+    pub balance: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InterestSchedule {
+    pub account_id: Uuid,
+    pub account_type: String,
+    pub interest_rate: f64,
+    pub compounding: String,
+    pub maturity_date: Option<NaiveDate>,
+    pub payment: Option<f64>, // regular loan payment
+    pub total_interest: f64,
+    pub entries: Vec<ScheduleEntry>,
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn terms(
+        interest_rate: f64,
+        compounding: &str,
+        term_months: i32,
+        start: NaiveDate,
+    ) -> AccountTerms {
+        AccountTerms {
+            account_id: Uuid::nil(),
+            interest_rate,
+            compounding: compounding.to_string(),
+            term_months,
+            start_date: start,
+            auto_post: false,
+            category_id: None,
+            last_posted_date: None,
+        }
+    }
+
+    #[test]
+    fn quarterly_term_ends_with_a_shorter_period() {
+        let terms = terms(4.0, "quarterly", 10, date(2024, 1, 15));
+
+        assert_eq!(
+            terms.period_dates(),
+            vec![
+                date(2024, 4, 15),
+                date(2024, 7, 15),
+                date(2024, 10, 15),
+                date(2024, 11, 15)
+            ]
+        );
+        assert_eq!(terms.maturity_date(), Some(date(2024, 11, 15)));
+    }
+
+    #[test]
+    fn month_end_start_is_clamped_without_drifting() {
+        let terms = terms(6.0, "monthly", 4, date(2024, 1, 31));
+
+        assert_eq!(
+            terms.period_dates(),
+            vec![
+                date(2024, 2, 29),
+                date(2024, 3, 31),
+                date(2024, 4, 30),
+                date(2024, 5, 31)
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_rate_loan_is_repaid_in_equal_parts() {
+        let terms = terms(0.0, "monthly", 12, date(2024, 1, 1));
+
+        let (payment, entries) = terms.project(true, -1200.0, date(2024, 1, 1));
+
+        assert_eq!(payment, Some(100.0));
+        assert_eq!(entries.len(), 12);
+        assert!(entries
+            .iter()
+            .all(|e| e.interest == 0.0 && e.payment == 100.0));
+        assert_eq!(entries.last().unwrap().balance, 0.0);
+    }
+
+    #[test]
+    fn loan_annuity_settles_the_balance_on_the_last_period() {
+        let terms = terms(12.0, "monthly", 12, date(2024, 1, 1));
+
+        let (payment, entries) = terms.project(true, -1000.0, date(2024, 1, 1));
+
+        assert_eq!(payment.map(round2), Some(88.85));
+        assert_eq!(entries[0].interest, 10.0);
+        assert_eq!(entries.last().unwrap().balance, 0.0);
+        let principal = entries.iter().map(|e| e.principal).sum::<f64>();
+        assert!((principal - 1000.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn deposit_compounds_over_the_periods_left() {
+        let terms = terms(12.0, "yearly", 36, date(2023, 6, 1));
+
+        let (payment, entries) = terms.project(false, 1000.0, date(2024, 6, 1));
+
+        assert_eq!(payment, None);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].number, 1);
+        assert_eq!(entries[0].date, date(2025, 6, 1));
+        assert_eq!(entries[0].balance, 1120.0);
+        assert_eq!(entries[1].balance, 1254.4);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::common::middlewares::auth::AuthenticationService;
+use crate::common::models::errors::AppError;
 use crate::features::achievement::models::Achievement;
 use crate::repository::database::Database;
 use actix::AsyncContext;
@@ -16,9 +17,13 @@ pub fn routes() -> Scope {
 }
 
 #[get("/")]
-async fn get(user: AuthenticationService, db: web::Data<Database>) -> HttpResponse {
-    let result = Achievement::get_all(db.clone(), user.0.id).await.unwrap();
-    HttpResponse::Ok().json(result)
+async fn get(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let result = Achievement::get_all(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(result))
 }
 
 struct MyWs {
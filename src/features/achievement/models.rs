@@ -1,5 +1,7 @@
+use crate::common::models::errors::AppError;
 use crate::diesel::ExpressionMethods;
 use crate::features::habit::models::{HabitDetails, HabitsAchievement, HabitsAchievementEnum};
+use crate::features::savings_goal::models::SavingsAchievementEnum;
 use crate::features::user::models::User;
 use crate::schema::{achievements, habits_achievements};
 use crate::{features::habit::models::Habit, repository::database::Database};
@@ -31,7 +33,7 @@ pub struct Achievement {
     pub user_id: Uuid,
     pub a_order: i32,
     pub key: String,
-    pub achievement_type: String, // "habits",
+    pub achievement_type: String, // "habits", "savings"
     pub completed_date: Option<DateTime<Utc>>,
     pub completed: bool,
     pub created_date: DateTime<Utc>,
@@ -41,17 +43,16 @@ impl Achievement {
     pub async fn get_achievements(
         db: web::Data<Database>,
         user_id: Uuid,
-    ) -> Result<Vec<Achievement>, String> {
-        achievements::table
+    ) -> Result<Vec<Achievement>, AppError> {
+        Ok(achievements::table
             .filter(achievements::user_id.eq(user_id))
-            .load::<Achievement>(&mut db.pool.get().unwrap())
-            .map_err(|_| "Error loading achievements".to_string())
+            .load::<Achievement>(&mut db.conn()?)?)
     }
 
     pub async fn get_all(
         db: web::Data<Database>,
         user_id: Uuid,
-    ) -> Result<Vec<AchievementResult>, String> {
+    ) -> Result<Vec<AchievementResult>, AppError> {
         let achievements: Vec<(Achievement, Option<HabitsAchievement>)> = achievements::table
             .filter(achievements::user_id.eq(user_id))
             .order(achievements::a_order.desc())
@@ -59,8 +60,7 @@ impl Achievement {
                 habits_achievements::table
                     .on(habits_achievements::achievement_id.eq(achievements::id)),
             )
-            .load::<(Achievement, Option<HabitsAchievement>)>(&mut db.pool.get().unwrap())
-            .unwrap();
+            .load::<(Achievement, Option<HabitsAchievement>)>(&mut db.conn()?)?;
 
         let achievements_map: HashMap<Uuid, Achievement> = achievements
             .clone()
@@ -86,8 +86,13 @@ impl Achievement {
         for (_, achievement) in achievements_map.into_iter() {
             let mut progress = vec![];
 
-            let mut completed = false;
-            let mut completed_date = None;
+            // Only habit achievements are tracked per habit
+            let mut completed = achievement.achievement_type != "habits" && achievement.completed;
+            let mut completed_date = if completed {
+                achievement.completed_date
+            } else {
+                None
+            };
 
             for (_, habits_achievement) in habits_achievements_map
                 .clone()
@@ -113,7 +118,7 @@ impl Achievement {
             let achievement = AchievementResult {
                 a_order: achievement.a_order,
                 key: achievement.key.clone(),
-                achievement_type: "habits".to_string(),
+                achievement_type: achievement.achievement_type.clone(),
                 completed_date,
                 completed,
                 progress,
@@ -131,35 +136,63 @@ impl Achievement {
         db: web::Data<Database>,
         user_id: Uuid,
         achievement_type: String,
-    ) -> Result<(), String> {
-        match achievement_type.as_str() {
-            "habits" => {
-                let achievements_to_insert = HabitsAchievementEnum::get_all()
-                    .iter()
-                    .enumerate()
-                    .map(|(index, a)| {
-                        let key = &a.to_string();
-
-                        NewAchivement {
-                            user_id: user_id.clone(),
-                            a_order: index as i32,
-                            key: key.clone(),
-                            achievement_type: achievement_type.clone(),
-                            completed_date: None,
-                            completed: false,
-                        }
-                    })
-                    .collect::<Vec<NewAchivement>>();
-
-                diesel::insert_into(achievements::table)
-                    .values(achievements_to_insert)
-                    .execute(&mut db.pool.get().unwrap())
-                    .unwrap();
-
-                Ok(())
+    ) -> Result<(), AppError> {
+        let keys = match achievement_type.as_str() {
+            "habits" => HabitsAchievementEnum::get_all()
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<String>>(),
+            "savings" => SavingsAchievementEnum::get_all()
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<String>>(),
+            _ => {
+                return Err(AppError::Internal(format!(
+                    "unknown achievement type {}",
+                    achievement_type
+                )))
             }
-            _ => Err("Achievement type not found".to_string()),
-        }
+        };
+
+        let achievements_to_insert = keys
+            .into_iter()
+            .enumerate()
+            .map(|(index, key)| NewAchivement {
+                user_id,
+                a_order: index as i32,
+                key,
+                achievement_type: achievement_type.clone(),
+                completed_date: None,
+                completed: false,
+            })
+            .collect::<Vec<NewAchivement>>();
+
+        diesel::insert_into(achievements::table)
+            .values(achievements_to_insert)
+            .execute(&mut db.conn()?)?;
+
+        Ok(())
+    }
+
+    // Marks an achievement that isn't tracked per habit as completed, returns whether it
+    // was completed just now
+    pub fn complete(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        achievement_type: &str,
+        key: &str,
+    ) -> QueryResult<bool> {
+        diesel::update(achievements::table)
+            .filter(achievements::user_id.eq(user_id))
+            .filter(achievements::achievement_type.eq(achievement_type))
+            .filter(achievements::key.eq(key))
+            .filter(achievements::completed.eq(false))
+            .set(UpdateAchievement {
+                completed: true,
+                completed_date: Some(Utc::now()),
+            })
+            .execute(conn)
+            .map(|updated| updated > 0)
     }
 }
 
@@ -169,7 +202,7 @@ pub struct NewAchivement {
     pub a_order: i32,
     pub user_id: Uuid,
     pub key: String,
-    pub achievement_type: String, // "habits",
+    pub achievement_type: String, // "habits", "savings"
     pub completed_date: Option<DateTime<Utc>>,
     pub completed: bool,
 }
@@ -1,4 +1,4 @@
-use crate::common::models::errors::FormError;
+use crate::common::models::errors::AppError;
 use crate::common::services::crypto::Auth;
 use crate::common::services::hashing::hashing;
 use crate::features::auth::models::LoginData;
@@ -13,34 +13,29 @@ pub fn routes() -> Scope {
 }
 
 #[post("/")]
-async fn login(db: Data<Database>, form: web::Json<LoginData>) -> HttpResponse {
+async fn login(db: Data<Database>, form: web::Json<LoginData>) -> Result<HttpResponse, AppError> {
     let username = form.username.clone();
     let password = form.password.clone();
 
-    let user = match User::get_by_username(db.clone(), username.to_string()).await {
-        Ok(user) => user,
-        Err(_) => {
-            return HttpResponse::BadRequest().json(FormError {
-                field: "username",
-                message: "profile:username.errors.notFound",
-            });
-        }
-    };
+    let user = User::get_by_username(db.clone(), username.to_string())
+        .await
+        .map_err(|err| match err {
+            AppError::NotFound(code) => AppError::field("username", code),
+            err => err,
+        })?;
 
-    let valid = hashing()
+    if !hashing()
         .verify_password(&password, &user.password_hash)
         .await
-        .unwrap();
+    {
+        return Err(AppError::field(
+            "password",
+            "profile:password.errors.invalid",
+        ));
+    }
 
-    if valid {
-        let token = hashing().generate_jwt(user.id).await.unwrap();
+    let token = hashing().generate_jwt(user.id).await?;
 
-        // TODO: make access and refresh tokens
-        HttpResponse::Ok().json(Auth { token })
-    } else {
-        HttpResponse::BadRequest().json(FormError {
-            field: "password",
-            message: "profile:password.errors.invalid",
-        })
-    }
+    // TODO: make access and refresh tokens
+    Ok(HttpResponse::Ok().json(Auth { token }))
 }
@@ -0,0 +1,278 @@
+use crate::common::models::errors::AppError;
+use crate::features::account::models::Account;
+use crate::repository::database::Database;
+use crate::schema::{account_balance_snapshots, accounts};
+use actix_web::web;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{Date, Float8, Nullable, Timestamptz};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+const MAX_HISTORY_DAYS: i64 = 3660;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = account_balance_snapshots)]
+pub struct BalanceSnapshot {
+    pub account_id: Uuid,
+    pub date: NaiveDate,
+    pub balance: f64, // balance at the end of `date`, UTC
+}
+
+impl BalanceSnapshot {
+    // Records yesterday's closing balance of every account, accounts that already have
+    // one are skipped so the job can safely run many times a day
+    pub async fn record_daily(db: web::Data<Database>) -> Result<usize, AppError> {
+        let today = Utc::now().date_naive();
+
+        sql_query(
+            "INSERT INTO account_balance_snapshots (account_id, date, balance)
+             SELECT a.id, $1,
+                    a.amount - COALESCE(SUM(CASE WHEN t.transaction_type = 'income' THEN t.amount ELSE -t.amount END), 0)
+             FROM accounts a
+             LEFT JOIN transactions t ON t.account_id = a.id AND t.created_date >= $2
+             GROUP BY a.id, a.amount
+             ON CONFLICT (account_id, date) DO NOTHING",
+        )
+        .bind::<Date, _>(today - Duration::days(1))
+        .bind::<Timestamptz, _>(day_start(today))
+        .execute(&mut db.conn()?)
+        .map_err(AppError::from)
+    }
+
+    // Drops the snapshots a change dated `from` makes stale
+    pub fn invalidate(
+        conn: &mut PgConnection,
+        account_id: Uuid,
+        from: NaiveDate,
+    ) -> QueryResult<()> {
+        diesel::delete(
+            account_balance_snapshots::table
+                .filter(account_balance_snapshots::account_id.eq(account_id))
+                .filter(account_balance_snapshots::date.ge(from)),
+        )
+        .execute(conn)
+        .map(|_| ())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl HistoryQuery {
+    // Defaults to the last 30 days
+    pub fn range(&self) -> Result<(NaiveDate, NaiveDate), AppError> {
+        let to = self.to.unwrap_or_else(|| Utc::now().date_naive());
+        let from = self.from.unwrap_or(to - Duration::days(29));
+
+        if from > to {
+            return Err(AppError::field(
+                "from",
+                "balanceHistory:from.errors.afterTo",
+            ));
+        }
+        if (to - from).num_days() >= MAX_HISTORY_DAYS {
+            return Err(AppError::field(
+                "from",
+                "balanceHistory:from.errors.tooLong",
+            ));
+        }
+
+        Ok((from, to))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BalancePoint {
+    pub date: NaiveDate,
+    pub balance: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AccountHistory {
+    pub account: Account,
+    pub points: Vec<BalancePoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NetWorthPoint {
+    pub date: NaiveDate,
+    pub assets: f64,
+    pub liabilities: f64,
+    pub net_worth: f64,
+}
+
+// Balances in different currencies are never added up, each currency gets its own series
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NetWorthHistory {
+    pub currency: String,
+    pub points: Vec<NetWorthPoint>,
+}
+
+#[derive(Debug, QueryableByName)]
+struct DailyDelta {
+    #[diesel(sql_type = Date)]
+    date: NaiveDate,
+    #[diesel(sql_type = Float8)]
+    delta: f64,
+}
+
+#[derive(Debug, QueryableByName)]
+struct LedgerDelta {
+    #[diesel(sql_type = Nullable<Float8>)]
+    delta: Option<f64>,
+}
+
+pub struct BalanceHistory;
+
+impl BalanceHistory {
+    pub async fn for_account(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        account_id: Uuid,
+        query: HistoryQuery,
+    ) -> Result<AccountHistory, AppError> {
+        let (from, to) = query.range()?;
+        let conn = &mut db.conn()?;
+
+        let account = Account::get_own(conn, user_id, account_id)?;
+
+        let points = Self::daily(conn, &account, from, to)?;
+
+        Ok(AccountHistory { account, points })
+    }
+
+    // Loan accounts hold the outstanding debt as a negative balance
+    pub async fn net_worth(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        query: HistoryQuery,
+    ) -> Result<Vec<NetWorthHistory>, AppError> {
+        let (from, to) = query.range()?;
+        let conn = &mut db.conn()?;
+
+        let accounts = accounts::table
+            .filter(accounts::user_id.eq(user_id))
+            .order(accounts::a_order.asc())
+            .load::<Account>(conn)?;
+
+        let mut by_currency = BTreeMap::<String, Vec<NetWorthPoint>>::new();
+        for account in &accounts {
+            let daily = Self::daily(conn, account, from, to)?;
+            let points = by_currency
+                .entry(account.currency.clone())
+                .or_insert_with(|| {
+                    daily
+                        .iter()
+                        .map(|p| NetWorthPoint {
+                            date: p.date,
+                            assets: 0.0,
+                            liabilities: 0.0,
+                            net_worth: 0.0,
+                        })
+                        .collect()
+                });
+
+            for (point, day) in points.iter_mut().zip(daily) {
+                if account.account_type == "loan" {
+                    point.liabilities -= day.balance;
+                } else {
+                    point.assets += day.balance;
+                }
+                point.net_worth = point.assets - point.liabilities;
+            }
+        }
+
+        Ok(by_currency
+            .into_iter()
+            .map(|(currency, points)| NetWorthHistory { currency, points })
+            .collect())
+    }
+
+    // Closing balance of every day in [from, to]. The walk starts from the latest snapshot
+    // before `from` when there is one, otherwise from the current balance backwards.
+    fn daily(
+        conn: &mut PgConnection,
+        account: &Account,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<BalancePoint>, AppError> {
+        let snapshot = account_balance_snapshots::table
+            .filter(account_balance_snapshots::account_id.eq(account.id))
+            .filter(account_balance_snapshots::date.lt(from))
+            .order(account_balance_snapshots::date.desc())
+            .first::<BalanceSnapshot>(conn)
+            .optional()?;
+
+        let opening = match snapshot {
+            Some(s) => {
+                s.balance
+                    + Self::ledger_delta(
+                        conn,
+                        account.id,
+                        day_start(s.date + Duration::days(1)),
+                        Some(day_start(from)),
+                    )?
+            }
+            None => account.amount - Self::ledger_delta(conn, account.id, day_start(from), None)?,
+        };
+
+        let deltas = sql_query(
+            "SELECT (t.created_date AT TIME ZONE 'UTC')::date AS date,
+                    SUM(CASE WHEN t.transaction_type = 'income' THEN t.amount ELSE -t.amount END) AS delta
+             FROM transactions t
+             WHERE t.account_id = $1
+               AND t.created_date >= $2
+               AND t.created_date < $3
+             GROUP BY 1",
+        )
+        .bind::<diesel::sql_types::Uuid, _>(account.id)
+        .bind::<Timestamptz, _>(day_start(from))
+        .bind::<Timestamptz, _>(day_start(to + Duration::days(1)))
+        .load::<DailyDelta>(conn)?
+        .into_iter()
+        .map(|d| (d.date, d.delta))
+        .collect::<HashMap<NaiveDate, f64>>();
+
+        let mut balance = opening;
+        Ok(from
+            .iter_days()
+            .take_while(|date| *date <= to)
+            .map(|date| {
+                balance += deltas.get(&date).unwrap_or(&0.0);
+                BalancePoint { date, balance }
+            })
+            .collect())
+    }
+
+    // Net effect on the balance of the transactions in [from, to)
+    fn ledger_delta(
+        conn: &mut PgConnection,
+        account_id: Uuid,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<f64, AppError> {
+        sql_query(
+            "SELECT SUM(CASE WHEN t.transaction_type = 'income' THEN t.amount ELSE -t.amount END) AS delta
+             FROM transactions t
+             WHERE t.account_id = $1
+               AND t.created_date >= $2
+               AND ($3::timestamptz IS NULL OR t.created_date < $3)",
+        )
+        .bind::<diesel::sql_types::Uuid, _>(account_id)
+        .bind::<Timestamptz, _>(from)
+        .bind::<Nullable<Timestamptz>, _>(to)
+        .get_result::<LedgerDelta>(conn)
+        .map(|d| d.delta.unwrap_or(0.0))
+        .map_err(AppError::from)
+    }
+}
+
+fn day_start(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc()
+}
@@ -0,0 +1,90 @@
+use crate::common::middlewares::auth::AuthenticationService;
+use crate::common::models::errors::AppError;
+use crate::features::budget::models::{Budget, BudgetData, BudgetStatus};
+use crate::repository::database::Database;
+use actix_web::{delete, get, post, put, web, HttpResponse, Scope};
+use uuid::Uuid;
+
+pub fn routes() -> Scope {
+    web::scope("/budget")
+        .service(get_budgets)
+        .service(get_budgets_status)
+        .service(get_budget_status)
+        .service(create_budget)
+        .service(update_budget)
+        .service(delete_budget)
+}
+
+#[get("")]
+async fn get_budgets(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let budgets = Budget::get_all(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(budgets))
+}
+
+#[get("/status")]
+async fn get_budgets_status(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let budgets = Budget::get_all(db.clone(), user.0.id).await?;
+
+    let mut statuses: Vec<BudgetStatus> = vec![];
+    for budget in budgets {
+        statuses.push(Budget::get_status(db.clone(), budget).await?);
+    }
+
+    Ok(HttpResponse::Ok().json(statuses))
+}
+
+#[get("/{id}/status")]
+async fn get_budget_status(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let budget = Budget::get_details(db.clone(), user.0.id, path.into_inner()).await?;
+    let status = Budget::get_status(db.clone(), budget).await?;
+
+    Ok(HttpResponse::Ok().json(status))
+}
+
+#[post("")]
+async fn create_budget(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    form: web::Json<BudgetData>,
+) -> Result<HttpResponse, AppError> {
+    let id = Budget::create(db.clone(), user.0.id, form.into_inner()).await?;
+    let budget = Budget::get_details(db.clone(), user.0.id, id).await?;
+
+    Ok(HttpResponse::Ok().json(budget))
+}
+
+#[put("/{id}")]
+async fn update_budget(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+    form: web::Json<BudgetData>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    Budget::update(db.clone(), user.0.id, id, form.into_inner()).await?;
+    let budget = Budget::get_details(db.clone(), user.0.id, id).await?;
+
+    Ok(HttpResponse::Ok().json(budget))
+}
+
+#[delete("/{id}")]
+async fn delete_budget(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    Budget::delete(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("Budget deleted"))
+}
@@ -0,0 +1,443 @@
+use crate::common::models::errors::{AppError, FormError, OrNotFound};
+use crate::features::category::models::Category;
+use crate::features::transaction::models::CATEGORY_LINES_SQL;
+use crate::features::user::models::User;
+use crate::repository::database::Database;
+use crate::schema::{budget_categories, budgets, categories};
+use actix_web::web;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{Array, Date, Float8, Text, Timestamptz};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    PartialEq,
+)]
+#[diesel(belongs_to(User, foreign_key = user_id))]
+#[diesel(table_name = budgets)]
+pub struct Budget {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub amount: f64,
+    pub period: String, // "monthly", "weekly"
+    pub rollover: bool,
+    pub created_date: DateTime<Utc>,
+}
+
+impl Budget {
+    pub async fn get_all(
+        db: web::Data<Database>,
+        user_id: Uuid,
+    ) -> Result<Vec<BudgetDetails>, AppError> {
+        let conn = &mut db.conn()?;
+
+        let budgets_list: Vec<Budget> = budgets::table
+            .filter(budgets::user_id.eq(user_id))
+            .order(budgets::created_date.asc())
+            .load::<Budget>(conn)?;
+
+        let categories_list: Vec<Vec<BudgetCategory>> = BudgetCategory::belonging_to(&budgets_list)
+            .load::<BudgetCategory>(conn)?
+            .grouped_by(&budgets_list);
+
+        Ok(budgets_list
+            .into_iter()
+            .zip(categories_list)
+            .map(|(b, c)| BudgetDetails::parse(b, c))
+            .collect())
+    }
+
+    pub async fn get_details(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<BudgetDetails, AppError> {
+        let conn = &mut db.conn()?;
+
+        let budget = budgets::table
+            .filter(budgets::id.eq(id))
+            .filter(budgets::user_id.eq(user_id))
+            .first::<Budget>(conn)
+            .or_not_found("budget:errors.notFound")?;
+
+        let categories_list = BudgetCategory::belonging_to(&budget).load::<BudgetCategory>(conn)?;
+
+        Ok(BudgetDetails::parse(budget, categories_list))
+    }
+
+    pub async fn create(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        data: BudgetData,
+    ) -> Result<Uuid, AppError> {
+        data.validate()?;
+
+        let conn = &mut db.conn()?;
+        let category_ids = Self::own_categories(conn, user_id, &data.category_ids)?;
+
+        conn.transaction(|conn| {
+            let budget = diesel::insert_into(budgets::table)
+                .values(NewBudget::create(&data, user_id))
+                .get_result::<Budget>(conn)?;
+
+            Self::set_categories(conn, budget.id, &category_ids)?;
+
+            Ok(budget.id)
+        })
+    }
+
+    pub async fn update(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+        data: BudgetData,
+    ) -> Result<(), AppError> {
+        data.validate()?;
+
+        let conn = &mut db.conn()?;
+        let category_ids = Self::own_categories(conn, user_id, &data.category_ids)?;
+
+        conn.transaction(|conn| {
+            let updated = diesel::update(budgets::table)
+                .filter(budgets::id.eq(id))
+                .filter(budgets::user_id.eq(user_id))
+                .set(UpdateBudgetData::create(&data))
+                .execute(conn)?;
+
+            if updated == 0 {
+                return Err(AppError::NotFound("budget:errors.notFound"));
+            }
+
+            diesel::delete(budget_categories::table.filter(budget_categories::budget_id.eq(id)))
+                .execute(conn)?;
+            Self::set_categories(conn, id, &category_ids)?;
+
+            Ok(())
+        })
+    }
+
+    pub async fn delete(db: web::Data<Database>, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        diesel::delete(
+            budgets::table
+                .filter(budgets::id.eq(id))
+                .filter(budgets::user_id.eq(user_id)),
+        )
+        .execute(&mut db.conn()?)?;
+
+        Ok(())
+    }
+
+    pub async fn get_status(
+        db: web::Data<Database>,
+        budget: BudgetDetails,
+    ) -> Result<BudgetStatus, AppError> {
+        let today = Utc::now().date_naive();
+        let period_start = BudgetPeriod::start(&budget.period, today);
+        let period_end = BudgetPeriod::next(&budget.period, period_start);
+
+        // Rollover carries what was left (or overspent) in every previous period
+        // since the budget was created into the current one
+        let first_period = if budget.rollover {
+            BudgetPeriod::start(&budget.period, budget.created_date.date_naive())
+        } else {
+            period_start
+        };
+
+        let spent_by_period =
+            Self::spent_by_period(db.clone(), &budget, first_period, period_end).await?;
+
+        let mut rollover_amount = 0.0;
+        let mut period = first_period;
+        while period < period_start {
+            rollover_amount += budget.amount - spent_by_period.get(&period).unwrap_or(&0.0);
+            period = BudgetPeriod::next(&budget.period, period);
+        }
+
+        let spent = *spent_by_period.get(&period_start).unwrap_or(&0.0);
+        let available = budget.amount + rollover_amount;
+
+        let total_days = (period_end - period_start).num_days() as f64;
+        let elapsed_days = ((today - period_start).num_days() + 1) as f64;
+        let projected_spent = spent / elapsed_days * total_days;
+
+        Ok(BudgetStatus {
+            period_start,
+            period_end: period_end - Duration::days(1),
+            rollover_amount,
+            available,
+            spent,
+            remaining: available - spent,
+            progress: if available > 0.0 {
+                spent / available * 100.0
+            } else {
+                100.0
+            },
+            projected_spent,
+            projected_overspend: (projected_spent - available).max(0.0),
+            budget,
+        })
+    }
+
+    async fn spent_by_period(
+        db: web::Data<Database>,
+        budget: &BudgetDetails,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<HashMap<NaiveDate, f64>, AppError> {
+        let truncate_to = match budget.period.as_str() {
+            "weekly" => "week",
+            _ => "month",
+        };
+
+        sql_query(format!(
+            "SELECT date_trunc($1, t.created_date)::date AS period,
+                    COALESCE(SUM(t.amount), 0) AS spent
+             FROM {} t
+             INNER JOIN categories c ON c.id = t.category_id
+             WHERE t.user_id = $2
+               AND t.transaction_type = 'expense'
+               AND (t.category_id = ANY($3) OR c.parent_id = ANY($3))
+               AND t.created_date >= $4
+               AND t.created_date < $5
+             GROUP BY period",
+            CATEGORY_LINES_SQL
+        ))
+        .bind::<Text, _>(truncate_to)
+        .bind::<diesel::sql_types::Uuid, _>(budget.user_id)
+        .bind::<Array<diesel::sql_types::Uuid>, _>(&budget.category_ids)
+        .bind::<Timestamptz, _>(from.and_time(NaiveTime::MIN).and_utc())
+        .bind::<Timestamptz, _>(to.and_time(NaiveTime::MIN).and_utc())
+        .load::<PeriodSpending>(&mut db.conn()?)
+        .map(|rows| rows.into_iter().map(|r| (r.period, r.spent)).collect())
+        .map_err(AppError::from)
+    }
+
+    fn own_categories(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        category_ids: &[Uuid],
+    ) -> Result<Vec<Uuid>, AppError> {
+        // A category listed twice is still a single category
+        let category_ids = category_ids.iter().collect::<HashSet<&Uuid>>();
+        let own_ids = categories::table
+            .filter(categories::user_id.eq(user_id))
+            .filter(categories::id.eq_any(category_ids.iter().copied()))
+            .select(categories::id)
+            .load::<Uuid>(conn)?;
+
+        if own_ids.len() != category_ids.len() {
+            return Err(AppError::field(
+                "category_ids",
+                "budget:categories.errors.notFound",
+            ));
+        }
+
+        Ok(own_ids)
+    }
+
+    fn set_categories(
+        conn: &mut PgConnection,
+        budget_id: Uuid,
+        category_ids: &[Uuid],
+    ) -> Result<(), diesel::result::Error> {
+        let rows = category_ids
+            .iter()
+            .map(|category_id| NewBudgetCategory {
+                budget_id,
+                category_id: *category_id,
+            })
+            .collect::<Vec<NewBudgetCategory>>();
+
+        diesel::insert_into(budget_categories::table)
+            .values(rows)
+            .execute(conn)
+            .map(|_| ())
+    }
+}
+
+pub struct BudgetPeriod {}
+
+impl BudgetPeriod {
+    pub fn start(period: &str, date: NaiveDate) -> NaiveDate {
+        match period {
+            "weekly" => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            _ => date.with_day(1).unwrap(),
+        }
+    }
+
+    pub fn next(period: &str, start: NaiveDate) -> NaiveDate {
+        match period {
+            "weekly" => start + Duration::days(7),
+            _ => start + Months::new(1),
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    PartialEq,
+)]
+#[diesel(belongs_to(Budget, foreign_key = budget_id))]
+#[diesel(belongs_to(Category, foreign_key = category_id))]
+#[diesel(table_name = budget_categories)]
+pub struct BudgetCategory {
+    pub id: Uuid,
+    pub budget_id: Uuid,
+    pub category_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
+#[diesel(table_name = budget_categories)]
+pub struct NewBudgetCategory {
+    pub budget_id: Uuid,
+    pub category_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
+#[diesel(table_name = budgets)]
+pub struct NewBudget {
+    pub user_id: Uuid,
+    pub name: String,
+    pub amount: f64,
+    pub period: String,
+    pub rollover: bool,
+}
+
+impl NewBudget {
+    pub fn create(data: &BudgetData, user_id: Uuid) -> Self {
+        Self {
+            user_id,
+            name: data.name.clone(),
+            amount: data.amount,
+            period: data.period.clone(),
+            rollover: data.rollover,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, AsChangeset)]
+#[diesel(table_name = budgets)]
+pub struct UpdateBudgetData {
+    pub name: String,
+    pub amount: f64,
+    pub period: String,
+    pub rollover: bool,
+}
+
+impl UpdateBudgetData {
+    pub fn create(data: &BudgetData) -> Self {
+        Self {
+            name: data.name.clone(),
+            amount: data.amount,
+            period: data.period.clone(),
+            rollover: data.rollover,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BudgetData {
+    pub name: String,
+    pub amount: f64,
+    pub period: String, // "monthly", "weekly"
+    pub rollover: bool,
+    pub category_ids: Vec<Uuid>,
+}
+
+impl BudgetData {
+    fn validate(&self) -> Result<(), AppError> {
+        let mut errors = vec![];
+        if self.period != "monthly" && self.period != "weekly" {
+            errors.push(FormError {
+                field: "period",
+                message: "budget:period.errors.invalid",
+            });
+        }
+        if self.amount <= 0.0 {
+            errors.push(FormError {
+                field: "amount",
+                message: "budget:amount.errors.notPositive",
+            });
+        }
+        if self.category_ids.is_empty() {
+            errors.push(FormError {
+                field: "category_ids",
+                message: "budget:categories.errors.required",
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(errors))
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BudgetDetails {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub amount: f64,
+    pub period: String,
+    pub rollover: bool,
+    pub created_date: DateTime<Utc>,
+    pub category_ids: Vec<Uuid>,
+}
+
+impl BudgetDetails {
+    pub fn parse(b: Budget, categories: Vec<BudgetCategory>) -> BudgetDetails {
+        BudgetDetails {
+            id: b.id,
+            user_id: b.user_id,
+            name: b.name,
+            amount: b.amount,
+            period: b.period,
+            rollover: b.rollover,
+            created_date: b.created_date,
+            category_ids: categories.into_iter().map(|c| c.category_id).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BudgetStatus {
+    pub budget: BudgetDetails,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub rollover_amount: f64,
+    pub available: f64,
+    pub spent: f64,
+    pub remaining: f64,
+    pub progress: f64,
+    pub projected_spent: f64,
+    pub projected_overspend: f64,
+}
+
+#[derive(Debug, QueryableByName)]
+struct PeriodSpending {
+    #[diesel(sql_type = Date)]
+    period: NaiveDate,
+    #[diesel(sql_type = Float8)]
+    spent: f64,
+}
@@ -1,5 +1,8 @@
-use crate::features::category::models::{Category, CategoryData, ReorderCategoriesData};
-use crate::features::transaction::models::Transaction;
+use crate::common::middlewares::validated_json::ValidatedJson;
+use crate::common::models::errors::AppError;
+use crate::features::category::models::{
+    Category, CategoryData, DeleteCategoryQuery, ReorderCategoriesData,
+};
 use crate::{common::middlewares::auth::AuthenticationService, repository::database::Database};
 use actix_web::{delete, get, post, put, web, HttpResponse, Scope};
 use uuid::Uuid;
@@ -15,13 +18,13 @@ pub fn routes() -> Scope {
 }
 
 #[get("")]
-async fn get_categories(user: AuthenticationService, db: web::Data<Database>) -> HttpResponse {
-    let categories = Category::get_all(db.clone(), user.0.id).await;
+async fn get_categories(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let categories = Category::get_all(db.clone(), user.0.id).await?;
 
-    match categories {
-        Ok(categories) => HttpResponse::Ok().json(categories),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+    Ok(HttpResponse::Ok().json(categories))
 }
 
 #[get("{category_id}")]
@@ -29,38 +32,33 @@ async fn get_category(
     _: AuthenticationService,
     db: web::Data<Database>,
     path: web::Path<Uuid>,
-) -> HttpResponse {
-    let categories = Category::get_by_id(db.clone(), path.into_inner()).await;
+) -> Result<HttpResponse, AppError> {
+    let category = Category::get_by_id(db.clone(), path.into_inner()).await?;
 
-    match categories {
-        Ok(categories) => HttpResponse::Ok().json(categories),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+    Ok(HttpResponse::Ok().json(category))
 }
 
 #[post("")]
 async fn create_category(
     user: AuthenticationService,
     db: web::Data<Database>,
-    form: web::Json<CategoryData>,
-) -> HttpResponse {
-    match Category::create(db.clone(), form.into_inner(), user.0.id, None).await {
-        Ok(_) => HttpResponse::Ok().body("Category created"),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+    form: ValidatedJson<CategoryData>,
+) -> Result<HttpResponse, AppError> {
+    Category::create(db.clone(), form.into_inner(), user.0.id, None).await?;
+
+    Ok(HttpResponse::Ok().body("Category created"))
 }
 
 #[put("/{id}")]
 async fn update_category(
-    _user: AuthenticationService,
+    user: AuthenticationService,
     db: web::Data<Database>,
     path: web::Path<Uuid>,
-    form: web::Json<CategoryData>,
-) -> HttpResponse {
-    match Category::update(db.clone(), path.clone(), form.into_inner()).await {
-        Ok(_) => HttpResponse::Ok().body("Category updated"),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+    form: ValidatedJson<CategoryData>,
+) -> Result<HttpResponse, AppError> {
+    Category::update(db.clone(), user.0.id, path.clone(), form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("Category updated"))
 }
 
 #[post("/reorder")]
@@ -68,23 +66,38 @@ async fn reorder_categories(
     user: AuthenticationService,
     db: web::Data<Database>,
     form: web::Json<Vec<ReorderCategoriesData>>,
-) -> HttpResponse {
-    match Category::reorder(db.clone(), form.into_inner()).await {
-        Ok(_) => HttpResponse::Ok().json(Category::get_all(db.clone(), user.0.id).await.unwrap()),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+) -> Result<HttpResponse, AppError> {
+    Category::reorder(db.clone(), user.0.id, form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(Category::get_all(db.clone(), user.0.id).await?))
 }
 
 #[delete("{category_id}")]
 async fn delete_category(
-    _: AuthenticationService,
+    user: AuthenticationService,
     db: web::Data<Database>,
     path: web::Path<Uuid>,
-) -> HttpResponse {
-    let category_id = Category::delete(db.clone(), path.clone()).await.unwrap();
+    query: web::Query<DeleteCategoryQuery>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    let category_id = path.into_inner();
+
+    let message = match (query.reassign_to, query.archive.unwrap_or(false)) {
+        (Some(reassign_to), false) => {
+            Category::delete(db.clone(), user.0.id, category_id, reassign_to)
+                .await
+                .map(|_| "Category deleted")?
+        }
+        (None, true) => Category::archive(db.clone(), user.0.id, category_id)
+            .await
+            .map(|_| "Category archived")?,
+        _ => {
+            return Err(AppError::field(
+                "reassign_to",
+                "category:reassignTo.errors.required",
+            ))
+        }
+    };
 
-    match Transaction::delete_by_category(db.clone(), category_id).await {
-        Ok(_) => HttpResponse::Ok().body("Category deleted"),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+    Ok(HttpResponse::Ok().body(message))
 }
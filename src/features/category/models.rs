@@ -1,13 +1,17 @@
+use crate::common::models::errors::{AppError, OrNotFound};
 use crate::features::user::models::User;
 use crate::repository::database::Database;
-use crate::schema::categories;
+use crate::schema::{
+    account_terms, budget_categories, categories, import_rules, recurring_transactions,
+    transaction_splits, transactions,
+};
 use actix_web::web;
 use chrono::DateTime;
 use chrono::Utc;
-use diesel::dsl::max;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use validator::{Validate, ValidationError};
 
 #[derive(
     Debug,
@@ -26,75 +30,75 @@ pub struct Category {
     pub id: Uuid,
     user_id: Uuid,
     pub category_type: String, // "income", "expense"
-    name: String,
+    pub name: String,
     icon: String,
     color: String,
     is_default: bool,
     created_date: DateTime<Utc>,
     modified_date: Option<DateTime<Utc>>,
     c_order: i32,
+    pub parent_id: Option<Uuid>,
+    pub archived: bool,
 }
 
 impl Category {
-    pub async fn get_by_id(db: web::Data<Database>, id: Uuid) -> Result<Category, String> {
+    pub async fn get_by_id(db: web::Data<Database>, id: Uuid) -> Result<Category, AppError> {
         categories::table
             .filter(categories::id.eq(id))
-            .first::<Category>(&mut db.pool.get().unwrap())
-            .map_err(|_| "Category not found".to_string())
+            .first::<Category>(&mut db.conn()?)
+            .or_not_found("category:errors.notFound")
     }
 
     pub async fn get_all(
         client: web::Data<Database>,
         user_id: Uuid,
-    ) -> Result<CategoriesResult, String> {
+    ) -> Result<CategoriesResult, AppError> {
         let categories = Self::get_all_raw(client, user_id).await?;
 
-        let income_categories: Vec<Category> = categories
-            .iter()
-            .filter(|c| c.category_type == "income")
-            .cloned()
-            .collect();
-
-        let expense_categories: Vec<Category> = categories
-            .iter()
-            .filter(|c| c.category_type == "expense")
-            .cloned()
-            .collect();
-
         Ok(CategoriesResult {
-            income: income_categories,
-            expense: expense_categories,
+            income: Self::tree(&categories, "income"),
+            expense: Self::tree(&categories, "expense"),
         })
     }
 
+    // Top-level categories of the given type with their subcategories, both in `c_order`
+    fn tree(categories: &[Category], category_type: &str) -> Vec<CategoryNode> {
+        categories
+            .iter()
+            .filter(|c| c.category_type == category_type && c.parent_id.is_none())
+            .map(|c| CategoryNode {
+                category: c.clone(),
+                children: categories
+                    .iter()
+                    .filter(|child| child.parent_id == Some(c.id))
+                    .cloned()
+                    .collect(),
+            })
+            .collect()
+    }
+
     pub async fn get_all_raw(
         db: web::Data<Database>,
         user_id: Uuid,
-    ) -> Result<Vec<Category>, String> {
-        categories::table
+    ) -> Result<Vec<Category>, AppError> {
+        Ok(categories::table
             .filter(categories::user_id.eq(user_id))
+            .filter(categories::archived.eq(false))
             .order(categories::c_order.asc())
-            .load::<Category>(&mut db.pool.get().unwrap())
-            .map_err(|_| "Error loading categories".to_string())
+            .load::<Category>(&mut db.conn()?)?)
     }
     pub async fn create(
         db: web::Data<Database>,
         category_data: CategoryData,
         user_id: Uuid,
         c_order: Option<i32>,
-    ) -> Result<Uuid, String> {
+    ) -> Result<Uuid, AppError> {
+        let conn = &mut db.conn()?;
+        Self::check_parent(conn, user_id, None, &category_data)?;
+
         let next_order_number: i32 = match c_order {
             Some(c_order) => c_order,
-            None => {
-                let max_order: Option<i32> = categories::table
-                    .select(max(categories::c_order))
-                    .first(&mut db.pool.get().unwrap())
-                    .unwrap();
-                match max_order {
-                    Some(max_order) => max_order + 1,
-                    None => 0, // Default to 0 if there are no existing orders
-                }
-            }
+            None => Self::next_order(conn, user_id, category_data.parent_id)?,
         };
 
         diesel::insert_into(categories::table)
@@ -103,12 +107,12 @@ impl Category {
                 user_id.clone(),
                 next_order_number,
             ))
-            .get_result::<Category>(&mut db.pool.get().unwrap())
+            .get_result::<Category>(conn)
             .map(|t| t.id)
-            .map_err(|_| "Failed to create category".to_string())
+            .map_err(AppError::from)
     }
 
-    pub async fn create_default(db: web::Data<Database>, user_id: Uuid) -> Result<(), String> {
+    pub async fn create_default(db: web::Data<Database>, user_id: Uuid) -> Result<(), AppError> {
         let income_categories = vec![
             ("salary", "orange"),
             ("freelance", "yellow"),
@@ -138,6 +142,7 @@ impl Category {
                     color: color.to_string(),
                     icon: name.to_string(),
                     is_default: true,
+                    parent_id: None,
                 },
                 user_id.clone(),
                 Some(index as i32),
@@ -154,6 +159,7 @@ impl Category {
                     color: color.to_string(),
                     icon: name.to_string(),
                     is_default: true,
+                    parent_id: None,
                 },
                 user_id.clone(),
                 Some(index as i32),
@@ -166,39 +172,278 @@ impl Category {
 
     pub async fn update(
         db: web::Data<Database>,
+        user_id: Uuid,
         id: Uuid,
         data: CategoryData,
-    ) -> Result<(), String> {
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+        let category = Self::get_own(conn, user_id, id)?;
+        Self::check_parent(conn, user_id, Some(id), &data)?;
+
+        // A category moved under another parent goes to the end of its new siblings
+        if category.parent_id != data.parent_id {
+            let c_order = Self::next_order(conn, user_id, data.parent_id)?;
+            diesel::update(categories::table)
+                .filter(categories::id.eq(id))
+                .set(categories::c_order.eq(c_order))
+                .execute(conn)?;
+        }
+
         diesel::update(categories::table)
             .filter(categories::id.eq(id.clone()))
             .set(data)
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Failed to update category".to_string())
+            .execute(conn)?;
+
+        Ok(())
     }
 
     pub async fn reorder(
         db: web::Data<Database>,
+        user_id: Uuid,
         data: Vec<ReorderCategoriesData>,
-    ) -> Result<(), String> {
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+
         for d in data {
-            let _ = diesel::update(categories::table)
+            diesel::update(categories::table)
                 .filter(categories::id.eq(d.id))
+                .filter(categories::user_id.eq(user_id))
                 .set(categories::c_order.eq(d.c_order))
-                .execute(&mut db.pool.get().unwrap())
-                .map(|_| ())
-                .map_err(|_| "Failed to update categories".to_string());
+                .execute(conn)?;
+        }
+
+        Ok(())
+    }
+
+    // Everything filed under the deleted category moves to the target, so the ledger and
+    // account balances are left untouched. Subcategories of a deleted parent are kept and
+    // moved to the top level, after the existing top-level categories
+    pub async fn delete(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+        reassign_to: Uuid,
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+
+        let category = Self::get_own(conn, user_id, id)?;
+        if reassign_to == id {
+            return Err(AppError::field(
+                "reassign_to",
+                "category:reassignTo.errors.sameCategory",
+            ));
+        }
+        let target = Self::get_own(conn, user_id, reassign_to).map_err(|err| match err {
+            AppError::NotFound(_) => {
+                AppError::field("reassign_to", "category:reassignTo.errors.notFound")
+            }
+            err => err,
+        })?;
+        if target.archived {
+            return Err(AppError::field(
+                "reassign_to",
+                "category:reassignTo.errors.archived",
+            ));
+        }
+        if target.category_type != category.category_type {
+            return Err(AppError::field(
+                "reassign_to",
+                "category:reassignTo.errors.typeMismatch",
+            ));
+        }
+        Self::check_not_last(conn, &category, false)?;
+
+        conn.transaction(|conn| {
+            diesel::update(transactions::table.filter(transactions::category_id.eq(id)))
+                .set(transactions::category_id.eq(reassign_to))
+                .execute(conn)?;
+            diesel::update(
+                transaction_splits::table.filter(transaction_splits::category_id.eq(id)),
+            )
+            .set(transaction_splits::category_id.eq(reassign_to))
+            .execute(conn)?;
+            diesel::update(
+                recurring_transactions::table.filter(recurring_transactions::category_id.eq(id)),
+            )
+            .set(recurring_transactions::category_id.eq(reassign_to))
+            .execute(conn)?;
+            diesel::update(import_rules::table.filter(import_rules::category_id.eq(id)))
+                .set(import_rules::category_id.eq(reassign_to))
+                .execute(conn)?;
+            diesel::update(account_terms::table.filter(account_terms::category_id.eq(id)))
+                .set(account_terms::category_id.eq(reassign_to))
+                .execute(conn)?;
+
+            // Budgets already tracking the target just drop the deleted category
+            let covered_budgets = budget_categories::table
+                .filter(budget_categories::category_id.eq(reassign_to))
+                .select(budget_categories::budget_id)
+                .load::<Uuid>(conn)?;
+            diesel::delete(
+                budget_categories::table
+                    .filter(budget_categories::category_id.eq(id))
+                    .filter(budget_categories::budget_id.eq_any(covered_budgets)),
+            )
+            .execute(conn)?;
+            diesel::update(budget_categories::table.filter(budget_categories::category_id.eq(id)))
+                .set(budget_categories::category_id.eq(reassign_to))
+                .execute(conn)?;
+
+            let offset = categories::table
+                .filter(categories::user_id.eq(user_id))
+                .filter(categories::parent_id.is_null())
+                .select(diesel::dsl::max(categories::c_order))
+                .first::<Option<i32>>(conn)?
+                .map_or(0, |max_order| max_order + 1);
+
+            diesel::update(categories::table.filter(categories::parent_id.eq(id)))
+                .set((
+                    categories::parent_id.eq(None::<Uuid>),
+                    categories::c_order.eq(categories::c_order + offset),
+                ))
+                .execute(conn)?;
+
+            diesel::delete(categories::table.filter(categories::id.eq(id))).execute(conn)?;
+
+            Ok(())
+        })
+    }
+
+    // Archiving hides the category and its subcategories, their transactions stay as they are
+    pub async fn archive(db: web::Data<Database>, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+
+        let category = Self::get_own(conn, user_id, id)?;
+        Self::check_not_last(conn, &category, true)?;
+
+        diesel::update(categories::table)
+            .filter(categories::id.eq(id).or(categories::parent_id.eq(id)))
+            .set(categories::archived.eq(true))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn get_own(conn: &mut PgConnection, user_id: Uuid, id: Uuid) -> Result<Category, AppError> {
+        categories::table
+            .filter(categories::id.eq(id))
+            .filter(categories::user_id.eq(user_id))
+            .first::<Category>(conn)
+            .or_not_found("category:errors.notFound")
+    }
+
+    // Every user keeps at least one active income and one active expense category
+    fn check_not_last(
+        conn: &mut PgConnection,
+        category: &Category,
+        with_children: bool,
+    ) -> Result<(), AppError> {
+        let mut query = categories::table
+            .filter(categories::user_id.eq(category.user_id))
+            .filter(categories::category_type.eq(&category.category_type))
+            .filter(categories::archived.eq(false))
+            .filter(categories::id.ne(category.id))
+            .into_boxed();
+        if with_children {
+            query = query.filter(categories::parent_id.is_distinct_from(category.id));
+        }
+
+        let remaining = query.count().get_result::<i64>(conn)?;
+
+        if remaining == 0 {
+            return Err(AppError::Conflict("category:errors.lastOfType"));
         }
 
         Ok(())
     }
 
-    pub async fn delete(db: web::Data<Database>, id: Uuid) -> Result<Uuid, String> {
-        let category = categories::table.filter(categories::id.eq(id));
-        diesel::delete(category)
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| id)
-            .map_err(|_| "Error deleting category".to_string())
+    fn next_order(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        parent_id: Option<Uuid>,
+    ) -> Result<i32, AppError> {
+        categories::table
+            .filter(categories::user_id.eq(user_id))
+            .filter(categories::parent_id.is_not_distinct_from(parent_id))
+            .select(diesel::dsl::max(categories::c_order))
+            .first::<Option<i32>>(conn)
+            .map(|max_order| max_order.map_or(0, |max_order| max_order + 1))
+            .map_err(AppError::from)
+    }
+
+    // Only one level of nesting is allowed, and a subcategory shares its parent's type
+    fn check_parent(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        id: Option<Uuid>,
+        data: &CategoryData,
+    ) -> Result<(), AppError> {
+        let has_children = match id {
+            Some(id) => {
+                categories::table
+                    .filter(categories::parent_id.eq(id))
+                    .count()
+                    .get_result::<i64>(conn)?
+                    > 0
+            }
+            None => false,
+        };
+
+        if has_children {
+            let category_type = categories::table
+                .filter(categories::id.eq(id.unwrap()))
+                .select(categories::category_type)
+                .first::<String>(conn)
+                .or_not_found("category:errors.notFound")?;
+            if category_type != data.category_type {
+                return Err(AppError::field(
+                    "category_type",
+                    "category:categoryType.errors.hasSubcategories",
+                ));
+            }
+        }
+
+        let Some(parent_id) = data.parent_id else {
+            return Ok(());
+        };
+
+        if Some(parent_id) == id {
+            return Err(AppError::field(
+                "parent_id",
+                "category:parentId.errors.self",
+            ));
+        }
+        if has_children {
+            return Err(AppError::field(
+                "parent_id",
+                "category:parentId.errors.hasSubcategories",
+            ));
+        }
+
+        let parent = categories::table
+            .filter(categories::id.eq(parent_id))
+            .filter(categories::user_id.eq(user_id))
+            .first::<Category>(conn)
+            .optional()?
+            .ok_or(AppError::field(
+                "parent_id",
+                "category:parentId.errors.notFound",
+            ))?;
+
+        if parent.parent_id.is_some() {
+            return Err(AppError::field(
+                "parent_id",
+                "category:parentId.errors.tooDeep",
+            ));
+        }
+        if parent.category_type != data.category_type {
+            return Err(AppError::field(
+                "parent_id",
+                "category:parentId.errors.typeMismatch",
+            ));
+        }
+
+        Ok(())
     }
 }
 
@@ -212,6 +457,7 @@ pub struct NewCategory {
     pub icon: String,
     pub is_default: bool,
     pub c_order: i32,
+    pub parent_id: Option<Uuid>,
 }
 
 impl NewCategory {
@@ -224,24 +470,52 @@ impl NewCategory {
             icon: data.icon.clone(),
             is_default: data.is_default,
             c_order: c_order,
+            parent_id: data.parent_id,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset)]
-#[diesel(table_name = categories)]
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset, Validate)]
+#[diesel(table_name = categories, treat_none_as_null = true)]
 pub struct CategoryData {
+    #[validate(custom = "validate_category_type")]
     pub category_type: String,
+    #[validate(length(min = 1, max = 50, message = "category:name.errors.length"))]
     pub name: String,
+    #[validate(length(min = 1, message = "category:color.errors.required"))]
     pub color: String,
+    #[validate(length(min = 1, message = "category:icon.errors.required"))]
     pub icon: String,
     pub is_default: bool,
+    #[serde(default)]
+    pub parent_id: Option<Uuid>,
+}
+
+fn validate_category_type(category_type: &str) -> Result<(), ValidationError> {
+    if category_type == "income" || category_type == "expense" {
+        Ok(())
+    } else {
+        Err(ValidationError::new("category:type.errors.invalid"))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryNode {
+    #[serde(flatten)]
+    pub category: Category,
+    pub children: Vec<Category>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeleteCategoryQuery {
+    pub reassign_to: Option<Uuid>,
+    pub archive: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CategoriesResult {
-    pub income: Vec<Category>,
-    pub expense: Vec<Category>,
+    pub income: Vec<CategoryNode>,
+    pub expense: Vec<CategoryNode>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset)]
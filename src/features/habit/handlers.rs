@@ -1,15 +1,34 @@
-use actix_web::{delete, get, post, put, web, HttpResponse, Scope};
+use actix::{Actor, AsyncContext, StreamHandler};
+use actix_web::{delete, get, post, put, web, Error, HttpRequest, HttpResponse, Scope};
+use actix_web_actors::ws;
+use std::time::Duration;
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
 use crate::common::middlewares::auth::AuthenticationService;
-use crate::features::habit::models::{Habit, HabitData, NewHabit};
+use crate::common::middlewares::validated_json::ValidatedJson;
+use crate::common::models::errors::AppError;
+use crate::common::services::notifier::{Notifiers, PushNotifier};
+use crate::features::habit::models::{
+    Habit, HabitData, HabitsAchievement, NewHabit, ReorderHabitsData,
+};
+use crate::features::habit_group::models::{HabitGroup, HabitGroupData, ReorderHabitGroupsData};
+use crate::features::habit_reminder::models::{HabitReminder, ReminderData, SnoozeData};
 use crate::features::habit_target::models::Target;
+use crate::features::habit_timer::models::TimerSession;
 use crate::repository::database::Database;
 
 pub fn routes() -> Scope {
     web::scope("/habits")
         .service(get_all)
         .service(create)
+        .service(reorder_habits)
+        .service(get_groups)
+        .service(create_group)
+        .service(reorder_groups)
+        .service(update_group)
+        .service(delete_group)
+        .service(get_todays_groups)
         .service(edit)
         .service(delete)
         .service(archive)
@@ -17,116 +36,362 @@ pub fn routes() -> Scope {
         .service(delete_habits)
         .service(get_todays_habits)
         .service(get_grid_habits)
+        .service(get_timer)
+        .service(start_timer)
+        .service(stop_timer)
+        .service(get_reminders)
+        .service(create_reminder)
+        .service(update_reminder)
+        .service(delete_reminder)
+        .service(snooze_reminder)
+        .route("/reminders/ws", web::get().to(reminders_ws))
 }
 
 #[get("/")]
-async fn get_all(user: AuthenticationService, db: web::Data<Database>) -> HttpResponse {
-    match Habit::get_all(db.clone(), user.0.id).await {
-        Ok(habits) => HttpResponse::Ok().json(habits),
-        Err(err) => return HttpResponse::InternalServerError().body(err),
-    }
+async fn get_all(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let habits = Habit::get_all(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(habits))
 }
 
 #[get("/today")]
-async fn get_todays_habits(user: AuthenticationService, db: web::Data<Database>) -> HttpResponse {
-    match Habit::get_todays_habits(db.clone(), user.0.id).await {
-        Ok(habits) => HttpResponse::Ok().json(habits),
-        Err(err) => return HttpResponse::InternalServerError().body(err),
-    }
+async fn get_todays_habits(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let habits = Habit::get_todays_habits(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(habits))
 }
 
 #[get("/grid")]
-async fn get_grid_habits(user: AuthenticationService, db: web::Data<Database>) -> HttpResponse {
-    match Habit::get_grid_habits(db.clone(), user.0.id).await {
-        Ok(habits) => HttpResponse::Ok().json(habits),
-        Err(err) => return HttpResponse::InternalServerError().body(err),
-    }
+async fn get_grid_habits(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let habits = Habit::get_grid_habits(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(habits))
 }
 
 #[post("/")]
 async fn create(
     user: AuthenticationService,
     db: web::Data<Database>,
-    form: web::Json<HabitData>,
-) -> HttpResponse {
-    match Habit::create(db.clone(), NewHabit::create(form.into_inner(), user.0.id)).await {
-        Ok(habit_id) => match Habit::get_details(db.clone(), habit_id).await {
-            Ok(habit) => HttpResponse::Ok().json(habit),
-            Err(err) => HttpResponse::InternalServerError().body(err),
-        },
-
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
+    form: ValidatedJson<HabitData>,
+) -> Result<HttpResponse, AppError> {
+    if let Some(Some(group_id)) = form.group_id {
+        HabitGroup::get(db.clone(), user.0.id, group_id).await?;
     }
+    let habit_id =
+        Habit::create(db.clone(), NewHabit::create(form.into_inner(), user.0.id)).await?;
+    let habit = Habit::get_details(db.clone(), habit_id).await?;
+
+    Ok(HttpResponse::Ok().json(habit))
+}
+
+#[post("/reorder")]
+async fn reorder_habits(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    form: web::Json<Vec<ReorderHabitsData>>,
+) -> Result<HttpResponse, AppError> {
+    Habit::reorder(db.clone(), user.0.id, form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("habits reordered"))
+}
+
+#[get("/groups")]
+async fn get_groups(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let groups = HabitGroup::get_all(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(groups))
+}
+
+#[get("/today/groups")]
+async fn get_todays_groups(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let groups = HabitGroup::get_todays_groups(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(groups))
+}
+
+#[post("/groups")]
+async fn create_group(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    form: ValidatedJson<HabitGroupData>,
+) -> Result<HttpResponse, AppError> {
+    let group = HabitGroup::create(db.clone(), user.0.id, form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(group))
+}
+
+#[post("/groups/reorder")]
+async fn reorder_groups(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    form: web::Json<Vec<ReorderHabitGroupsData>>,
+) -> Result<HttpResponse, AppError> {
+    HabitGroup::reorder(db.clone(), user.0.id, form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("groups reordered"))
+}
+
+#[put("/groups/{group_id}")]
+async fn update_group(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+    form: ValidatedJson<HabitGroupData>,
+) -> Result<HttpResponse, AppError> {
+    let group =
+        HabitGroup::update(db.clone(), user.0.id, path.into_inner(), form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(group))
+}
+
+#[delete("/groups/{group_id}")]
+async fn delete_group(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    HabitGroup::delete(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("group deleted"))
 }
 
 #[put("/{habit_id}")]
 async fn edit(
-    _: AuthenticationService,
+    user: AuthenticationService,
     db: web::Data<Database>,
     path: web::Path<Uuid>,
-    form: web::Json<HabitData>,
-) -> HttpResponse {
-    match Habit::edit(db.clone(), path.clone(), form.into_inner()).await {
-        Ok(_) => match Habit::get_details(db.clone(), path.clone()).await {
-            Ok(habit) => HttpResponse::Ok().json(habit),
-            Err(err) => HttpResponse::InternalServerError().body(err),
-        },
-        Err(err) => HttpResponse::InternalServerError().body(err),
+    form: ValidatedJson<HabitData>,
+) -> Result<HttpResponse, AppError> {
+    if let Some(Some(group_id)) = form.group_id {
+        HabitGroup::get(db.clone(), user.0.id, group_id).await?;
     }
+    Habit::edit(db.clone(), user.0.id, path.clone(), form.into_inner()).await?;
+    let habit = Habit::get_details(db.clone(), path.clone()).await?;
+
+    Ok(HttpResponse::Ok().json(habit))
 }
 
 #[delete("/{habit_id}")]
 async fn delete(
-    _: AuthenticationService,
+    user: AuthenticationService,
     db: web::Data<Database>,
     path: web::Path<Uuid>,
-) -> HttpResponse {
-    let res = Habit::delete(db, path.clone()).await;
+) -> Result<HttpResponse, AppError> {
+    Habit::delete(db, user.0.id, path.clone()).await?;
 
-    match res {
-        Ok(_) => HttpResponse::Ok().body("habit deleted"),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+    Ok(HttpResponse::Ok().body("habit deleted"))
 }
 
 #[put("/{habit_id}/archive")]
 async fn archive(
-    _: AuthenticationService,
+    user: AuthenticationService,
     db: web::Data<Database>,
     path: web::Path<Uuid>,
-) -> HttpResponse {
-    let res = Habit::archive(db, path.clone()).await;
-    match res {
-        Ok(_) => HttpResponse::Ok().body("habit archived"),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+) -> Result<HttpResponse, AppError> {
+    Habit::archive(db, user.0.id, path.clone()).await?;
+
+    Ok(HttpResponse::Ok().body("habit archived"))
 }
 
 #[put("/{habit_id}/clean")]
 async fn clean_habit(
-    _: AuthenticationService,
+    user: AuthenticationService,
     db: web::Data<Database>,
     path: web::Path<Uuid>,
-) -> HttpResponse {
+) -> Result<HttpResponse, AppError> {
     let habit_id = path.clone();
-    let res = Target::clean_habit(db.clone(), &habit_id).await;
-    // TODO: refactor
-    match res {
-        Ok(_) => match Habit::get_by_id(db.clone(), habit_id.clone()).await {
-            Ok(_) => match Habit::get_details(db.clone(), habit_id).await {
-                Ok(habit) => HttpResponse::Ok().json(habit),
-                Err(err) => HttpResponse::InternalServerError().body(err),
-            },
-            Err(err) => HttpResponse::InternalServerError().body(err),
-        },
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+    Target::clean_habit(db.clone(), user.0.id, habit_id).await?;
+    Habit::get_by_id(db.clone(), habit_id).await?;
+    let habit = Habit::get_details(db.clone(), habit_id).await?;
+
+    Ok(HttpResponse::Ok().json(habit))
 }
 
 #[delete("/")]
-async fn delete_habits(user: AuthenticationService, db: web::Data<Database>) -> HttpResponse {
-    match Habit::delete_all_habits(db, user.0.id).await {
-        Ok(_) => HttpResponse::Ok().body("habits deleted"),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
+async fn delete_habits(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    Habit::delete_all_habits(db, user.0.id).await?;
+
+    Ok(HttpResponse::Ok().body("habits deleted"))
+}
+
+#[get("/{habit_id}/timer")]
+async fn get_timer(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let session = TimerSession::get_running(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(session))
+}
+
+#[post("/{habit_id}/timer/start")]
+async fn start_timer(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let session = TimerSession::start(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(session))
+}
+
+#[post("/{habit_id}/timer/stop")]
+async fn stop_timer(
+    achievements_data: web::Data<mpsc::UnboundedSender<Vec<String>>>,
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let habit_id = path.into_inner();
+    let session = TimerSession::stop(db.clone(), user.0.id, user.0.tz(), habit_id).await?;
+    HabitsAchievement::check_habit(db.clone(), achievements_data.get_ref().clone(), habit_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(session))
+}
+
+#[get("/{habit_id}/reminders")]
+async fn get_reminders(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let reminders = HabitReminder::get_all(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(reminders))
+}
+
+#[post("/{habit_id}/reminders")]
+async fn create_reminder(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    notifiers: web::Data<Notifiers>,
+    path: web::Path<Uuid>,
+    form: ValidatedJson<ReminderData>,
+) -> Result<HttpResponse, AppError> {
+    let reminder = HabitReminder::create(
+        db.clone(),
+        &notifiers,
+        user.0.id,
+        path.into_inner(),
+        form.into_inner(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(reminder))
+}
+
+#[put("/{habit_id}/reminders/{reminder_id}")]
+async fn update_reminder(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    notifiers: web::Data<Notifiers>,
+    path: web::Path<(Uuid, Uuid)>,
+    form: ValidatedJson<ReminderData>,
+) -> Result<HttpResponse, AppError> {
+    let (habit_id, reminder_id) = path.into_inner();
+    let reminder = HabitReminder::update(
+        db.clone(),
+        &notifiers,
+        user.0.id,
+        habit_id,
+        reminder_id,
+        form.into_inner(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(reminder))
+}
+
+#[delete("/{habit_id}/reminders/{reminder_id}")]
+async fn delete_reminder(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, AppError> {
+    let (habit_id, reminder_id) = path.into_inner();
+    HabitReminder::delete(db.clone(), user.0.id, habit_id, reminder_id).await?;
+
+    Ok(HttpResponse::Ok().body("reminder deleted"))
+}
+
+#[post("/{habit_id}/reminders/{reminder_id}/snooze")]
+async fn snooze_reminder(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<(Uuid, Uuid)>,
+    form: ValidatedJson<SnoozeData>,
+) -> Result<HttpResponse, AppError> {
+    let (habit_id, reminder_id) = path.into_inner();
+    let reminder = HabitReminder::snooze(
+        db.clone(),
+        user.0.id,
+        habit_id,
+        reminder_id,
+        form.into_inner(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(reminder))
+}
+
+// Streams the push reminders of the user
+struct ReminderWs {
+    receiver: mpsc::UnboundedReceiver<String>,
+}
+
+impl Actor for ReminderWs {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(Duration::from_secs(1), |act, ctx| {
+            while let Ok(message) = act.receiver.try_recv() {
+                ctx.text(message);
+            }
+        });
     }
 }
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ReminderWs {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Close(reason)) => ctx.close(reason),
+            _ => (),
+        }
+    }
+}
+
+async fn reminders_ws(
+    user: AuthenticationService,
+    push: web::Data<PushNotifier>,
+    req: HttpRequest,
+    stream: web::Payload,
+) -> Result<HttpResponse, Error> {
+    ws::start(
+        ReminderWs {
+            receiver: push.subscribe(user.0.id),
+        },
+        &req,
+        stream,
+    )
+}
@@ -1,13 +1,19 @@
+use crate::common::models::errors::{AppError, OrNotFound};
 use crate::features::achievement::models::Achievement;
 use crate::features::habit_target::models::{Target, TargetHelper};
 use crate::features::user::models::User;
 use crate::schema::{achievements, habits, habits_achievements, targets};
 use actix_web::web;
-use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use diesel::deserialize;
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::sql_types::{Jsonb, Varchar};
 use serde::{Deserialize, Serialize};
 use std::{cmp, fmt};
+use tokio::sync::mpsc;
 use uuid::Uuid;
+use validator::{Validate, ValidationError};
 
 use crate::diesel::ExpressionMethods;
 use crate::repository::database::Database;
@@ -21,7 +27,6 @@ use std::str::FromStr;
     Clone,
     Queryable,
     PartialEq,
-    Eq,
     Selectable,
     Identifiable,
     Associations,
@@ -38,26 +43,34 @@ pub struct Habit {
     pub deleted: bool,
     pub color: String,
     pub icon: String,
-    pub amount: i32,
-    pub frequency_type: String, // Daily | Weekly | Monthly | Interval
-    pub frequency_amount: serde_json::Value, // Vec<i32> (list of days for daily), a single number in vec for weekly, monthly and interval
+    pub amount: f64, // daily threshold, in `unit`
+    #[serde(flatten)]
+    #[diesel(
+        select_expression = (habits::frequency_type, habits::frequency_amount),
+        select_expression_type = (habits::frequency_type, habits::frequency_amount)
+    )]
+    pub frequency: Frequency,
+    pub polarity: String, // "positive", "negative" (a habit to avoid, its targets are slips)
+    pub unit: String,     // "count", "minutes", "km", "pages", "ml" or a custom label
+    pub goal_direction: String, // "at_least", "at_most"
+    pub h_order: i32,
+    pub group_id: Option<Uuid>,
 }
 
 impl Habit {
     pub async fn get_all(
         db: web::Data<Database>,
         user_id: Uuid,
-    ) -> Result<Vec<HabitDetails>, String> {
+    ) -> Result<Vec<HabitDetails>, AppError> {
         let habits_list: Vec<Habit> = habits::table
             .filter(habits::user_id.eq(user_id))
-            .order(habits::created_date.asc())
-            .load::<Habit>(&mut db.pool.get().unwrap())
-            .unwrap();
+            .order((habits::h_order.asc(), habits::created_date.asc()))
+            .select(Habit::as_select())
+            .load::<Habit>(&mut db.conn()?)?;
 
         let targets_list: Vec<Vec<Target>> = Target::belonging_to(&habits_list)
             .order(targets::date.asc())
-            .load::<Target>(&mut db.pool.get().unwrap())
-            .unwrap()
+            .load::<Target>(&mut db.conn()?)?
             .grouped_by(&habits_list);
 
         let data: Vec<HabitDetails> = habits_list
@@ -74,20 +87,17 @@ impl Habit {
     pub async fn get_todays_habits(
         db: web::Data<Database>,
         user_id: Uuid,
-    ) -> Result<Vec<TodaysHabitDetails>, String> {
-        let weekday: u32 = Utc::now().weekday().num_days_from_sunday();
+    ) -> Result<Vec<TodaysHabitDetails>, AppError> {
+        let today = Utc::now().date_naive();
         let habits_list: Vec<Habit> = habits::table
             .filter(habits::user_id.eq(user_id))
-            .filter(habits::frequency_type.eq("daily"))
-            .filter(habits::frequency_amount.contains(serde_json::json!([weekday])))
-            .order(habits::created_date.asc())
-            .load::<Habit>(&mut db.pool.get().unwrap())
-            .unwrap();
+            .order((habits::h_order.asc(), habits::created_date.asc()))
+            .select(Habit::as_select())
+            .load::<Habit>(&mut db.conn()?)?;
 
         let targets_list: Vec<Vec<Target>> = Target::belonging_to(&habits_list)
             .order(targets::date.asc())
-            .load::<Target>(&mut db.pool.get().unwrap())
-            .unwrap()
+            .load::<Target>(&mut db.conn()?)?
             .grouped_by(&habits_list);
 
         let data: Vec<TodaysHabitDetails> = habits_list
@@ -95,6 +105,14 @@ impl Habit {
             .zip(targets_list)
             .collect::<Vec<(Habit, Vec<Target>)>>()
             .into_iter()
+            .filter(|(h, t)| {
+                let dates = t
+                    .iter()
+                    .map(|target| target.date)
+                    .collect::<Vec<NaiveDate>>();
+                // Staying clean is a daily task whatever the frequency
+                h.is_negative() || h.frequency.is_due(today, &dates)
+            })
             .map(|(h, t)| TodaysHabitDetails::parse(&h, t))
             .collect::<Vec<TodaysHabitDetails>>();
 
@@ -104,17 +122,16 @@ impl Habit {
     pub async fn get_grid_habits(
         db: web::Data<Database>,
         user_id: Uuid,
-    ) -> Result<Vec<GridHabitDetails>, String> {
+    ) -> Result<Vec<GridHabitDetails>, AppError> {
         let habits_list: Vec<Habit> = habits::table
             .filter(habits::user_id.eq(user_id))
-            .order(habits::created_date.asc())
-            .load::<Habit>(&mut db.pool.get().unwrap())
-            .unwrap();
+            .order((habits::h_order.asc(), habits::created_date.asc()))
+            .select(Habit::as_select())
+            .load::<Habit>(&mut db.conn()?)?;
 
         let targets_list: Vec<Vec<Target>> = Target::belonging_to(&habits_list)
             .order(targets::date.asc())
-            .load::<Target>(&mut db.pool.get().unwrap())
-            .unwrap()
+            .load::<Target>(&mut db.conn()?)?
             .grouped_by(&habits_list);
 
         let data = habits_list
@@ -128,27 +145,27 @@ impl Habit {
         return Ok(data);
     }
 
-    pub async fn get_by_id(db: web::Data<Database>, id: Uuid) -> Result<Habit, String> {
+    pub async fn get_by_id(db: web::Data<Database>, id: Uuid) -> Result<Habit, AppError> {
         return habits::table
             .filter({
                 habits::id.eq(id);
                 habits::deleted.eq(false);
                 habits::archived.eq(false)
             })
-            .first::<Habit>(&mut db.pool.get().unwrap())
-            .map_err(|_| "Habit not found".to_string());
+            .select(Habit::as_select())
+            .first::<Habit>(&mut db.conn()?)
+            .or_not_found("habit:errors.notFound");
     }
 
-    pub async fn get_details(db: web::Data<Database>, id: Uuid) -> Result<HabitDetails, String> {
+    pub async fn get_details(db: web::Data<Database>, id: Uuid) -> Result<HabitDetails, AppError> {
         let habit: Vec<Habit> = habits::table
             .filter(habits::id.eq(id))
-            .load::<Habit>(&mut db.pool.get().unwrap())
-            .unwrap();
+            .select(Habit::as_select())
+            .load::<Habit>(&mut db.conn()?)?;
 
         let targets_list: Vec<Vec<Target>> = Target::belonging_to(&habit)
             .order(targets::date.asc())
-            .load::<Target>(&mut db.pool.get().unwrap())
-            .unwrap()
+            .load::<Target>(&mut db.conn()?)?
             .grouped_by(&habit);
 
         let data: Vec<HabitDetails> = habit
@@ -159,61 +176,117 @@ impl Habit {
             .map(|(h, t)| HabitDetails::parse(&h, t))
             .collect::<Vec<HabitDetails>>();
 
-        return Ok(data[0].clone());
+        data.into_iter()
+            .next()
+            .ok_or(AppError::NotFound("habit:errors.notFound"))
     }
 
-    pub async fn create(db: web::Data<Database>, new_habit: NewHabit) -> Result<Uuid, String> {
+    pub async fn create(db: web::Data<Database>, new_habit: NewHabit) -> Result<Uuid, AppError> {
+        let conn = &mut db.conn()?;
+        // New habits go last
+        let h_order: Option<i32> = habits::table
+            .filter(habits::user_id.eq(new_habit.user_id))
+            .select(diesel::dsl::max(habits::h_order))
+            .first(conn)?;
+
         let new_habit = diesel::insert_into(habits::table)
-            .values(&new_habit)
-            .get_result::<Habit>(&mut db.pool.get().unwrap());
-
-        match new_habit {
-            Ok(new_habit) => {
-                tokio::spawn(HabitsAchievement::create_default(
-                    db.clone(),
-                    new_habit.user_id.clone(),
-                    new_habit.id,
-                ));
-
-                Ok(new_habit.id)
-            }
-            Err(_) => Err("Failed to create habit".to_string()),
-        }
+            .values((&new_habit, habits::h_order.eq(h_order.map_or(0, |o| o + 1))))
+            .returning(Habit::as_returning())
+            .get_result::<Habit>(conn)?;
+
+        tokio::spawn(HabitsAchievement::create_default(
+            db.clone(),
+            new_habit.user_id.clone(),
+            new_habit.id,
+        ));
+
+        Ok(new_habit.id)
     }
 
-    pub async fn edit(db: web::Data<Database>, id: Uuid, habit: HabitData) -> Result<(), String> {
-        diesel::update(habits::table)
+    pub async fn edit(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+        habit: HabitData,
+    ) -> Result<(), AppError> {
+        let updated = diesel::update(habits::table)
             .filter(habits::id.eq(id))
+            .filter(habits::user_id.eq(user_id))
             .set(habit)
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Failed to update habit".to_string())
+            .execute(&mut db.conn()?)?;
+        if updated == 0 {
+            return Err(AppError::NotFound("habit:errors.notFound"));
+        }
+
+        Ok(())
     }
 
-    pub async fn delete(db: web::Data<Database>, id: Uuid) -> Result<(), String> {
-        let habit = habits::table.filter(habits::id.eq(id));
-        diesel::delete(habit)
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Error deleting habit".to_string())
+    pub async fn reorder(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        data: Vec<ReorderHabitsData>,
+    ) -> Result<(), AppError> {
+        // Ids of other users fail the whole reorder
+        db.conn()?.transaction::<_, AppError, _>(|conn| {
+            for d in data {
+                let updated = diesel::update(habits::table)
+                    .filter(habits::id.eq(d.id))
+                    .filter(habits::user_id.eq(user_id))
+                    .set(habits::h_order.eq(d.h_order))
+                    .execute(conn)?;
+                if updated == 0 {
+                    return Err(AppError::NotFound("habit:errors.notFound"));
+                }
+            }
+
+            Ok(())
+        })
     }
 
-    pub async fn archive(db: web::Data<Database>, id: Uuid) -> Result<(), String> {
-        diesel::update(habits::table)
+    pub async fn delete(db: web::Data<Database>, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        let habit = habits::table
             .filter(habits::id.eq(id))
+            .filter(habits::user_id.eq(user_id));
+        let deleted = diesel::delete(habit).execute(&mut db.conn()?)?;
+        if deleted == 0 {
+            return Err(AppError::NotFound("habit:errors.notFound"));
+        }
+
+        Ok(())
+    }
+
+    pub async fn archive(db: web::Data<Database>, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        let updated = diesel::update(habits::table)
+            .filter(habits::id.eq(id))
+            .filter(habits::user_id.eq(user_id))
             .set(habits::archived.eq(true))
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Failed to archive habit".to_string())
+            .execute(&mut db.conn()?)?;
+        if updated == 0 {
+            return Err(AppError::NotFound("habit:errors.notFound"));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.polarity == "negative"
+    }
+
+    // Whether the amount of a day reaches the daily threshold
+    pub fn is_completed(&self, amount: f64) -> bool {
+        match self.goal_direction.as_str() {
+            "at_most" => amount <= self.amount,
+            _ => amount >= self.amount,
+        }
     }
 
-    pub async fn delete_all_habits(db: web::Data<Database>, user_id: Uuid) -> Result<(), String> {
+    pub async fn delete_all_habits(db: web::Data<Database>, user_id: Uuid) -> Result<(), AppError> {
         diesel::update(habits::table)
             .filter(habits::user_id.eq(user_id))
             .set(habits::deleted.eq(true))
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Failed to delete habit".to_string())
+            .execute(&mut db.conn()?)?;
+
+        Ok(())
     }
 }
 
@@ -224,10 +297,15 @@ pub struct NewHabit {
     pub name: String,
     pub color: String,
     pub icon: String,
-    pub amount: i32,
+    pub amount: f64,
     pub goal: i32,
-    pub frequency_type: String, // 'daily' | 'weekly' | 'monthly' | 'interval'
-    pub frequency_amount: serde_json::Value,
+    #[serde(flatten)]
+    #[diesel(embed)]
+    pub frequency: Frequency,
+    pub polarity: String,
+    pub unit: String,
+    pub goal_direction: String,
+    pub group_id: Option<Uuid>,
 }
 
 impl NewHabit {
@@ -239,8 +317,13 @@ impl NewHabit {
             icon: new_habit.icon,
             amount: new_habit.amount,
             goal: new_habit.goal,
-            frequency_type: new_habit.frequency_type,
-            frequency_amount: new_habit.frequency_amount,
+            frequency: new_habit.frequency,
+            polarity: new_habit.polarity.unwrap_or_else(|| "positive".to_string()),
+            unit: new_habit.unit.unwrap_or_else(|| "count".to_string()),
+            goal_direction: new_habit
+                .goal_direction
+                .unwrap_or_else(|| "at_least".to_string()),
+            group_id: new_habit.group_id.flatten(),
         }
     }
 }
@@ -252,10 +335,14 @@ pub struct HabitDetails {
     pub name: String,
     pub color: String,
     pub icon: String,
-    pub amount: i32,
+    pub amount: f64,
     pub goal: i32,
-    pub frequency_type: String,
-    pub frequency_amount: serde_json::Value,
+    #[serde(flatten)]
+    pub frequency: Frequency,
+    pub polarity: String,
+    pub unit: String,
+    pub goal_direction: String,
+    pub group_id: Option<Uuid>,
     pub created_date: DateTime<Utc>,
     pub targets: Vec<Target>,
 }
@@ -270,8 +357,11 @@ impl HabitDetails {
             icon: h.icon.clone(),
             amount: h.amount.clone(),
             goal: h.goal.clone(),
-            frequency_type: h.frequency_type.clone(),
-            frequency_amount: h.frequency_amount.clone(),
+            frequency: h.frequency.clone(),
+            polarity: h.polarity.clone(),
+            unit: h.unit.clone(),
+            goal_direction: h.goal_direction.clone(),
+            group_id: h.group_id,
             created_date: h.created_date.clone(),
             targets: targets,
         }
@@ -292,15 +382,20 @@ pub struct GridHabitDetails {
     pub name: String,
     pub color: String,
     pub icon: String,
-    pub amount: i32,
+    pub amount: f64,
     pub goal: i32,
-    pub frequency_type: String,
-    pub frequency_amount: serde_json::Value,
+    #[serde(flatten)]
+    pub frequency: Frequency,
+    pub polarity: String,
+    pub unit: String,
+    pub goal_direction: String,
+    pub group_id: Option<Uuid>,
     pub created_date: DateTime<Utc>,
     pub targets: Vec<GridTarget>,
     pub current_streak: i32,
     pub longest_streak: i32,
     pub total_count: i32,
+    pub completed_count: i32, // days that reached the threshold
 }
 
 impl GridHabitDetails {
@@ -315,13 +410,17 @@ impl GridHabitDetails {
             icon: h.icon.clone(),
             amount: h.amount.clone(),
             goal: h.goal.clone(),
-            frequency_type: h.frequency_type.clone(),
-            frequency_amount: h.frequency_amount.clone(),
+            frequency: h.frequency.clone(),
+            polarity: h.polarity.clone(),
+            unit: h.unit.clone(),
+            goal_direction: h.goal_direction.clone(),
+            group_id: h.group_id,
             created_date: h.created_date.clone(),
             targets: weekly_targets,
             current_streak: current_streak,
             longest_streak: longest_streak,
             total_count: targets.len() as i32,
+            completed_count: targets.iter().filter(|t| h.is_completed(t.amount)).count() as i32,
         }
     }
 }
@@ -333,36 +432,50 @@ pub struct TodaysHabitDetails {
     icon: String,
     color: String,
     goal: i32,
+    polarity: String,
+    unit: String,
+    amount: f64,
+    today_amount: f64,
     progress: i32,
-    today_completed: bool,
+    pub today_completed: bool, // for negative habits, no slip so far today
+    pub group_id: Option<Uuid>,
 }
 
 impl TodaysHabitDetails {
     pub fn parse(h: &Habit, targets: Vec<Target>) -> TodaysHabitDetails {
+        let today = Utc::now().date_naive();
+        let logged_today = targets.iter().any(|t| t.date == today);
+        let today_amount = targets
+            .iter()
+            .filter(|t| t.date == today)
+            .fold(0.0, |sum, t| sum + t.amount);
+
+        let (done, today_completed) = if h.is_negative() {
+            // The goal of a negative habit is a number of clean days in a row
+            let (clean_days, _, _) = TargetHelper::calculate_streaks(h, targets);
+            (clean_days, !logged_today)
+        } else {
+            let completed_days = targets.iter().filter(|t| h.is_completed(t.amount)).count();
+            (
+                completed_days as i32,
+                logged_today && h.is_completed(today_amount),
+            )
+        };
+
         TodaysHabitDetails {
             id: h.id,
             name: h.name.clone(),
             icon: h.icon.clone(),
             color: h.color.clone(),
             goal: h.goal,
-            progress: cmp::min((targets.len() as f64 / h.goal as f64 * 100.0) as i32, 100),
-            today_completed: Self::is_today_completed(targets),
-        }
-    }
-
-    fn is_today_completed(targets: Vec<Target>) -> bool {
-        let today = Utc::now().date_naive();
-
-        match targets.last() {
-            Some(target) => {
-                if target.date == today {
-                    return true;
-                }
-            }
-            None => {}
+            polarity: h.polarity.clone(),
+            unit: h.unit.clone(),
+            amount: h.amount,
+            today_amount,
+            progress: cmp::min((done as f64 / h.goal as f64 * 100.0) as i32, 100),
+            today_completed,
+            group_id: h.group_id,
         }
-
-        return false;
     }
 }
 
@@ -370,21 +483,319 @@ impl TodaysHabitDetails {
 pub struct GridTarget {
     pub id: Uuid,
     pub date: NaiveDate,
-    pub amount: i32,
+    pub amount: f64,
+    pub completed: bool, // reached the threshold, never for slips
     pub current_streak: i32,
 }
-#[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset)]
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable, Validate)]
 #[diesel(table_name = habits)]
 pub struct HabitData {
+    #[validate(length(min = 1, max = 100, message = "habit:name.errors.length"))]
     name: String,
+    #[validate(length(min = 1, message = "habit:color.errors.required"))]
     color: String,
+    #[validate(length(min = 1, message = "habit:icon.errors.required"))]
     icon: String,
-    amount: i32,
+    #[validate(custom = "validate_amount")]
+    amount: f64,
+    #[validate(range(min = 0, message = "habit:goal.errors.negative"))]
     goal: i32,
-    frequency_type: String, // 'daily' | 'weekly' | 'monthly' | 'interval'
+    #[serde(flatten)]
+    #[diesel(embed)]
+    #[validate(custom = "validate_frequency")]
+    frequency: Frequency,
+    // Left as is on edit when missing
+    #[validate(custom = "validate_polarity")]
+    polarity: Option<String>,
+    #[validate(length(min = 1, max = 20, message = "habit:unit.errors.length"))]
+    unit: Option<String>,
+    #[validate(custom = "validate_goal_direction")]
+    goal_direction: Option<String>,
+    // Left as is on edit when missing, null removes the habit from its group
+    #[serde(default, with = "::serde_with::rust::double_option")]
+    pub group_id: Option<Option<Uuid>>,
+}
+
+// Written by hand as the frequency spans two columns
+impl AsChangeset for HabitData {
+    type Target = habits::table;
+    type Changeset = <(
+        diesel::dsl::Eq<habits::name, String>,
+        diesel::dsl::Eq<habits::color, String>,
+        diesel::dsl::Eq<habits::icon, String>,
+        diesel::dsl::Eq<habits::amount, f64>,
+        diesel::dsl::Eq<habits::goal, i32>,
+        Frequency,
+        Option<diesel::dsl::Eq<habits::polarity, String>>,
+        Option<diesel::dsl::Eq<habits::unit, String>>,
+        Option<diesel::dsl::Eq<habits::goal_direction, String>>,
+        Option<diesel::dsl::Eq<habits::group_id, Option<Uuid>>>,
+    ) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            habits::name.eq(self.name),
+            habits::color.eq(self.color),
+            habits::icon.eq(self.icon),
+            habits::amount.eq(self.amount),
+            habits::goal.eq(self.goal),
+            self.frequency,
+            self.polarity.map(|polarity| habits::polarity.eq(polarity)),
+            self.unit.map(|unit| habits::unit.eq(unit)),
+            self.goal_direction
+                .map(|goal_direction| habits::goal_direction.eq(goal_direction)),
+            self.group_id.map(|group_id| habits::group_id.eq(group_id)),
+        )
+            .as_changeset()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset)]
+#[diesel(table_name = habits)]
+pub struct ReorderHabitsData {
+    id: Uuid,
+    h_order: i32,
+}
+
+fn validate_amount(amount: f64) -> Result<(), ValidationError> {
+    if amount > 0.0 && amount.is_finite() {
+        Ok(())
+    } else {
+        Err(ValidationError::new("habit:amount.errors.notPositive"))
+    }
+}
+
+fn validate_goal_direction(goal_direction: &str) -> Result<(), ValidationError> {
+    if goal_direction == "at_least" || goal_direction == "at_most" {
+        Ok(())
+    } else {
+        Err(ValidationError::new("habit:goalDirection.errors.invalid"))
+    }
+}
+
+fn validate_polarity(polarity: &str) -> Result<(), ValidationError> {
+    if polarity == "positive" || polarity == "negative" {
+        Ok(())
+    } else {
+        Err(ValidationError::new("habit:polarity.errors.invalid"))
+    }
+}
+
+// Stored in the `frequency_type` and `frequency_amount` columns and sent the
+// same way, e.g. "daily" with [1, 3, 5] or "weekly" with [3]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(try_from = "FrequencyColumns", into = "FrequencyColumns")]
+pub enum Frequency {
+    Daily { weekdays: Vec<u32> }, // 0 is Sunday
+    Weekly { times: u32 },
+    Monthly { times: u32 },
+    Interval { days: u32 },         // at most this many days between check-ins
+    DaysOfMonth { days: Vec<u32> }, // days past the end of a month fall on its last day
+    TimesPerPeriod { times: u32, days: u32 }, // rolling window of `days` days
+    NthWeekday { nth: u32, weekday: u32 }, // nth 5 is the last one of the month
+}
+
+impl Frequency {
+    // Frequencies with fixed days, the others only set a quota
+    pub fn is_scheduled(&self, date: NaiveDate) -> bool {
+        let weekday = date.weekday().num_days_from_sunday();
+        let last_day = last_day_of_month(date);
+
+        match self {
+            Frequency::Daily { weekdays } => weekdays.contains(&weekday),
+            Frequency::DaysOfMonth { days } => days
+                .iter()
+                .any(|day| cmp::min(*day, last_day) == date.day()),
+            Frequency::NthWeekday { nth: 5, weekday: w } => {
+                *w == weekday && date.day() + 7 > last_day
+            }
+            Frequency::NthWeekday { nth, weekday: w } => {
+                *w == weekday && (date.day() - 1) / 7 + 1 == *nth
+            }
+            _ => false,
+        }
+    }
+
+    pub fn next_scheduled(&self, after: NaiveDate) -> Option<NaiveDate> {
+        // Every fixed schedule repeats within a year
+        (1..=366)
+            .map(|days| after + Duration::days(days))
+            .find(|date| self.is_scheduled(*date))
+    }
+
+    // Scheduled for the date, a quota of the period that the check-ins before it haven't
+    // met yet, or an interval that has passed since the last check-in
+    pub fn is_due(&self, date: NaiveDate, dates: &[NaiveDate]) -> bool {
+        let done_since =
+            |start: NaiveDate| dates.iter().filter(|d| **d >= start && **d < date).count() as u32;
+
+        match self {
+            Frequency::Weekly { times } => {
+                let week_start =
+                    date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                done_since(week_start) < *times
+            }
+            Frequency::Monthly { times } => done_since(date.with_day(1).unwrap_or(date)) < *times,
+            Frequency::TimesPerPeriod { times, days } => {
+                done_since(date - Duration::days(*days as i64 - 1)) < *times
+            }
+            Frequency::Interval { days } => dates
+                .iter()
+                .filter(|d| **d < date)
+                .max()
+                .is_none_or(|last| (date - *last).num_days() >= *days as i64),
+            _ => self.is_scheduled(date),
+        }
+    }
+}
+
+fn last_day_of_month(date: NaiveDate) -> u32 {
+    (28..=31)
+        .rev()
+        .find(|day| date.with_day(*day).is_some())
+        .unwrap_or(28)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct FrequencyColumns {
+    frequency_type: String,
     frequency_amount: serde_json::Value,
 }
 
+impl TryFrom<FrequencyColumns> for Frequency {
+    type Error = String;
+
+    fn try_from(columns: FrequencyColumns) -> Result<Self, Self::Error> {
+        let amount = serde_json::from_value::<Vec<u32>>(columns.frequency_amount)
+            .map_err(|err| err.to_string())?;
+
+        Ok(match (columns.frequency_type.as_str(), amount.as_slice()) {
+            ("daily", weekdays) => Frequency::Daily {
+                weekdays: weekdays.to_vec(),
+            },
+            ("weekly", [times]) => Frequency::Weekly { times: *times },
+            ("monthly", [times]) => Frequency::Monthly { times: *times },
+            ("interval", [days]) => Frequency::Interval { days: *days },
+            ("days_of_month", days) => Frequency::DaysOfMonth {
+                days: days.to_vec(),
+            },
+            ("times_per_period", [times, days]) => Frequency::TimesPerPeriod {
+                times: *times,
+                days: *days,
+            },
+            ("nth_weekday", [nth, weekday]) => Frequency::NthWeekday {
+                nth: *nth,
+                weekday: *weekday,
+            },
+            (frequency_type, amount) => {
+                return Err(format!("invalid {} frequency {:?}", frequency_type, amount))
+            }
+        })
+    }
+}
+
+impl From<Frequency> for FrequencyColumns {
+    fn from(frequency: Frequency) -> Self {
+        let (frequency_type, amount) = match frequency {
+            Frequency::Daily { weekdays } => ("daily", weekdays),
+            Frequency::Weekly { times } => ("weekly", vec![times]),
+            Frequency::Monthly { times } => ("monthly", vec![times]),
+            Frequency::Interval { days } => ("interval", vec![days]),
+            Frequency::DaysOfMonth { days } => ("days_of_month", days),
+            Frequency::TimesPerPeriod { times, days } => ("times_per_period", vec![times, days]),
+            Frequency::NthWeekday { nth, weekday } => ("nth_weekday", vec![nth, weekday]),
+        };
+
+        Self {
+            frequency_type: frequency_type.to_string(),
+            frequency_amount: serde_json::json!(amount),
+        }
+    }
+}
+
+type FrequencyValues = (
+    diesel::dsl::Eq<habits::frequency_type, String>,
+    diesel::dsl::Eq<habits::frequency_amount, serde_json::Value>,
+);
+
+impl FrequencyColumns {
+    fn values(self) -> FrequencyValues {
+        (
+            habits::frequency_type.eq(self.frequency_type),
+            habits::frequency_amount.eq(self.frequency_amount),
+        )
+    }
+}
+
+// Rows without a valid frequency are read as every day instead of failing
+// the whole habit list of the user
+impl Queryable<(Varchar, Jsonb), Pg> for Frequency {
+    type Row = (String, serde_json::Value);
+
+    fn build((frequency_type, frequency_amount): Self::Row) -> deserialize::Result<Self> {
+        let columns = FrequencyColumns {
+            frequency_type,
+            frequency_amount,
+        };
+
+        Ok(Frequency::try_from(columns).unwrap_or_else(|err| {
+            log::warn!("falling back to a daily frequency: {}", err);
+            Frequency::Daily {
+                weekdays: (0..=6).collect(),
+            }
+        }))
+    }
+}
+
+impl Insertable<habits::table> for Frequency {
+    type Values = <FrequencyValues as Insertable<habits::table>>::Values;
+
+    fn values(self) -> Self::Values {
+        FrequencyColumns::from(self).values().values()
+    }
+}
+
+impl Insertable<habits::table> for &Frequency {
+    type Values = <FrequencyValues as Insertable<habits::table>>::Values;
+
+    fn values(self) -> Self::Values {
+        self.clone().values()
+    }
+}
+
+impl AsChangeset for Frequency {
+    type Target = habits::table;
+    type Changeset = <FrequencyValues as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        FrequencyColumns::from(self).values().as_changeset()
+    }
+}
+
+fn validate_frequency(frequency: &Frequency) -> Result<(), ValidationError> {
+    let valid = match frequency {
+        Frequency::Daily { weekdays } => {
+            !weekdays.is_empty() && weekdays.iter().all(|day| (0..=6).contains(day))
+        }
+        Frequency::Weekly { times } => (1..=7).contains(times),
+        Frequency::Monthly { times } => (1..=31).contains(times),
+        Frequency::Interval { days } => *days >= 1,
+        Frequency::DaysOfMonth { days } => {
+            !days.is_empty() && days.iter().all(|day| (1..=31).contains(day))
+        }
+        Frequency::TimesPerPeriod { times, days } => {
+            *times >= 1 && (1..=366).contains(days) && times <= days
+        }
+        Frequency::NthWeekday { nth, weekday } => (1..=5).contains(nth) && *weekday <= 6,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("habit:frequency.errors.invalid"))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy)]
 pub enum HabitsAchievementEnum {
     StreakStarter,
@@ -447,6 +858,20 @@ impl HabitsAchievementEnum {
     //     }
     // }
 
+    // Achievements earned by the habit's longest streak, clean days for negative habits
+    pub fn streak_goal(&self) -> Option<i32> {
+        match self {
+            Self::StreakStarter => Some(3),
+            Self::HabitFormed => Some(7),
+            Self::ConsistencyChampion => Some(14),
+            Self::HabitualHero => Some(30),
+            Self::HabitMaster => Some(60),
+            Self::HabitProdigy => Some(90),
+            Self::HabitLegend => Some(180),
+            _ => None,
+        }
+    }
+
     pub fn get_all() -> Vec<Self> {
         vec![
             Self::StreakStarter,
@@ -547,10 +972,9 @@ impl HabitsAchievement {
         db: web::Data<Database>,
         user_id: Uuid,
         habit_id: Uuid,
-    ) -> Result<(), String> {
+    ) -> Result<(), AppError> {
         let achievements_map = Achievement::get_achievements(db.clone(), user_id)
-            .await
-            .unwrap()
+            .await?
             .into_iter()
             .map(|item| (item.clone().key, item))
             .collect::<HashMap<String, Achievement>>();
@@ -573,8 +997,7 @@ impl HabitsAchievement {
 
         diesel::insert_into(habits_achievements::table)
             .values(achievements_to_insert)
-            .execute(&mut db.pool.get().unwrap())
-            .unwrap();
+            .execute(&mut db.conn()?)?;
 
         Ok(())
     }
@@ -582,15 +1005,103 @@ impl HabitsAchievement {
     pub async fn get_all(
         db: web::Data<Database>,
         habit_id: Uuid,
-    ) -> Result<Vec<(HabitsAchievement, Achievement)>, String> {
-        habits_achievements::table
+    ) -> Result<Vec<(HabitsAchievement, Achievement)>, AppError> {
+        Ok(habits_achievements::table
             .filter(habits_achievements::habit_id.eq(habit_id))
             .inner_join(
                 achievements::table.on(achievements::id.eq(habits_achievements::achievement_id)),
             )
             .select((HabitsAchievement::as_select(), Achievement::as_select()))
-            .load::<(HabitsAchievement, Achievement)>(&mut db.pool.get().unwrap())
-            .map_err(|_| "Error loading achievements".to_string())
+            .load::<(HabitsAchievement, Achievement)>(&mut db.conn()?)?)
+    }
+
+    pub async fn check_habit(
+        db: web::Data<Database>,
+        achievements_sender: mpsc::UnboundedSender<Vec<String>>,
+        habit_id: Uuid,
+    ) -> Result<usize, AppError> {
+        let conn = &mut db.conn()?;
+        let habit = habits::table
+            .find(habit_id)
+            .select(Habit::as_select())
+            .first::<Habit>(conn)
+            .or_not_found("habit:errors.notFound")?;
+
+        Self::check(conn, Some(&achievements_sender), &habit)
+    }
+
+    // Clean days of negative habits add up without any check-in. The achievements socket
+    // isn't scoped to a user, so unlocks found here are only persisted.
+    pub async fn check_negative_habits(db: web::Data<Database>) -> Result<usize, AppError> {
+        let conn = &mut db.conn()?;
+        let habits_list = habits::table
+            .filter(habits::polarity.eq("negative"))
+            .filter(habits::archived.eq(false))
+            .filter(habits::deleted.eq(false))
+            .select(Habit::as_select())
+            .load::<Habit>(conn)?;
+
+        let mut unlocked = 0;
+        for habit in habits_list {
+            unlocked += Self::check(conn, None, &habit)?;
+        }
+
+        Ok(unlocked)
+    }
+
+    // Updates the habit's progress on streak achievements and unlocks the ones it
+    // reached. Newly unlocked achievements go to the achievements socket when a sender
+    // is given.
+    fn check(
+        conn: &mut PgConnection,
+        achievements_sender: Option<&mpsc::UnboundedSender<Vec<String>>>,
+        habit: &Habit,
+    ) -> Result<usize, AppError> {
+        let targets_list = Target::belonging_to(habit)
+            .order(targets::date.asc())
+            .load::<Target>(conn)?;
+        let (_, longest_streak, _) = TargetHelper::calculate_streaks(habit, targets_list);
+
+        let habit_achievements = habits_achievements::table
+            .filter(habits_achievements::habit_id.eq(habit.id))
+            .inner_join(
+                achievements::table.on(achievements::id.eq(habits_achievements::achievement_id)),
+            )
+            .select((HabitsAchievement::as_select(), Achievement::as_select()))
+            .load::<(HabitsAchievement, Achievement)>(conn)?;
+
+        let unlocked = conn.transaction::<_, AppError, _>(|conn| {
+            let mut unlocked = vec![];
+            for (habit_achievement, achievement) in habit_achievements {
+                let Some(goal) = HabitsAchievementEnum::from_str(&achievement.key)
+                    .ok()
+                    .and_then(|key| key.streak_goal())
+                else {
+                    continue;
+                };
+
+                if habit_achievement.progress != longest_streak {
+                    diesel::update(habits_achievements::table.find(habit_achievement.id))
+                        .set(habits_achievements::progress.eq(longest_streak))
+                        .execute(conn)?;
+                }
+
+                if longest_streak >= goal
+                    && Achievement::complete(conn, habit.user_id, "habits", &achievement.key)?
+                {
+                    unlocked.push(achievement.key);
+                }
+            }
+
+            Ok(unlocked)
+        })?;
+
+        let count = unlocked.len();
+        if let Some(sender) = achievements_sender.filter(|_| count > 0) {
+            let _ = sender.send(unlocked);
+        }
+
+        Ok(count)
     }
     // pub async fn check_all(
     //     db: web::Data<Database>,
@@ -752,3 +1263,162 @@ pub struct HabitsAchievementData {
     habit_id: Uuid,
     progress: i32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn columns(frequency_type: &str, frequency_amount: serde_json::Value) -> FrequencyColumns {
+        FrequencyColumns {
+            frequency_type: frequency_type.to_string(),
+            frequency_amount,
+        }
+    }
+
+    #[test]
+    fn frequency_reads_the_legacy_columns() {
+        assert_eq!(
+            Frequency::try_from(columns("daily", json!([1, 3, 5]))),
+            Ok(Frequency::Daily {
+                weekdays: vec![1, 3, 5]
+            })
+        );
+        assert_eq!(
+            Frequency::try_from(columns("weekly", json!([3]))),
+            Ok(Frequency::Weekly { times: 3 })
+        );
+        assert_eq!(
+            Frequency::try_from(columns("monthly", json!([2]))),
+            Ok(Frequency::Monthly { times: 2 })
+        );
+        assert_eq!(
+            Frequency::try_from(columns("interval", json!([4]))),
+            Ok(Frequency::Interval { days: 4 })
+        );
+    }
+
+    #[test]
+    fn frequency_rejects_malformed_columns() {
+        assert!(Frequency::try_from(columns("weekly", json!([1, 2]))).is_err());
+        assert!(Frequency::try_from(columns("weekly", json!("3"))).is_err());
+        assert!(Frequency::try_from(columns("interval", json!([-1]))).is_err());
+        assert!(Frequency::try_from(columns("hourly", json!([1]))).is_err());
+    }
+
+    #[test]
+    fn frequency_round_trips_through_the_columns() {
+        let frequencies = [
+            Frequency::Daily {
+                weekdays: vec![0, 6],
+            },
+            Frequency::Weekly { times: 3 },
+            Frequency::Monthly { times: 1 },
+            Frequency::Interval { days: 2 },
+            Frequency::DaysOfMonth { days: vec![1, 15] },
+            Frequency::TimesPerPeriod { times: 5, days: 14 },
+            Frequency::NthWeekday { nth: 5, weekday: 1 },
+        ];
+
+        for frequency in frequencies {
+            let columns = FrequencyColumns::from(frequency.clone());
+            assert_eq!(Frequency::try_from(columns), Ok(frequency));
+        }
+    }
+
+    #[test]
+    fn frequency_keeps_the_habit_wire_shape() {
+        let value = serde_json::to_value(Frequency::Weekly { times: 3 }).unwrap();
+        assert_eq!(
+            value,
+            json!({"frequency_type": "weekly", "frequency_amount": [3]})
+        );
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn days_of_month_past_the_end_fall_on_the_last_day() {
+        let frequency = Frequency::DaysOfMonth { days: vec![15, 31] };
+
+        assert!(frequency.is_scheduled(date(2024, 2, 15)));
+        assert!(frequency.is_scheduled(date(2024, 2, 29)));
+        assert!(!frequency.is_scheduled(date(2024, 2, 28)));
+        assert!(frequency.is_scheduled(date(2024, 4, 30)));
+    }
+
+    #[test]
+    fn nth_weekday_is_scheduled_on_that_weekday_only() {
+        // Second Tuesday, then last Friday of May 2024
+        let second_tuesday = Frequency::NthWeekday { nth: 2, weekday: 2 };
+        assert!(second_tuesday.is_scheduled(date(2024, 5, 14)));
+        assert!(!second_tuesday.is_scheduled(date(2024, 5, 7)));
+        assert!(!second_tuesday.is_scheduled(date(2024, 5, 15)));
+
+        let last_friday = Frequency::NthWeekday { nth: 5, weekday: 5 };
+        assert!(last_friday.is_scheduled(date(2024, 5, 31)));
+        assert!(!last_friday.is_scheduled(date(2024, 5, 24)));
+        assert_eq!(
+            last_friday.next_scheduled(date(2024, 5, 31)),
+            Some(date(2024, 6, 28))
+        );
+    }
+
+    #[test]
+    fn times_per_period_is_due_until_the_window_quota_is_met() {
+        let frequency = Frequency::TimesPerPeriod { times: 2, days: 7 };
+
+        assert!(!frequency.is_due(date(2024, 5, 8), &[date(2024, 5, 2), date(2024, 5, 5)]));
+        assert!(frequency.is_due(date(2024, 5, 8), &[date(2024, 5, 1), date(2024, 5, 5)]));
+        assert!(frequency.is_due(date(2024, 5, 8), &[]));
+    }
+
+    #[test]
+    fn weekly_is_due_until_the_quota_of_the_week_is_met() {
+        // 2024-05-08 is a Wednesday, the week started on Monday the 6th
+        let frequency = Frequency::Weekly { times: 2 };
+
+        assert!(frequency.is_due(date(2024, 5, 8), &[date(2024, 5, 3), date(2024, 5, 4)]));
+        assert!(frequency.is_due(date(2024, 5, 8), &[date(2024, 5, 6)]));
+        assert!(!frequency.is_due(date(2024, 5, 8), &[date(2024, 5, 6), date(2024, 5, 7)]));
+    }
+
+    #[test]
+    fn monthly_is_due_until_the_quota_of_the_month_is_met() {
+        let frequency = Frequency::Monthly { times: 1 };
+
+        assert!(frequency.is_due(date(2024, 5, 1), &[date(2024, 4, 30)]));
+        assert!(!frequency.is_due(date(2024, 5, 20), &[date(2024, 5, 2)]));
+    }
+
+    #[test]
+    fn interval_is_due_once_the_days_passed_since_the_last_check_in() {
+        let frequency = Frequency::Interval { days: 3 };
+
+        assert!(frequency.is_due(date(2024, 5, 8), &[]));
+        assert!(!frequency.is_due(date(2024, 5, 8), &[date(2024, 5, 1), date(2024, 5, 6)]));
+        assert!(frequency.is_due(date(2024, 5, 8), &[date(2024, 5, 5)]));
+    }
+
+    #[test]
+    fn quota_frequencies_have_no_fixed_days() {
+        assert!(!Frequency::Weekly { times: 3 }.is_scheduled(date(2024, 5, 1)));
+        assert_eq!(
+            Frequency::Interval { days: 2 }.next_scheduled(date(2024, 5, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_stored_frequency_falls_back_to_every_day() {
+        let frequency = Frequency::build(("weekly".to_string(), json!(null))).expect("never fails");
+        assert_eq!(
+            frequency,
+            Frequency::Daily {
+                weekdays: vec![0, 1, 2, 3, 4, 5, 6]
+            }
+        );
+    }
+}
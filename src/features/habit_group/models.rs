@@ -0,0 +1,175 @@
+use crate::common::models::errors::{AppError, OrNotFound};
+use crate::features::habit::models::Habit;
+use crate::repository::database::Database;
+use crate::schema::habit_groups;
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable, Identifiable, PartialEq)]
+#[diesel(table_name = habit_groups)]
+pub struct HabitGroup {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub color: String,
+    pub g_order: i32,
+    pub created_date: DateTime<Utc>,
+}
+
+impl HabitGroup {
+    pub async fn get_all(
+        db: web::Data<Database>,
+        user_id: Uuid,
+    ) -> Result<Vec<HabitGroup>, AppError> {
+        Ok(habit_groups::table
+            .filter(habit_groups::user_id.eq(user_id))
+            .order((
+                habit_groups::g_order.asc(),
+                habit_groups::created_date.asc(),
+            ))
+            .load::<HabitGroup>(&mut db.conn()?)?)
+    }
+
+    pub async fn get(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<HabitGroup, AppError> {
+        habit_groups::table
+            .filter(habit_groups::id.eq(id))
+            .filter(habit_groups::user_id.eq(user_id))
+            .first::<HabitGroup>(&mut db.conn()?)
+            .or_not_found("habitGroup:errors.notFound")
+    }
+
+    pub async fn create(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        data: HabitGroupData,
+    ) -> Result<HabitGroup, AppError> {
+        let conn = &mut db.conn()?;
+        // New groups go last
+        let g_order: Option<i32> = habit_groups::table
+            .filter(habit_groups::user_id.eq(user_id))
+            .select(diesel::dsl::max(habit_groups::g_order))
+            .first(conn)?;
+
+        Ok(diesel::insert_into(habit_groups::table)
+            .values((
+                &data,
+                habit_groups::user_id.eq(user_id),
+                habit_groups::g_order.eq(g_order.map_or(0, |o| o + 1)),
+            ))
+            .get_result::<HabitGroup>(conn)?)
+    }
+
+    pub async fn update(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+        data: HabitGroupData,
+    ) -> Result<HabitGroup, AppError> {
+        diesel::update(habit_groups::table)
+            .filter(habit_groups::id.eq(id))
+            .filter(habit_groups::user_id.eq(user_id))
+            .set(&data)
+            .get_result::<HabitGroup>(&mut db.conn()?)
+            .or_not_found("habitGroup:errors.notFound")
+    }
+
+    // Habits of the group stay, without a group
+    pub async fn delete(db: web::Data<Database>, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        let deleted = diesel::delete(habit_groups::table)
+            .filter(habit_groups::id.eq(id))
+            .filter(habit_groups::user_id.eq(user_id))
+            .execute(&mut db.conn()?)?;
+        if deleted == 0 {
+            return Err(AppError::NotFound("habitGroup:errors.notFound"));
+        }
+
+        Ok(())
+    }
+
+    pub async fn reorder(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        data: Vec<ReorderHabitGroupsData>,
+    ) -> Result<(), AppError> {
+        // Ids of other users fail the whole reorder
+        db.conn()?.transaction::<_, AppError, _>(|conn| {
+            for d in data {
+                let updated = diesel::update(habit_groups::table)
+                    .filter(habit_groups::id.eq(d.id))
+                    .filter(habit_groups::user_id.eq(user_id))
+                    .set(habit_groups::g_order.eq(d.g_order))
+                    .execute(conn)?;
+                if updated == 0 {
+                    return Err(AppError::NotFound("habitGroup:errors.notFound"));
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    // Share of the habits of each group due today that are completed. Groups
+    // without habits due today are left out
+    pub async fn get_todays_groups(
+        db: web::Data<Database>,
+        user_id: Uuid,
+    ) -> Result<Vec<TodaysGroupDetails>, AppError> {
+        let groups = Self::get_all(db.clone(), user_id).await?;
+        let habits = Habit::get_todays_habits(db.clone(), user_id).await?;
+
+        Ok(groups
+            .into_iter()
+            .filter_map(|group| {
+                let (habits_count, completed_count) = habits
+                    .iter()
+                    .filter(|h| h.group_id == Some(group.id))
+                    .fold((0, 0), |(total, completed), h| {
+                        (total + 1, completed + h.today_completed as i32)
+                    });
+
+                (habits_count > 0).then(|| TodaysGroupDetails {
+                    id: group.id,
+                    name: group.name,
+                    color: group.color,
+                    habits_count,
+                    completed_count,
+                    progress: completed_count * 100 / habits_count,
+                })
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset, Validate)]
+#[diesel(table_name = habit_groups)]
+pub struct HabitGroupData {
+    #[validate(length(min = 1, max = 50, message = "habitGroup:name.errors.length"))]
+    name: String,
+    #[validate(length(min = 1, message = "habitGroup:color.errors.required"))]
+    color: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset)]
+#[diesel(table_name = habit_groups)]
+pub struct ReorderHabitGroupsData {
+    id: Uuid,
+    g_order: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TodaysGroupDetails {
+    pub id: Uuid,
+    pub name: String,
+    pub color: String,
+    pub habits_count: i32,
+    pub completed_count: i32,
+    pub progress: i32, // percentage of the habits due today that are completed
+}
@@ -0,0 +1,617 @@
+use crate::common::models::errors::{AppError, OrNotFound};
+use crate::common::services::notifier::{is_allowed_webhook_url, Notification, Notifiers};
+use crate::features::habit::models::Habit;
+use crate::features::habit_target::models::Target;
+use crate::features::user::models::User;
+use crate::repository::database::Database;
+use crate::schema::{habit_reminders, habits, targets, users};
+use actix_web::web;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+// Failed deliveries are retried after 5, 10, 20 and 40 minutes, then given up
+// until the next day
+const MAX_ATTEMPTS: i32 = 5;
+const FIRST_RETRY_MINUTES: i64 = 5;
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    PartialEq,
+)]
+#[diesel(belongs_to(Habit, foreign_key = habit_id))]
+#[diesel(table_name = habit_reminders)]
+pub struct HabitReminder {
+    pub id: Uuid,
+    pub habit_id: Uuid,
+    pub user_id: Uuid,
+    pub time: NaiveTime, // local time of the user
+    pub channel: String, // "push", "email", "webhook"
+    pub webhook_url: Option<String>,
+    pub last_sent_date: Option<NaiveDate>, // local date of the last delivery
+    pub snoozed_until: Option<DateTime<Utc>>,
+    pub failed_attempts: i32, // failed deliveries since the last one
+    pub retry_after: Option<DateTime<Utc>>,
+    pub created_date: DateTime<Utc>,
+}
+
+impl HabitReminder {
+    pub async fn get_all(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        habit_id: Uuid,
+    ) -> Result<Vec<HabitReminder>, AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_habit(conn, user_id, habit_id)?;
+
+        Ok(habit_reminders::table
+            .filter(habit_reminders::habit_id.eq(habit_id))
+            .order(habit_reminders::time.asc())
+            .load::<HabitReminder>(conn)?)
+    }
+
+    pub async fn create(
+        db: web::Data<Database>,
+        notifiers: &Notifiers,
+        user_id: Uuid,
+        habit_id: Uuid,
+        data: ReminderData,
+    ) -> Result<HabitReminder, AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_habit(conn, user_id, habit_id)?;
+        let data = data.checked(notifiers)?;
+
+        Ok(diesel::insert_into(habit_reminders::table)
+            .values(NewReminder {
+                habit_id,
+                user_id,
+                time: data.time,
+                channel: data.channel,
+                webhook_url: data.webhook_url,
+            })
+            .get_result::<HabitReminder>(conn)?)
+    }
+
+    // A new channel or URL gets a fresh set of delivery attempts
+    pub async fn update(
+        db: web::Data<Database>,
+        notifiers: &Notifiers,
+        user_id: Uuid,
+        habit_id: Uuid,
+        id: Uuid,
+        data: ReminderData,
+    ) -> Result<HabitReminder, AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_habit(conn, user_id, habit_id)?;
+        let data = data.checked(notifiers)?;
+
+        diesel::update(habit_reminders::table)
+            .filter(habit_reminders::id.eq(id))
+            .filter(habit_reminders::habit_id.eq(habit_id))
+            .set((
+                habit_reminders::time.eq(data.time),
+                habit_reminders::channel.eq(data.channel),
+                habit_reminders::webhook_url.eq(data.webhook_url),
+                habit_reminders::failed_attempts.eq(0),
+                habit_reminders::retry_after.eq(None::<DateTime<Utc>>),
+            ))
+            .get_result::<HabitReminder>(conn)
+            .or_not_found("habitReminder:errors.notFound")
+    }
+
+    pub async fn delete(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        habit_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_habit(conn, user_id, habit_id)?;
+
+        let deleted = diesel::delete(habit_reminders::table)
+            .filter(habit_reminders::id.eq(id))
+            .filter(habit_reminders::habit_id.eq(habit_id))
+            .execute(conn)?;
+        if deleted == 0 {
+            return Err(AppError::NotFound("habitReminder:errors.notFound"));
+        }
+
+        Ok(())
+    }
+
+    // Fires the reminder again once the snooze is over, even if it was sent today
+    pub async fn snooze(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        habit_id: Uuid,
+        id: Uuid,
+        data: SnoozeData,
+    ) -> Result<HabitReminder, AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_habit(conn, user_id, habit_id)?;
+
+        diesel::update(habit_reminders::table)
+            .filter(habit_reminders::id.eq(id))
+            .filter(habit_reminders::habit_id.eq(habit_id))
+            .set(
+                habit_reminders::snoozed_until
+                    .eq(Utc::now() + Duration::minutes(data.minutes as i64)),
+            )
+            .get_result::<HabitReminder>(conn)
+            .or_not_found("habitReminder:errors.notFound")
+    }
+
+    // Delivers the reminders that are due, skipping habits that are not due
+    // or already completed today. Failed deliveries are retried with a backoff
+    pub async fn send_due(
+        db: web::Data<Database>,
+        notifiers: &Notifiers,
+    ) -> Result<usize, AppError> {
+        let conn = &mut db.conn()?;
+        let now = Utc::now();
+
+        let reminders = habit_reminders::table
+            .inner_join(habits::table)
+            .inner_join(users::table)
+            .filter(habits::deleted.eq(false))
+            .filter(habits::archived.eq(false))
+            .filter(users::active.eq(true))
+            .filter(
+                habit_reminders::retry_after
+                    .is_null()
+                    .or(habit_reminders::retry_after.le(now)),
+            )
+            .select((
+                HabitReminder::as_select(),
+                Habit::as_select(),
+                users::all_columns,
+            ))
+            .load::<(HabitReminder, Habit, User)>(conn)?;
+
+        let mut sent = 0;
+        for (reminder, habit, user) in reminders {
+            let Some(today) = reminder.due_date(user.tz(), now) else {
+                continue;
+            };
+
+            let targets = Target::belonging_to(&habit)
+                .filter(targets::deleted.eq(false))
+                .load::<Target>(conn)?;
+            if !Self::needs_reminder(&habit, &targets, today) {
+                // Nothing to remind of today
+                reminder.mark_sent(conn, today)?;
+                continue;
+            }
+
+            let notification = Notification {
+                user_id: reminder.user_id,
+                habit_id: habit.id,
+                reminder_id: reminder.id,
+                title: habit.name.clone(),
+                body: format!("It's time for {}", habit.name),
+                email: user.email,
+                webhook_url: reminder.webhook_url.clone(),
+            };
+            match notifiers.send(&reminder.channel, &notification).await {
+                Ok(()) => {
+                    reminder.mark_sent(conn, today)?;
+                    sent += 1;
+                }
+                Err(err) => {
+                    log::error!("failed to send reminder {}: {}", reminder.id, err);
+                    reminder.mark_failed(conn, today, now)?;
+                }
+            }
+        }
+
+        Ok(sent)
+    }
+
+    // The local date the reminder is due for, if it is due now
+    fn due_date(&self, tz: Tz, now: DateTime<Utc>) -> Option<NaiveDate> {
+        let local_now = now.with_timezone(&tz);
+        let today = local_now.date_naive();
+
+        if let Some(snoozed_until) = self.snoozed_until {
+            return (now >= snoozed_until).then_some(today);
+        }
+        if self.last_sent_date == Some(today) || local_now.time() < self.time {
+            return None;
+        }
+
+        // Reminders created after their time of the day start on the next day
+        let created = self.created_date.with_timezone(&tz);
+        if created.date_naive() == today && created.time() > self.time {
+            return None;
+        }
+
+        Some(today)
+    }
+
+    fn needs_reminder(habit: &Habit, targets: &[Target], today: NaiveDate) -> bool {
+        // Staying clean is a daily task whatever the frequency
+        if habit.is_negative() {
+            return true;
+        }
+
+        let dates = targets.iter().map(|t| t.date).collect::<Vec<NaiveDate>>();
+        if !habit.frequency.is_due(today, &dates) {
+            return false;
+        }
+
+        let todays = targets
+            .iter()
+            .filter(|t| t.date == today)
+            .collect::<Vec<&Target>>();
+        let amount = todays.iter().fold(0.0, |sum, t| sum + t.amount);
+
+        todays.is_empty() || !habit.is_completed(amount)
+    }
+
+    fn mark_sent(&self, conn: &mut PgConnection, date: NaiveDate) -> QueryResult<usize> {
+        diesel::update(habit_reminders::table.find(self.id))
+            .set((
+                habit_reminders::last_sent_date.eq(date),
+                habit_reminders::snoozed_until.eq(None::<DateTime<Utc>>),
+                habit_reminders::failed_attempts.eq(0),
+                habit_reminders::retry_after.eq(None::<DateTime<Utc>>),
+            ))
+            .execute(conn)
+    }
+
+    fn mark_failed(
+        &self,
+        conn: &mut PgConnection,
+        date: NaiveDate,
+        now: DateTime<Utc>,
+    ) -> QueryResult<usize> {
+        let failed_attempts = self.failed_attempts + 1;
+        let Some(delay) = Self::retry_delay(failed_attempts) else {
+            log::error!(
+                "giving up on reminder {} for {} after {} attempts",
+                self.id,
+                date,
+                failed_attempts
+            );
+            return self.mark_sent(conn, date);
+        };
+
+        diesel::update(habit_reminders::table.find(self.id))
+            .set((
+                habit_reminders::failed_attempts.eq(failed_attempts),
+                habit_reminders::retry_after.eq(now + delay),
+            ))
+            .execute(conn)
+    }
+
+    // Doubles after each failed attempt, none once the attempts are used up
+    fn retry_delay(failed_attempts: i32) -> Option<Duration> {
+        (1..MAX_ATTEMPTS)
+            .contains(&failed_attempts)
+            .then(|| Duration::minutes(FIRST_RETRY_MINUTES << (failed_attempts - 1)))
+    }
+
+    fn get_habit(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        habit_id: Uuid,
+    ) -> Result<Habit, AppError> {
+        habits::table
+            .filter(habits::id.eq(habit_id))
+            .filter(habits::user_id.eq(user_id))
+            .filter(habits::deleted.eq(false))
+            .select(Habit::as_select())
+            .first::<Habit>(conn)
+            .or_not_found("habit:errors.notFound")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
+#[diesel(table_name = habit_reminders)]
+pub struct NewReminder {
+    pub habit_id: Uuid,
+    pub user_id: Uuid,
+    pub time: NaiveTime,
+    pub channel: String,
+    pub webhook_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Validate)]
+pub struct ReminderData {
+    pub time: NaiveTime,
+    #[validate(custom = "validate_channel")]
+    pub channel: Option<String>,
+    #[validate(
+        url(message = "habitReminder:webhookUrl.errors.invalid"),
+        custom = "validate_webhook_url"
+    )]
+    pub webhook_url: Option<String>,
+}
+
+struct CheckedReminderData {
+    time: NaiveTime,
+    channel: String,
+    webhook_url: Option<String>,
+}
+
+impl ReminderData {
+    // Only webhook reminders keep a URL. Channels without a notifier, such as email
+    // without SMTP settings, can't be picked
+    fn checked(self, notifiers: &Notifiers) -> Result<CheckedReminderData, AppError> {
+        let channel = self.channel.unwrap_or_else(|| "push".to_string());
+        if !notifiers.has_channel(&channel) {
+            return Err(AppError::field(
+                "channel",
+                "habitReminder:channel.errors.unavailable",
+            ));
+        }
+        let webhook_url = match channel.as_str() {
+            "webhook" => Some(self.webhook_url.ok_or(AppError::field(
+                "webhook_url",
+                "habitReminder:webhookUrl.errors.required",
+            ))?),
+            _ => None,
+        };
+
+        Ok(CheckedReminderData {
+            time: self.time,
+            channel,
+            webhook_url,
+        })
+    }
+}
+
+fn validate_channel(channel: &str) -> Result<(), ValidationError> {
+    match channel {
+        "push" | "email" | "webhook" => Ok(()),
+        _ => Err(ValidationError::new("habitReminder:channel.errors.invalid")),
+    }
+}
+
+fn validate_webhook_url(url: &str) -> Result<(), ValidationError> {
+    if is_allowed_webhook_url(url) {
+        Ok(())
+    } else {
+        Err(ValidationError::new(
+            "habitReminder:webhookUrl.errors.notAllowed",
+        ))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Validate)]
+pub struct SnoozeData {
+    #[validate(range(
+        min = 1,
+        max = 1440,
+        message = "habitReminder:minutes.errors.outOfRange"
+    ))]
+    pub minutes: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::habit::models::Frequency;
+    use crate::features::habit_target::models::fixtures::{habit, target};
+    use chrono::TimeZone;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn reminder(hour: u32) -> HabitReminder {
+        HabitReminder {
+            id: Uuid::nil(),
+            habit_id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            time: NaiveTime::from_hms_opt(hour, 0, 0).unwrap(),
+            channel: "push".to_string(),
+            webhook_url: None,
+            last_sent_date: None,
+            snoozed_until: None,
+            failed_attempts: 0,
+            retry_after: None,
+            created_date: utc(2024, 4, 1, 0, 0),
+        }
+    }
+
+    fn every_day() -> Frequency {
+        Frequency::Daily {
+            weekdays: (0..=6).collect(),
+        }
+    }
+
+    #[test]
+    fn reminder_is_due_from_its_local_time() {
+        let reminder = reminder(8);
+        let madrid = chrono_tz::Europe::Madrid;
+
+        // 07:30 and 08:30 in Madrid
+        assert_eq!(reminder.due_date(madrid, utc(2024, 5, 1, 5, 30)), None);
+        assert_eq!(
+            reminder.due_date(madrid, utc(2024, 5, 1, 6, 30)),
+            Some(date(2024, 5, 1))
+        );
+    }
+
+    #[test]
+    fn reminder_is_due_for_the_local_date() {
+        // 21:30 of the day before in New York
+        let reminder = reminder(21);
+
+        assert_eq!(
+            reminder.due_date(chrono_tz::America::New_York, utc(2024, 5, 2, 1, 30)),
+            Some(date(2024, 5, 1))
+        );
+    }
+
+    #[test]
+    fn reminder_fires_once_a_day() {
+        let reminder = HabitReminder {
+            last_sent_date: Some(date(2024, 5, 1)),
+            ..reminder(8)
+        };
+
+        assert_eq!(reminder.due_date(Tz::UTC, utc(2024, 5, 1, 20, 0)), None);
+        assert_eq!(
+            reminder.due_date(Tz::UTC, utc(2024, 5, 2, 8, 0)),
+            Some(date(2024, 5, 2))
+        );
+    }
+
+    #[test]
+    fn snoozed_reminder_fires_again_once_the_snooze_is_over() {
+        let reminder = HabitReminder {
+            last_sent_date: Some(date(2024, 5, 1)),
+            snoozed_until: Some(utc(2024, 5, 1, 9, 0)),
+            ..reminder(8)
+        };
+
+        assert_eq!(reminder.due_date(Tz::UTC, utc(2024, 5, 1, 8, 30)), None);
+        assert_eq!(
+            reminder.due_date(Tz::UTC, utc(2024, 5, 1, 9, 0)),
+            Some(date(2024, 5, 1))
+        );
+    }
+
+    #[test]
+    fn reminder_created_after_its_time_starts_the_next_day() {
+        let reminder = HabitReminder {
+            created_date: utc(2024, 5, 1, 9, 0),
+            ..reminder(8)
+        };
+
+        assert_eq!(reminder.due_date(Tz::UTC, utc(2024, 5, 1, 10, 0)), None);
+        assert_eq!(
+            reminder.due_date(Tz::UTC, utc(2024, 5, 2, 8, 0)),
+            Some(date(2024, 5, 2))
+        );
+    }
+
+    #[test]
+    fn reminder_is_needed_until_the_day_is_completed() {
+        let habit = habit(every_day());
+        let today = date(2024, 5, 1);
+
+        assert!(HabitReminder::needs_reminder(&habit, &[], today));
+        assert!(HabitReminder::needs_reminder(
+            &habit,
+            &[target(today, 0.5)],
+            today
+        ));
+        assert!(!HabitReminder::needs_reminder(
+            &habit,
+            &[target(today, 0.5), target(today, 0.5)],
+            today
+        ));
+        assert!(HabitReminder::needs_reminder(
+            &habit,
+            &[target(date(2024, 4, 30), 1.0)],
+            today
+        ));
+    }
+
+    #[test]
+    fn reminder_is_not_needed_on_unscheduled_days() {
+        // 2024-05-01 is a Wednesday
+        let habit = habit(Frequency::Daily {
+            weekdays: vec![1, 5],
+        });
+
+        assert!(!HabitReminder::needs_reminder(
+            &habit,
+            &[],
+            date(2024, 5, 1)
+        ));
+        assert!(HabitReminder::needs_reminder(&habit, &[], date(2024, 5, 3)));
+    }
+
+    #[test]
+    fn weekly_habits_are_reminded_until_the_week_is_done() {
+        // 2024-05-08 is a Wednesday
+        let habit = habit(Frequency::Weekly { times: 2 });
+        let today = date(2024, 5, 8);
+
+        assert!(HabitReminder::needs_reminder(
+            &habit,
+            &[target(date(2024, 5, 6), 1.0)],
+            today
+        ));
+        assert!(!HabitReminder::needs_reminder(
+            &habit,
+            &[target(date(2024, 5, 6), 1.0), target(date(2024, 5, 7), 1.0)],
+            today
+        ));
+    }
+
+    #[test]
+    fn monthly_habits_are_reminded_until_the_month_is_done() {
+        let habit = habit(Frequency::Monthly { times: 1 });
+
+        assert!(HabitReminder::needs_reminder(
+            &habit,
+            &[target(date(2024, 4, 30), 1.0)],
+            date(2024, 5, 1)
+        ));
+        assert!(!HabitReminder::needs_reminder(
+            &habit,
+            &[target(date(2024, 5, 1), 1.0)],
+            date(2024, 5, 2)
+        ));
+    }
+
+    #[test]
+    fn interval_habits_are_reminded_once_the_interval_passed() {
+        let habit = habit(Frequency::Interval { days: 2 });
+        let targets = [target(date(2024, 5, 1), 1.0)];
+
+        assert!(!HabitReminder::needs_reminder(
+            &habit,
+            &targets,
+            date(2024, 5, 2)
+        ));
+        assert!(HabitReminder::needs_reminder(
+            &habit,
+            &targets,
+            date(2024, 5, 3)
+        ));
+        assert!(HabitReminder::needs_reminder(&habit, &[], date(2024, 5, 2)));
+    }
+
+    #[test]
+    fn negative_habits_are_reminded_every_day() {
+        let habit = Habit {
+            polarity: "negative".to_string(),
+            ..habit(Frequency::Weekly { times: 1 })
+        };
+        let today = date(2024, 5, 1);
+
+        assert!(HabitReminder::needs_reminder(
+            &habit,
+            &[target(today, 1.0)],
+            today
+        ));
+    }
+
+    #[test]
+    fn retries_back_off_and_stop() {
+        assert_eq!(HabitReminder::retry_delay(1), Some(Duration::minutes(5)));
+        assert_eq!(HabitReminder::retry_delay(2), Some(Duration::minutes(10)));
+        assert_eq!(HabitReminder::retry_delay(4), Some(Duration::minutes(40)));
+        assert_eq!(HabitReminder::retry_delay(MAX_ATTEMPTS), None);
+    }
+}
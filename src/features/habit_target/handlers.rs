@@ -1,55 +1,133 @@
 use crate::common::middlewares::auth::AuthenticationService;
-use crate::features::habit_target::models::{Target, TargetData};
+use crate::common::middlewares::validated_json::ValidatedJson;
+use crate::common::models::errors::AppError;
+use crate::common::services::storage::BlobStorage;
+use crate::features::habit::models::HabitsAchievement;
+use crate::features::habit_target::models::{Target, TargetData, TargetSearchQuery};
 use crate::repository::database::Database;
-use actix_web::{delete, post, web, HttpResponse, Scope};
+use actix_web::http::header::ContentType;
+use actix_web::{delete, get, post, put, web, HttpResponse, Scope};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
 pub fn routes() -> Scope {
     web::scope("/targets")
+        .app_data(web::PayloadConfig::new(10 * 1024 * 1024))
         .service(create_target)
+        .service(search_targets)
         .service(delete_target)
         .service(clean_targets)
+        .service(get_attachment)
+        .service(put_attachment)
+        .service(delete_attachment)
 }
 
 #[post("/")]
 async fn create_target(
-    _achievements_data: web::Data<mpsc::UnboundedSender<Vec<String>>>,
+    achievements_data: web::Data<mpsc::UnboundedSender<Vec<String>>>,
     user: AuthenticationService,
     db: web::Data<Database>,
-    form: web::Json<TargetData>,
-) -> HttpResponse {
-    match Target::insert(db.clone(), user.0.id, form.clone()).await {
-        Ok(_) => {
-            // tokio::spawn(HabitsAchievement::check_all(
-            //     db.clone(),
-            //     achievements_data.get_ref().clone(),
-            //     user.0.id,
-            //     form.habit_id,
-            // ));
-
-            return HttpResponse::Ok().body("target created");
-        }
-        Err(err) => HttpResponse::InternalServerError().body(err),
+    form: ValidatedJson<TargetData>,
+) -> Result<HttpResponse, AppError> {
+    Target::insert(db.clone(), user.0.id, form.clone()).await?;
+
+    // The check-in is already saved, a failed achievement check shouldn't report otherwise
+    if let Err(err) = HabitsAchievement::check_habit(
+        db.clone(),
+        achievements_data.get_ref().clone(),
+        form.habit_id,
+    )
+    .await
+    {
+        log::error!(
+            "failed to check achievements of habit {}: {}",
+            form.habit_id,
+            err
+        );
     }
+
+    Ok(HttpResponse::Ok().body("target created"))
+}
+
+#[get("/search")]
+async fn search_targets(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<TargetSearchQuery>,
+) -> Result<HttpResponse, AppError> {
+    let targets = Target::search(db.clone(), user.0.id, query.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(targets))
 }
 
 #[delete("/{target_id}")]
 async fn delete_target(
-    _: AuthenticationService,
+    user: AuthenticationService,
     db: web::Data<Database>,
+    storage: web::Data<dyn BlobStorage>,
     path: web::Path<Uuid>,
-) -> HttpResponse {
-    match Target::delete(db.clone(), path.clone()).await {
-        Ok(_) => HttpResponse::Ok().body("target deleted"),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+) -> Result<HttpResponse, AppError> {
+    Target::delete(db.clone(), storage, user.0.id, path.clone()).await?;
+
+    Ok(HttpResponse::Ok().body("target deleted"))
 }
 
 #[post("/clean")]
-async fn clean_targets(user: AuthenticationService, db: web::Data<Database>) -> HttpResponse {
-    match Target::clean_data(db.clone(), user.0.id).await {
-        Ok(_) => HttpResponse::Ok().body("targets cleaned"),
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+async fn clean_targets(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    Target::clean_data(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().body("targets cleaned"))
+}
+
+#[get("/{target_id}/attachment")]
+async fn get_attachment(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    storage: web::Data<dyn BlobStorage>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let (content_type, data) =
+        Target::get_attachment(db.clone(), storage, user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().content_type(content_type).body(data))
+}
+
+#[put("/{target_id}/attachment")]
+async fn put_attachment(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    storage: web::Data<dyn BlobStorage>,
+    path: web::Path<Uuid>,
+    content_type: Option<web::Header<ContentType>>,
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    let content_type = content_type
+        .map(|header| header.into_inner().essence_str().to_string())
+        .unwrap_or_default();
+    let target = Target::attach(
+        db.clone(),
+        storage,
+        user.0.id,
+        path.into_inner(),
+        &content_type,
+        &body,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(target))
+}
+
+#[delete("/{target_id}/attachment")]
+async fn delete_attachment(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    storage: web::Data<dyn BlobStorage>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    Target::detach(db.clone(), storage, user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("attachment deleted"))
 }
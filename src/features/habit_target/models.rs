@@ -1,13 +1,17 @@
+use crate::common::models::errors::{AppError, OrNotFound};
+use crate::common::models::search::contains_pattern;
+use crate::common::services::storage::BlobStorage;
 use crate::features::habit::models::GridTarget;
-use crate::features::habit::models::Habit;
+use crate::features::habit::models::{Frequency, Habit};
 use crate::repository::database::Database;
-use crate::schema::targets;
+use crate::schema::{habits, targets};
 use actix_web::web;
 use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use uuid::Uuid;
+use validator::Validate;
 
 #[derive(
     Debug,
@@ -19,7 +23,6 @@ use uuid::Uuid;
     Identifiable,
     Associations,
     PartialEq,
-    Eq,
 )]
 #[diesel(belongs_to(Habit, foreign_key = habit_id))]
 #[diesel(table_name = targets)]
@@ -29,8 +32,11 @@ pub struct Target {
     pub user_id: Uuid,
     pub date: NaiveDate,
     pub created_date: DateTime<Utc>,
-    pub amount: i32,
+    pub amount: f64,
     pub deleted: bool,
+    pub note: Option<String>,
+    pub mood: Option<i32>,          // 1-5 mood/difficulty rating
+    pub attachment: Option<String>, // blob storage key
 }
 
 impl Target {
@@ -38,11 +44,19 @@ impl Target {
         db: web::Data<Database>,
         user_id: Uuid,
         target: TargetData,
-    ) -> Result<(), String> {
+    ) -> Result<(), AppError> {
+        habits::table
+            .filter(habits::id.eq(target.habit_id))
+            .filter(habits::user_id.eq(user_id))
+            .select(habits::id)
+            .first::<Uuid>(&mut db.conn()?)
+            .or_not_found("habit:errors.notFound")?;
+
         match target.id {
             Some(id) => {
                 Target::update(
                     db.clone(),
+                    user_id,
                     id,
                     UpdateTargetData::create(&target, target.amount),
                 )
@@ -52,57 +66,216 @@ impl Target {
         }
     }
 
-    pub async fn create(db: web::Data<Database>, target_data: NewTargetData) -> Result<(), String> {
+    pub async fn create(
+        db: web::Data<Database>,
+        target_data: NewTargetData,
+    ) -> Result<(), AppError> {
         diesel::insert_into(targets::table)
             .values(&target_data)
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Failed to update create".to_string())
+            .execute(&mut db.conn()?)?;
+
+        Ok(())
     }
 
     pub async fn update(
         db: web::Data<Database>,
+        user_id: Uuid,
         id: Uuid,
         target_data: UpdateTargetData,
-    ) -> Result<(), String> {
-        diesel::update(targets::table)
+    ) -> Result<(), AppError> {
+        let updated = diesel::update(targets::table)
             .filter(targets::id.eq(id))
+            .filter(targets::user_id.eq(user_id))
             .set(target_data)
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Failed to update target".to_string())
+            .execute(&mut db.conn()?)?;
+        if updated == 0 {
+            return Err(AppError::NotFound("target:errors.notFound"));
+        }
+
+        Ok(())
     }
 
-    pub async fn delete(db: web::Data<Database>, id: Uuid) -> Result<(), String> {
-        let target = targets::table.filter(targets::id.eq(id));
-        diesel::delete(target)
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Error deleting target".to_string())
+    pub async fn delete(
+        db: web::Data<Database>,
+        storage: web::Data<dyn BlobStorage>,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), AppError> {
+        let target = targets::table
+            .filter(targets::id.eq(id))
+            .filter(targets::user_id.eq(user_id));
+        let deleted = diesel::delete(target).get_results::<Target>(&mut db.conn()?)?;
+        if deleted.is_empty() {
+            return Err(AppError::NotFound("target:errors.notFound"));
+        }
+
+        for key in deleted.iter().filter_map(|t| t.attachment.as_deref()) {
+            storage.delete(key)?;
+        }
+
+        Ok(())
     }
-    pub async fn get_all(db: web::Data<Database>, habit_id: &Uuid) -> Result<Vec<Target>, String> {
+
+    pub async fn get(db: web::Data<Database>, user_id: Uuid, id: Uuid) -> Result<Target, AppError> {
         targets::table
+            .filter(targets::id.eq(id))
+            .filter(targets::user_id.eq(user_id))
+            .filter(targets::deleted.eq(false))
+            .first::<Target>(&mut db.conn()?)
+            .or_not_found("target:errors.notFound")
+    }
+
+    // Check-ins with a note, mood or attachment, newest first
+    pub async fn search(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        filter: TargetSearchQuery,
+    ) -> Result<Vec<Target>, AppError> {
+        let mut query = targets::table
+            .inner_join(habits::table)
+            .filter(targets::user_id.eq(user_id))
+            .filter(targets::deleted.eq(false))
+            .filter(habits::deleted.eq(false))
+            .filter(
+                targets::note
+                    .is_not_null()
+                    .or(targets::mood.is_not_null())
+                    .or(targets::attachment.is_not_null()),
+            )
+            .select(Target::as_select())
+            .into_boxed();
+
+        if let Some(search) = &filter.q {
+            query = query.filter(targets::note.ilike(contains_pattern(search)));
+        }
+        if let Some(habit_id) = filter.habit_id {
+            query = query.filter(targets::habit_id.eq(habit_id));
+        }
+        if let Some(mood) = filter.mood {
+            query = query.filter(targets::mood.eq(mood));
+        }
+        if let Some(from) = filter.from {
+            query = query.filter(targets::date.ge(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(targets::date.le(to));
+        }
+
+        Ok(query
+            .order((targets::date.desc(), targets::created_date.desc()))
+            .load::<Target>(&mut db.conn()?)?)
+    }
+
+    // Replaces the attachment of the check-in; the key keeps the extension of the
+    // content type so that it can be served back as is
+    pub async fn attach(
+        db: web::Data<Database>,
+        storage: web::Data<dyn BlobStorage>,
+        user_id: Uuid,
+        id: Uuid,
+        content_type: &str,
+        data: &[u8],
+    ) -> Result<Target, AppError> {
+        let extension = attachment_extension(content_type).ok_or(AppError::field(
+            "attachment",
+            "target:attachment.errors.unsupportedType",
+        ))?;
+        if data.is_empty() {
+            return Err(AppError::field(
+                "attachment",
+                "target:attachment.errors.empty",
+            ));
+        }
+
+        let target = Self::get(db.clone(), user_id, id).await?;
+        let key = format!("targets/{}/{}.{}", user_id, id, extension);
+        storage.put(&key, data)?;
+
+        let updated = diesel::update(targets::table.find(id))
+            .set(targets::attachment.eq(&key))
+            .get_result::<Target>(&mut db.conn()?)?;
+
+        if let Some(previous) = target.attachment.filter(|previous| *previous != key) {
+            storage.delete(&previous)?;
+        }
+
+        Ok(updated)
+    }
+
+    pub async fn get_attachment(
+        db: web::Data<Database>,
+        storage: web::Data<dyn BlobStorage>,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(&'static str, Vec<u8>), AppError> {
+        let key = Self::get(db, user_id, id)
+            .await?
+            .attachment
+            .ok_or(AppError::NotFound("target:attachment.errors.notFound"))?;
+        let content_type = key
+            .rsplit('.')
+            .next()
+            .and_then(attachment_content_type)
+            .unwrap_or("application/octet-stream");
+
+        Ok((content_type, storage.get(&key)?))
+    }
+
+    pub async fn detach(
+        db: web::Data<Database>,
+        storage: web::Data<dyn BlobStorage>,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), AppError> {
+        let target = Self::get(db.clone(), user_id, id).await?;
+
+        if let Some(key) = target.attachment {
+            diesel::update(targets::table.find(id))
+                .set(targets::attachment.eq(None::<String>))
+                .execute(&mut db.conn()?)?;
+            storage.delete(&key)?;
+        }
+
+        Ok(())
+    }
+    pub async fn get_all(
+        db: web::Data<Database>,
+        habit_id: &Uuid,
+    ) -> Result<Vec<Target>, AppError> {
+        Ok(targets::table
             .filter(targets::habit_id.eq(habit_id))
-            .load::<Target>(&mut db.pool.get().unwrap())
-            .map_err(|_| "Error loading targets".to_string())
+            .load::<Target>(&mut db.conn()?)?)
     }
 
-    pub async fn clean_data(db: web::Data<Database>, user_id: Uuid) -> Result<(), String> {
+    pub async fn clean_data(db: web::Data<Database>, user_id: Uuid) -> Result<(), AppError> {
         diesel::update(targets::table)
             .filter(targets::user_id.eq(user_id))
             .set(targets::deleted.eq(true))
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Failed to clean targets".to_string())
+            .execute(&mut db.conn()?)?;
+
+        Ok(())
     }
 
-    pub async fn clean_habit(db: web::Data<Database>, habit_id: &Uuid) -> Result<(), String> {
+    pub async fn clean_habit(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        habit_id: Uuid,
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+        habits::table
+            .filter(habits::id.eq(habit_id))
+            .filter(habits::user_id.eq(user_id))
+            .select(habits::id)
+            .first::<Uuid>(conn)
+            .or_not_found("habit:errors.notFound")?;
+
         diesel::update(targets::table)
             .filter(targets::habit_id.eq(habit_id))
+            .filter(targets::user_id.eq(user_id))
             .set(targets::deleted.eq(true))
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Failed to clean targets".to_string())
+            .execute(conn)?;
+
+        Ok(())
     }
 }
 
@@ -113,7 +286,7 @@ pub struct NewTarget {
     pub user_id: Uuid,
     pub date: NaiveDate,
     pub created_date: DateTime<Utc>,
-    pub amount: i32,
+    pub amount: f64,
     pub deleted: bool,
 }
 
@@ -130,41 +303,76 @@ impl NewTarget {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct UpdateTarget {
+#[derive(Debug, Serialize, Deserialize, Clone, Validate)]
+pub struct TargetData {
     pub id: Option<Uuid>,
-    pub habit_id: Uuid,
-    pub user_id: Uuid,
     pub date: NaiveDate,
-    pub created_date: DateTime<Utc>,
-    pub amount: i32,
-    pub deleted: bool,
+    pub habit_id: Uuid,
+    #[validate(range(min = 0.0, message = "target:amount.errors.negative"))]
+    pub amount: f64,
+    #[validate(length(max = 1000, message = "target:note.errors.tooLong"))]
+    pub note: Option<String>,
+    #[validate(range(min = 1, max = 5, message = "target:mood.errors.outOfRange"))]
+    pub mood: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct TargetData {
-    pub id: Option<Uuid>,
-    pub date: NaiveDate,
-    pub habit_id: Uuid,
-    pub amount: i32,
+pub struct TargetSearchQuery {
+    pub q: Option<String>,
+    pub habit_id: Option<Uuid>,
+    pub mood: Option<i32>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+const ATTACHMENT_TYPES: [(&str, &str); 4] = [
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/webp", "webp"),
+    ("image/heic", "heic"),
+];
+
+fn attachment_extension(content_type: &str) -> Option<&'static str> {
+    ATTACHMENT_TYPES
+        .iter()
+        .find(|(mime, _)| *mime == content_type)
+        .map(|(_, extension)| *extension)
+}
+
+fn attachment_content_type(extension: &str) -> Option<&'static str> {
+    ATTACHMENT_TYPES
+        .iter()
+        .find(|(_, ext)| *ext == extension)
+        .map(|(mime, _)| *mime)
+}
+
+// An empty note clears the stored one, a missing note leaves it untouched
+fn journal_note(note: &Option<String>) -> Option<Option<String>> {
+    note.as_ref().map(|note| {
+        let note = note.trim();
+        (!note.is_empty()).then(|| note.to_string())
+    })
 }
 
+// The habit of a check-in can't change, only what was logged on it
 #[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset)]
 #[diesel(table_name = targets)]
 pub struct UpdateTargetData {
     pub id: Uuid,
     pub date: NaiveDate,
-    pub habit_id: Uuid,
-    pub amount: i32,
+    pub amount: f64,
+    pub note: Option<Option<String>>,
+    pub mood: Option<i32>,
 }
 
 impl UpdateTargetData {
-    pub fn create(data: &TargetData, val: i32) -> Self {
+    pub fn create(data: &TargetData, val: f64) -> Self {
         Self {
             id: data.id.clone().unwrap(),
             date: data.date.clone(),
-            habit_id: data.habit_id.clone(),
             amount: val,
+            note: journal_note(&data.note),
+            mood: data.mood,
         }
     }
 }
@@ -175,7 +383,9 @@ pub struct NewTargetData {
     pub date: NaiveDate,
     pub habit_id: Uuid,
     pub user_id: Uuid,
-    pub amount: i32,
+    pub amount: f64,
+    pub note: Option<String>,
+    pub mood: Option<i32>,
 }
 
 impl NewTargetData {
@@ -185,6 +395,8 @@ impl NewTargetData {
             habit_id: data.habit_id.clone(),
             user_id: user_id.clone(),
             amount: data.amount,
+            note: journal_note(&data.note).flatten(),
+            mood: data.mood,
         }
     }
 }
@@ -364,20 +576,46 @@ impl TargetHelper {
     //     return statistics;
     // }
 
-    pub fn get_next_mandatory_day(mandatory_days: Vec<i32>, last_date: NaiveDate) -> NaiveDate {
-        let mandatory_days_set: HashSet<i32> = mandatory_days.iter().cloned().collect();
-        let mut next_mandatory_day = last_date;
+    pub fn calculate_streaks(habit: &Habit, targets: Vec<Target>) -> (i32, i32, Vec<GridTarget>) {
+        if habit.is_negative() {
+            return Self::calculate_clean_streaks(habit, &targets, Utc::now().date_naive());
+        }
 
-        loop {
-            next_mandatory_day = next_mandatory_day + Duration::days(1);
-            let next_mandatory_day_num = next_mandatory_day.weekday().num_days_from_sunday() as i32;
+        // A logged day that misses the threshold breaks the streak like a missed day
+        // would, so the completed days between failed ones are walked separately
+        let mut longest_streak = 0;
+        let mut grid_targets = vec![];
+        let mut completed = vec![];
 
-            if mandatory_days_set.contains(&next_mandatory_day_num) {
-                return next_mandatory_day;
+        for target in targets {
+            if habit.is_completed(target.amount) {
+                completed.push(target);
+                continue;
             }
+
+            let (_, longest, grid) =
+                Self::calculate_completed_streaks(habit, std::mem::take(&mut completed));
+            longest_streak = longest_streak.max(longest);
+            grid_targets.extend(grid);
+            grid_targets.push(GridTarget {
+                id: target.id,
+                date: target.date,
+                amount: target.amount,
+                completed: false,
+                current_streak: 0,
+            });
         }
+
+        let (current_streak, longest, grid) = Self::calculate_completed_streaks(habit, completed);
+        grid_targets.extend(grid);
+
+        (current_streak, longest_streak.max(longest), grid_targets)
     }
-    pub fn calculate_streaks(habit: &Habit, targets: Vec<Target>) -> (i32, i32, Vec<GridTarget>) {
+
+    fn calculate_completed_streaks(
+        habit: &Habit,
+        targets: Vec<Target>,
+    ) -> (i32, i32, Vec<GridTarget>) {
         if targets.len() == 0 {
             return (0, 0, Vec::new());
         }
@@ -394,170 +632,458 @@ impl TargetHelper {
             .map(|t| (t.date, t))
             .collect::<HashMap<NaiveDate, &Target>>();
 
-        if habit.frequency_type == "daily" {
-            let frequency: Vec<i32> = habit
-                .frequency_amount
-                .as_array()
-                .unwrap()
-                .to_owned()
-                .iter()
-                .map(|v| v.as_i64().unwrap() as i32)
-                .collect();
-
-            let mut next_mandatory_day = Self::get_next_mandatory_day(frequency.clone(), last_date);
-            let calc_full_week = frequency.len() == 7;
-
-            for date in dates {
-                if (calc_full_week
-                    && (date == last_date + chrono::Duration::days(1) || last_date == date))
-                    || (!calc_full_week && date <= next_mandatory_day)
-                {
-                    current_streak += 1;
+        match &habit.frequency {
+            // Every scheduled day up to the next check-in has to be covered
+            Frequency::Daily { .. }
+            | Frequency::DaysOfMonth { .. }
+            | Frequency::NthWeekday { .. } => {
+                let frequency = &habit.frequency;
+                // A schedule without any day would never reach a mandatory one
+                let Some(mut next_mandatory_day) = frequency.next_scheduled(last_date) else {
+                    return (0, 0, Vec::new());
+                };
 
-                    if current_streak > longest_streak {
-                        longest_streak = current_streak;
-                    }
-                } else {
-                    if current_streak > longest_streak {
-                        longest_streak = current_streak;
+                for date in dates {
+                    if date <= next_mandatory_day {
+                        current_streak += 1;
+
+                        if current_streak > longest_streak {
+                            longest_streak = current_streak;
+                        }
+                    } else {
+                        if current_streak > longest_streak {
+                            longest_streak = current_streak;
+                        }
+
+                        current_streak = 1;
                     }
 
-                    current_streak = 1;
-                }
+                    last_date = date;
+                    next_mandatory_day = frequency
+                        .next_scheduled(last_date)
+                        .unwrap_or(next_mandatory_day);
 
-                last_date = date;
-                next_mandatory_day = Self::get_next_mandatory_day(frequency.clone(), last_date);
-
-                let target = grid_targets_map.get(&date);
-                if target.is_some() {
-                    let target = target.unwrap();
-                    grid_targets.push(GridTarget {
-                        id: target.id,
-                        date: target.date,
-                        amount: target.amount,
-                        current_streak: current_streak,
-                    });
+                    if let Some(target) = grid_targets_map.get(&date) {
+                        grid_targets.push(GridTarget {
+                            id: target.id,
+                            date: target.date,
+                            amount: target.amount,
+                            completed: true,
+                            current_streak,
+                        });
+                    }
                 }
             }
-        } else if habit.frequency_type == "weekly" {
-            let frequency_list = habit
-                .frequency_amount
-                .as_array()
-                .unwrap()
-                .to_owned()
-                .iter()
-                .map(|v| v.as_i64().unwrap() as i32)
-                .collect::<Vec<i32>>();
-
-            let frequency = frequency_list.first().unwrap();
-            let mut current_week = last_date.iso_week().week();
-            let mut current_week_streak = 0;
-            for date in dates {
-                if current_week == date.iso_week().week() {
-                    current_week_streak += 1;
-                    current_streak += 1;
+            // Periods of `days` days follow each other from the first check-in of the
+            // streak, each of them needs `times` check-ins
+            Frequency::TimesPerPeriod { times, days } => {
+                let period = Duration::days(*days as i64);
+                let mut period_start = last_date;
+                let mut period_count = 0;
+
+                for date in dates {
+                    if date < period_start + period {
+                        period_count += 1;
+                        current_streak += 1;
+                    } else if date < period_start + period * 2 && period_count >= *times {
+                        period_start += period;
+                        period_count = 1;
+                        current_streak += 1;
+                    } else {
+                        period_start = date;
+                        period_count = 1;
+                        current_streak = 1;
+                    }
 
                     if current_streak > longest_streak {
                         longest_streak = current_streak;
                     }
-                } else if &current_week_streak < frequency && current_week_streak != 0 {
-                    current_week_streak = 0;
-                    current_streak = 1;
-                }
 
-                last_date = date;
-                current_week = last_date.iso_week().week();
-
-                let target = grid_targets_map.get(&date);
-                if target.is_some() {
-                    let target = target.unwrap();
-                    grid_targets.push(GridTarget {
-                        id: target.id,
-                        date: target.date,
-                        amount: target.amount,
-                        current_streak: current_streak,
-                    });
+                    if let Some(target) = grid_targets_map.get(&date) {
+                        grid_targets.push(GridTarget {
+                            id: target.id,
+                            date: target.date,
+                            amount: target.amount,
+                            completed: true,
+                            current_streak,
+                        });
+                    }
                 }
             }
-        } else if habit.frequency_type == "monthly" {
-            let frequency_list = habit
-                .frequency_amount
-                .as_array()
-                .unwrap()
-                .to_owned()
-                .iter()
-                .map(|v| v.as_i64().unwrap() as i32)
-                .collect::<Vec<i32>>();
-
-            let frequency = frequency_list.first().unwrap();
-            let mut current_month = last_date.month();
-            let mut current_month_streak = 0;
-
-            for date in dates {
-                if current_month == date.month() {
-                    current_month_streak += 1;
-                    current_streak += 1;
+            Frequency::Weekly { times } => {
+                let mut current_week = last_date.iso_week().week();
+                let mut current_week_streak = 0;
+                for date in dates {
+                    if current_week == date.iso_week().week() {
+                        current_week_streak += 1;
+                        current_streak += 1;
 
-                    if current_streak > longest_streak {
-                        longest_streak = current_streak;
+                        if current_streak > longest_streak {
+                            longest_streak = current_streak;
+                        }
+                    } else if current_week_streak < *times && current_week_streak != 0 {
+                        current_week_streak = 0;
+                        current_streak = 1;
                     }
-                } else if &current_month_streak < frequency && current_month_streak != 0 {
-                    current_month_streak = 0;
-                    current_streak = 1;
-                }
 
-                last_date = date;
-                current_month = last_date.month();
-
-                let target = grid_targets_map.get(&date);
-                if target.is_some() {
-                    let target = target.unwrap();
-                    grid_targets.push(GridTarget {
-                        id: target.id,
-                        date: target.date,
-                        amount: target.amount,
-                        current_streak: current_streak,
-                    });
+                    last_date = date;
+                    current_week = last_date.iso_week().week();
+
+                    let target = grid_targets_map.get(&date);
+                    if target.is_some() {
+                        let target = target.unwrap();
+                        grid_targets.push(GridTarget {
+                            id: target.id,
+                            date: target.date,
+                            amount: target.amount,
+                            completed: true,
+                            current_streak: current_streak,
+                        });
+                    }
                 }
             }
-        } else if habit.frequency_type == "interval" {
-            let frequency_list = habit
-                .frequency_amount
-                .as_array()
-                .unwrap()
-                .to_owned()
-                .iter()
-                .map(|v| v.as_i64().unwrap() as i32)
-                .collect::<Vec<i32>>();
-
-            let frequency = frequency_list.first().unwrap();
-
-            for date in dates {
-                if date - last_date <= chrono::Duration::days(*frequency as i64) {
-                    current_streak += 1;
+            Frequency::Monthly { times } => {
+                let mut current_month = last_date.month();
+                let mut current_month_streak = 0;
 
-                    if current_streak > longest_streak {
-                        longest_streak = current_streak;
+                for date in dates {
+                    if current_month == date.month() {
+                        current_month_streak += 1;
+                        current_streak += 1;
+
+                        if current_streak > longest_streak {
+                            longest_streak = current_streak;
+                        }
+                    } else if current_month_streak < *times && current_month_streak != 0 {
+                        current_month_streak = 0;
+                        current_streak = 1;
+                    }
+
+                    last_date = date;
+                    current_month = last_date.month();
+
+                    let target = grid_targets_map.get(&date);
+                    if target.is_some() {
+                        let target = target.unwrap();
+                        grid_targets.push(GridTarget {
+                            id: target.id,
+                            date: target.date,
+                            amount: target.amount,
+                            completed: true,
+                            current_streak: current_streak,
+                        });
                     }
-                } else {
-                    current_streak = 1;
                 }
+            }
+            Frequency::Interval { days } => {
+                for date in dates {
+                    if date - last_date <= chrono::Duration::days(*days as i64) {
+                        current_streak += 1;
+
+                        if current_streak > longest_streak {
+                            longest_streak = current_streak;
+                        }
+                    } else {
+                        current_streak = 1;
+                    }
 
-                last_date = date;
-
-                let target = grid_targets_map.get(&date);
-                if target.is_some() {
-                    let target = target.unwrap();
-                    grid_targets.push(GridTarget {
-                        id: target.id,
-                        date: target.date,
-                        amount: target.amount,
-                        current_streak: current_streak,
-                    });
+                    last_date = date;
+
+                    let target = grid_targets_map.get(&date);
+                    if target.is_some() {
+                        let target = target.unwrap();
+                        grid_targets.push(GridTarget {
+                            id: target.id,
+                            date: target.date,
+                            amount: target.amount,
+                            completed: true,
+                            current_streak: current_streak,
+                        });
+                    }
                 }
             }
         }
 
         (current_streak, longest_streak, grid_targets)
     }
+
+    // Targets of a negative habit are slips: a streak is the clean days since the habit
+    // was created or since the last slip, up to today. Slips reset the streak to zero.
+    fn calculate_clean_streaks(
+        habit: &Habit,
+        targets: &[Target],
+        today: NaiveDate,
+    ) -> (i32, i32, Vec<GridTarget>) {
+        let mut clean_since = targets
+            .iter()
+            .map(|t| t.date)
+            .chain([habit.created_date.date_naive()])
+            .min()
+            .unwrap_or(today);
+        let mut longest_streak = 0;
+        let mut grid_targets = vec![];
+
+        for target in targets {
+            let clean_days = (target.date - clean_since).num_days() as i32;
+            longest_streak = longest_streak.max(clean_days);
+            clean_since = target.date + Duration::days(1);
+
+            grid_targets.push(GridTarget {
+                id: target.id,
+                date: target.date,
+                amount: target.amount,
+                completed: false,
+                current_streak: 0,
+            });
+        }
+
+        // Today counts as clean until a slip is logged
+        let current_streak = ((today - clean_since).num_days() as i32 + 1).max(0);
+
+        (
+            current_streak,
+            longest_streak.max(current_streak),
+            grid_targets,
+        )
+    }
+}
+
+// Habits and check-ins for unit tests, the habit is a "Read" one created on 2024-05-01
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+    use chrono::TimeZone;
+
+    pub fn habit(frequency: Frequency) -> Habit {
+        Habit {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            name: "Read".to_string(),
+            created_date: Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap(),
+            goal: 0,
+            archived: false,
+            deleted: false,
+            color: "#fff".to_string(),
+            icon: "book".to_string(),
+            amount: 1.0,
+            frequency,
+            polarity: "positive".to_string(),
+            unit: "count".to_string(),
+            goal_direction: "at_least".to_string(),
+            h_order: 0,
+            group_id: None,
+        }
+    }
+
+    pub fn target(date: NaiveDate, amount: f64) -> Target {
+        Target {
+            id: Uuid::new_v4(),
+            habit_id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            date,
+            created_date: Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap(),
+            amount,
+            deleted: false,
+            note: None,
+            mood: None,
+            attachment: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{habit, target};
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn streaks(habit: &Habit, dates: &[NaiveDate]) -> (i32, i32) {
+        let targets = dates.iter().map(|d| target(*d, 1.0)).collect();
+        let (current, longest, _) = TargetHelper::calculate_streaks(habit, targets);
+        (current, longest)
+    }
+
+    #[test]
+    fn daily_streak_breaks_on_a_missed_weekday() {
+        // Mondays, Wednesdays and Fridays, 2024-05-01 is a Wednesday
+        let habit = habit(Frequency::Daily {
+            weekdays: vec![1, 3, 5],
+        });
+        let dates = [
+            date(2024, 5, 1),
+            date(2024, 5, 3),
+            date(2024, 5, 6),
+            date(2024, 5, 10),
+        ];
+
+        assert_eq!(streaks(&habit, &dates), (1, 3));
+    }
+
+    #[test]
+    fn days_of_month_streak_needs_every_scheduled_day() {
+        let habit = habit(Frequency::DaysOfMonth { days: vec![1, 15] });
+        let dates = [
+            date(2024, 5, 1),
+            date(2024, 5, 15),
+            date(2024, 6, 1),
+            date(2024, 7, 15),
+        ];
+
+        assert_eq!(streaks(&habit, &dates), (1, 3));
+    }
+
+    #[test]
+    fn times_per_period_streak_needs_the_quota_of_each_period() {
+        let habit = habit(Frequency::TimesPerPeriod { times: 2, days: 7 });
+        let dates = [
+            date(2024, 5, 1),
+            date(2024, 5, 3),
+            date(2024, 5, 9),
+            date(2024, 5, 20),
+        ];
+
+        assert_eq!(streaks(&habit, &dates), (1, 3));
+    }
+
+    #[test]
+    fn last_weekday_streak_follows_the_last_friday_of_each_month() {
+        let habit = habit(Frequency::NthWeekday { nth: 5, weekday: 5 });
+        let dates = [date(2024, 5, 31), date(2024, 6, 28), date(2024, 8, 30)];
+
+        assert_eq!(streaks(&habit, &dates), (1, 2));
+    }
+
+    #[test]
+    fn interval_streak_allows_gaps_up_to_the_interval() {
+        let habit = habit(Frequency::Interval { days: 3 });
+        let dates = [
+            date(2024, 5, 1),
+            date(2024, 5, 4),
+            date(2024, 5, 6),
+            date(2024, 5, 10),
+        ];
+
+        assert_eq!(streaks(&habit, &dates), (1, 3));
+    }
+
+    #[test]
+    fn no_targets_means_no_streak() {
+        let habit = habit(Frequency::Weekly { times: 2 });
+
+        assert_eq!(streaks(&habit, &[]), (0, 0));
+    }
+
+    fn negative_habit() -> Habit {
+        Habit {
+            polarity: "negative".to_string(),
+            ..habit(Frequency::Daily {
+                weekdays: (0..=6).collect(),
+            })
+        }
+    }
+
+    #[test]
+    fn clean_streak_counts_days_since_creation_without_slips() {
+        let habit = negative_habit();
+
+        let (current, longest, grid) =
+            TargetHelper::calculate_clean_streaks(&habit, &[], date(2024, 5, 10));
+        assert_eq!((current, longest), (10, 10));
+        assert!(grid.is_empty());
+    }
+
+    #[test]
+    fn slips_reset_the_clean_streak() {
+        let habit = negative_habit();
+        let slips = [target(date(2024, 5, 4), 1.0), target(date(2024, 5, 6), 1.0)];
+
+        let (current, longest, grid) =
+            TargetHelper::calculate_clean_streaks(&habit, &slips, date(2024, 5, 10));
+        assert_eq!((current, longest), (4, 4));
+        assert_eq!(grid.len(), 2);
+        assert!(grid.iter().all(|t| !t.completed && t.current_streak == 0));
+    }
+
+    #[test]
+    fn a_slip_today_leaves_no_current_streak() {
+        let habit = negative_habit();
+        let slips = [target(date(2024, 5, 10), 1.0)];
+
+        let (current, longest, _) =
+            TargetHelper::calculate_clean_streaks(&habit, &slips, date(2024, 5, 10));
+        assert_eq!((current, longest), (0, 9));
+    }
+
+    #[test]
+    fn days_below_the_threshold_break_the_streak() {
+        let habit = Habit {
+            amount: 5.0,
+            ..habit(Frequency::Daily {
+                weekdays: (0..=6).collect(),
+            })
+        };
+        let targets = vec![
+            target(date(2024, 5, 1), 6.0),
+            target(date(2024, 5, 2), 3.0),
+            target(date(2024, 5, 3), 5.0),
+        ];
+
+        let (current, longest, grid) = TargetHelper::calculate_streaks(&habit, targets);
+        assert_eq!((current, longest), (1, 1));
+        assert_eq!(
+            grid.iter()
+                .map(|t| (t.date, t.completed))
+                .collect::<Vec<(NaiveDate, bool)>>(),
+            vec![
+                (date(2024, 5, 1), true),
+                (date(2024, 5, 2), false),
+                (date(2024, 5, 3), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn at_most_habits_complete_up_to_the_threshold() {
+        let habit = Habit {
+            amount: 2.0,
+            goal_direction: "at_most".to_string(),
+            ..habit(Frequency::Daily {
+                weekdays: (0..=6).collect(),
+            })
+        };
+        let targets = vec![
+            target(date(2024, 5, 1), 1.5),
+            target(date(2024, 5, 2), 2.0),
+            target(date(2024, 5, 3), 2.5),
+        ];
+
+        assert!(habit.is_completed(2.0));
+        assert!(!habit.is_completed(2.25));
+        let (current, longest, _) = TargetHelper::calculate_streaks(&habit, targets);
+        assert_eq!((current, longest), (0, 2));
+    }
+
+    #[test]
+    fn a_failed_day_breaks_an_interval_streak() {
+        let habit = Habit {
+            amount: 2.0,
+            ..habit(Frequency::Interval { days: 3 })
+        };
+        let targets = vec![
+            target(date(2024, 5, 1), 2.0),
+            target(date(2024, 5, 2), 2.0),
+            target(date(2024, 5, 3), 1.0),
+            target(date(2024, 5, 4), 3.0),
+        ];
+
+        let (current, longest, grid) = TargetHelper::calculate_streaks(&habit, targets);
+        assert_eq!((current, longest), (1, 2));
+        assert_eq!(
+            grid.iter().map(|t| t.current_streak).collect::<Vec<i32>>(),
+            vec![1, 2, 0, 1]
+        );
+    }
 }
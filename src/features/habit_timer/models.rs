@@ -0,0 +1,263 @@
+use crate::common::models::errors::{AppError, OrNotFound};
+use crate::features::habit::models::Habit;
+use crate::repository::database::Database;
+use crate::schema::{habit_timer_sessions, habits, targets};
+use actix_web::web;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// A timer left running only counts up to this long
+const MAX_SESSION_HOURS: i64 = 12;
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    PartialEq,
+)]
+#[diesel(belongs_to(Habit, foreign_key = habit_id))]
+#[diesel(table_name = habit_timer_sessions)]
+pub struct TimerSession {
+    pub id: Uuid,
+    pub habit_id: Uuid,
+    pub user_id: Uuid,
+    pub started_date: DateTime<Utc>,
+    pub stopped_date: Option<DateTime<Utc>>, // running while empty
+    pub minutes: Option<f64>,
+}
+
+impl TimerSession {
+    pub async fn get_running(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        habit_id: Uuid,
+    ) -> Result<Option<TimerSession>, AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_habit(conn, user_id, habit_id)?;
+
+        Ok(Self::running(conn, habit_id)?)
+    }
+
+    pub async fn start(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        habit_id: Uuid,
+    ) -> Result<TimerSession, AppError> {
+        let conn = &mut db.conn()?;
+        let habit = Self::get_habit(conn, user_id, habit_id)?;
+
+        if habit.unit != "minutes" {
+            return Err(AppError::Conflict("habitTimer:errors.notDurationHabit"));
+        }
+        if Self::running(conn, habit_id)?.is_some() {
+            return Err(AppError::Conflict("habitTimer:errors.alreadyRunning"));
+        }
+
+        // The running session index settles concurrent starts
+        diesel::insert_into(habit_timer_sessions::table)
+            .values(NewTimerSession {
+                habit_id,
+                user_id,
+                started_date: Utc::now(),
+            })
+            .get_result::<TimerSession>(conn)
+            .map_err(|err| match err {
+                DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                    AppError::Conflict("habitTimer:errors.alreadyRunning")
+                }
+                err => AppError::from(err),
+            })
+    }
+
+    // Adds the minutes of the session to the targets of the local days it spans,
+    // creating the targets that don't exist yet
+    pub async fn stop(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        tz: Tz,
+        habit_id: Uuid,
+    ) -> Result<TimerSession, AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_habit(conn, user_id, habit_id)?;
+
+        let session = Self::running(conn, habit_id)?
+            .ok_or(AppError::Conflict("habitTimer:errors.notRunning"))?;
+        let now = Utc::now();
+        let counted_until = now.min(session.started_date + Duration::hours(MAX_SESSION_HOURS));
+        let minutes = (counted_until - session.started_date).num_seconds() as f64 / 60.0;
+
+        conn.transaction::<_, AppError, _>(|conn| {
+            // Stopping twice at once must not count the session twice
+            let session = diesel::update(habit_timer_sessions::table.find(session.id))
+                .filter(habit_timer_sessions::stopped_date.is_null())
+                .set((
+                    habit_timer_sessions::stopped_date.eq(now),
+                    habit_timer_sessions::minutes.eq(minutes),
+                ))
+                .get_result::<TimerSession>(conn)
+                .optional()?
+                .ok_or(AppError::Conflict("habitTimer:errors.notRunning"))?;
+
+            for (date, minutes) in Self::minutes_per_day(tz, session.started_date, counted_until) {
+                let target_id = targets::table
+                    .filter(targets::habit_id.eq(habit_id))
+                    .filter(targets::date.eq(date))
+                    .filter(targets::deleted.eq(false))
+                    .select(targets::id)
+                    .first::<Uuid>(conn)
+                    .optional()?;
+
+                match target_id {
+                    Some(id) => diesel::update(targets::table.find(id))
+                        .set(targets::amount.eq(targets::amount + minutes))
+                        .execute(conn)?,
+                    None => diesel::insert_into(targets::table)
+                        .values((
+                            targets::habit_id.eq(habit_id),
+                            targets::user_id.eq(user_id),
+                            targets::date.eq(date),
+                            targets::amount.eq(minutes),
+                        ))
+                        .execute(conn)?,
+                };
+            }
+
+            Ok(session)
+        })
+    }
+
+    fn get_habit(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        habit_id: Uuid,
+    ) -> Result<Habit, AppError> {
+        habits::table
+            .filter(habits::id.eq(habit_id))
+            .filter(habits::user_id.eq(user_id))
+            .filter(habits::deleted.eq(false))
+            .select(Habit::as_select())
+            .first::<Habit>(conn)
+            .or_not_found("habit:errors.notFound")
+    }
+
+    fn running(conn: &mut PgConnection, habit_id: Uuid) -> QueryResult<Option<TimerSession>> {
+        habit_timer_sessions::table
+            .filter(habit_timer_sessions::habit_id.eq(habit_id))
+            .filter(habit_timer_sessions::stopped_date.is_null())
+            .first::<TimerSession>(conn)
+            .optional()
+    }
+
+    // Sessions running past midnight count towards each local day they cover
+    fn minutes_per_day(tz: Tz, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<(NaiveDate, f64)> {
+        let mut days = vec![];
+        let mut from = start;
+
+        while from < end {
+            let date = from.with_timezone(&tz).date_naive();
+            let to = start_of_day(tz, date + Duration::days(1)).min(end);
+
+            days.push((date, (to - from).num_seconds() as f64 / 60.0));
+            from = to;
+        }
+
+        days
+    }
+}
+
+// Midnight can be skipped by a DST change, the day then starts an hour later
+fn start_of_day(tz: Tz, date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    tz.from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(midnight + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
+#[diesel(table_name = habit_timer_sessions)]
+pub struct NewTimerSession {
+    pub habit_id: Uuid,
+    pub user_id: Uuid,
+    pub started_date: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn session_within_a_day_counts_once() {
+        let days =
+            TimerSession::minutes_per_day(Tz::UTC, utc(2024, 5, 1, 10, 0), utc(2024, 5, 1, 10, 45));
+
+        assert_eq!(days, vec![(date(2024, 5, 1), 45.0)]);
+    }
+
+    #[test]
+    fn session_past_midnight_is_split_in_utc() {
+        let days =
+            TimerSession::minutes_per_day(Tz::UTC, utc(2024, 5, 1, 23, 30), utc(2024, 5, 2, 0, 20));
+
+        assert_eq!(
+            days,
+            vec![(date(2024, 5, 1), 30.0), (date(2024, 5, 2), 20.0)]
+        );
+    }
+
+    #[test]
+    fn session_is_split_at_the_local_midnight() {
+        // 21:30 to 22:20 UTC is 23:30 to 00:20 in Madrid in summer
+        let days = TimerSession::minutes_per_day(
+            chrono_tz::Europe::Madrid,
+            utc(2024, 5, 1, 21, 30),
+            utc(2024, 5, 1, 22, 20),
+        );
+
+        assert_eq!(
+            days,
+            vec![(date(2024, 5, 1), 30.0), (date(2024, 5, 2), 20.0)]
+        );
+    }
+
+    #[test]
+    fn session_before_utc_midnight_stays_on_the_local_day() {
+        // 23:30 to 00:20 UTC is 19:30 to 20:20 in New York
+        let days = TimerSession::minutes_per_day(
+            chrono_tz::America::New_York,
+            utc(2024, 5, 1, 23, 30),
+            utc(2024, 5, 2, 0, 20),
+        );
+
+        assert_eq!(days, vec![(date(2024, 5, 1), 50.0)]);
+    }
+
+    #[test]
+    fn day_starts_after_a_skipped_midnight() {
+        // Santiago skipped from 00:00 to 01:00 on 2024-09-08
+        let start = start_of_day(chrono_tz::America::Santiago, date(2024, 9, 8));
+
+        assert_eq!(start, utc(2024, 9, 8, 4, 0));
+    }
+}
@@ -1,8 +1,20 @@
 pub mod account;
+pub mod account_terms;
 pub mod achievement;
 pub mod auth;
+pub mod balance_history;
+pub mod budget;
 pub mod category;
 pub mod habit;
+pub mod habit_group;
+pub mod habit_reminder;
 pub mod habit_target;
+pub mod habit_timer;
+pub mod recurring_transaction;
+pub mod report;
+pub mod savings_goal;
+pub mod tag;
 pub mod transaction;
+pub mod transaction_export;
+pub mod transaction_import;
 pub mod user;
@@ -0,0 +1,76 @@
+use crate::common::middlewares::auth::AuthenticationService;
+use crate::common::models::errors::AppError;
+use crate::features::recurring_transaction::models::{
+    RecurringTransaction, RecurringTransactionData, UpcomingQuery,
+};
+use crate::repository::database::Database;
+use actix_web::{delete, get, post, put, web, HttpResponse, Scope};
+use uuid::Uuid;
+
+pub fn routes() -> Scope {
+    web::scope("/recurring")
+        .service(get_recurring_transactions)
+        .service(get_upcoming)
+        .service(create_recurring_transaction)
+        .service(update_recurring_transaction)
+        .service(delete_recurring_transaction)
+}
+
+#[get("")]
+async fn get_recurring_transactions(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let recurring = RecurringTransaction::get_all(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(recurring))
+}
+
+#[get("/upcoming")]
+async fn get_upcoming(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<UpcomingQuery>,
+) -> Result<HttpResponse, AppError> {
+    let days = query.days.unwrap_or(30).clamp(1, 366);
+    let upcoming = RecurringTransaction::get_upcoming(db.clone(), user.0.id, days).await?;
+
+    Ok(HttpResponse::Ok().json(upcoming))
+}
+
+#[post("")]
+async fn create_recurring_transaction(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    form: web::Json<RecurringTransactionData>,
+) -> Result<HttpResponse, AppError> {
+    let id = RecurringTransaction::create(db.clone(), user.0.id, form.into_inner()).await?;
+    let recurring = RecurringTransaction::get_by_id(db.clone(), user.0.id, id).await?;
+
+    Ok(HttpResponse::Ok().json(recurring))
+}
+
+#[put("/{id}")]
+async fn update_recurring_transaction(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+    form: web::Json<RecurringTransactionData>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    RecurringTransaction::update(db.clone(), user.0.id, id, form.into_inner()).await?;
+    let recurring = RecurringTransaction::get_by_id(db.clone(), user.0.id, id).await?;
+
+    Ok(HttpResponse::Ok().json(recurring))
+}
+
+#[delete("/{id}")]
+async fn delete_recurring_transaction(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    RecurringTransaction::delete(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("Recurring transaction deleted"))
+}
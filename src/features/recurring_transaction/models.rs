@@ -0,0 +1,584 @@
+use crate::common::models::errors::{AppError, FormError, OrNotFound};
+use crate::features::account::models::Account;
+use crate::features::category::models::Category;
+use crate::features::transaction::models::{NewTransaction, Transaction};
+use crate::features::user::models::User;
+use crate::repository::database::Database;
+use crate::schema::{accounts, categories, recurring_transactions, transactions};
+use actix_web::web;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    PartialEq,
+)]
+#[diesel(belongs_to(User, foreign_key = user_id))]
+#[diesel(belongs_to(Account, foreign_key = account_id))]
+#[diesel(belongs_to(Category, foreign_key = category_id))]
+#[diesel(table_name = recurring_transactions)]
+pub struct RecurringTransaction {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    pub category_id: Uuid,
+    pub transaction_type: String, // "income", "expense"
+    pub note: Option<String>,
+    pub amount: f64,
+    pub interval_unit: String, // "day", "week", "month"
+    pub interval_count: i32,
+    pub day_of_month: Option<i32>, // only for "month", clamped to the length of the month
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub next_date: NaiveDate,
+    pub active: bool,
+    pub created_date: DateTime<Utc>,
+}
+
+impl RecurringTransaction {
+    pub async fn get_all(
+        db: web::Data<Database>,
+        user_id: Uuid,
+    ) -> Result<Vec<RecurringTransaction>, AppError> {
+        Ok(recurring_transactions::table
+            .filter(recurring_transactions::user_id.eq(user_id))
+            .order(recurring_transactions::next_date.asc())
+            .load::<RecurringTransaction>(&mut db.conn()?)?)
+    }
+
+    pub async fn get_by_id(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<RecurringTransaction, AppError> {
+        recurring_transactions::table
+            .filter(recurring_transactions::id.eq(id))
+            .filter(recurring_transactions::user_id.eq(user_id))
+            .first::<RecurringTransaction>(&mut db.conn()?)
+            .or_not_found("recurring:errors.notFound")
+    }
+
+    pub async fn create(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        data: RecurringTransactionData,
+    ) -> Result<Uuid, AppError> {
+        data.validate()?;
+
+        let conn = &mut db.conn()?;
+        Self::check_ownership(conn, user_id, &data)?;
+
+        diesel::insert_into(recurring_transactions::table)
+            .values(NewRecurringTransaction::create(&data, user_id))
+            .get_result::<RecurringTransaction>(conn)
+            .map(|r| r.id)
+            .map_err(AppError::from)
+    }
+
+    pub async fn update(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+        data: RecurringTransactionData,
+    ) -> Result<(), AppError> {
+        data.validate()?;
+
+        let current = Self::get_by_id(db.clone(), user_id, id).await?;
+
+        let conn = &mut db.conn()?;
+        Self::check_ownership(conn, user_id, &data)?;
+
+        // Occurrences before the current next date were already posted, don't post them again
+        let rule = NewRecurringTransaction::create(&data, user_id);
+        let mut next_date = rule.next_date;
+        while next_date < current.next_date {
+            next_date = Recurrence::from(&rule).next(next_date);
+        }
+
+        diesel::update(recurring_transactions::table)
+            .filter(recurring_transactions::id.eq(id))
+            .set(UpdateRecurringTransaction::create(&data, next_date))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    pub async fn delete(db: web::Data<Database>, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        diesel::delete(
+            recurring_transactions::table
+                .filter(recurring_transactions::id.eq(id))
+                .filter(recurring_transactions::user_id.eq(user_id)),
+        )
+        .execute(&mut db.conn()?)?;
+
+        Ok(())
+    }
+
+    pub async fn get_upcoming(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        days: i64,
+    ) -> Result<Vec<UpcomingTransaction>, AppError> {
+        let until = Utc::now().date_naive() + Duration::days(days);
+
+        let templates: Vec<RecurringTransaction> = recurring_transactions::table
+            .filter(recurring_transactions::user_id.eq(user_id))
+            .filter(recurring_transactions::active.eq(true))
+            .filter(recurring_transactions::next_date.le(until))
+            .load::<RecurringTransaction>(&mut db.conn()?)?;
+
+        let mut upcoming: Vec<UpcomingTransaction> = templates
+            .iter()
+            .flat_map(|r| {
+                r.occurrences(until)
+                    .into_iter()
+                    .map(|date| UpcomingTransaction::create(r, date))
+            })
+            .collect();
+
+        upcoming.sort_by_key(|u| u.date);
+
+        Ok(upcoming)
+    }
+
+    // Posts every occurrence that became due, catching up on the ones missed while the
+    // server was down. Occurrences already present in `transactions` are skipped, so
+    // running this again after a partial run never posts twice.
+    pub async fn post_due(db: web::Data<Database>) -> Result<usize, AppError> {
+        let today = Utc::now().date_naive();
+        let conn = &mut db.conn()?;
+
+        let due: Vec<RecurringTransaction> = recurring_transactions::table
+            .filter(recurring_transactions::active.eq(true))
+            .filter(recurring_transactions::next_date.le(today))
+            .load::<RecurringTransaction>(conn)?;
+
+        let mut posted = 0;
+
+        'recurring: for r in due {
+            let dates = r.occurrences(today);
+
+            for date in dates.iter().copied() {
+                let already_posted = transactions::table
+                    .filter(transactions::recurring_id.eq(r.id))
+                    .filter(transactions::recurring_date.eq(date))
+                    .count()
+                    .get_result::<i64>(conn)?
+                    > 0;
+
+                if !already_posted {
+                    // An archived account or a retyped category stops this schedule only,
+                    // it resumes from the same date once the references are valid again
+                    match Transaction::post(conn, &r.occurrence(date)) {
+                        Ok(_) => posted += 1,
+                        Err(err @ AppError::Validation(_)) => {
+                            log::warn!("skipping recurring transaction {}: {}", r.id, err);
+                            continue 'recurring;
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+
+            let next_date = dates
+                .last()
+                .map_or(r.next_date, |last| Recurrence::from(&r).next(*last));
+
+            diesel::update(recurring_transactions::table)
+                .filter(recurring_transactions::id.eq(r.id))
+                .set((
+                    recurring_transactions::next_date.eq(next_date),
+                    recurring_transactions::active
+                        .eq(r.end_date.is_none_or(|end| next_date <= end)),
+                ))
+                .execute(conn)?;
+        }
+
+        Ok(posted)
+    }
+
+    fn occurrences(&self, until: NaiveDate) -> Vec<NaiveDate> {
+        let recurrence = Recurrence::from(self);
+        let until = match self.end_date {
+            Some(end) if end < until => end,
+            _ => until,
+        };
+
+        let mut dates = vec![];
+        let mut date = self.next_date;
+        while date <= until {
+            dates.push(date);
+            date = recurrence.next(date);
+        }
+
+        dates
+    }
+
+    fn occurrence(&self, date: NaiveDate) -> NewTransaction {
+        NewTransaction {
+            user_id: self.user_id,
+            account_id: self.account_id,
+            category_id: self.category_id,
+            transaction_type: self.transaction_type.clone(),
+            note: self.note.clone(),
+            amount: self.amount,
+            created_date: date.and_time(NaiveTime::MIN).and_utc(),
+            recurring_id: Some(self.id),
+            recurring_date: Some(date),
+            external_id: None,
+        }
+    }
+
+    fn check_ownership(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        data: &RecurringTransactionData,
+    ) -> Result<(), AppError> {
+        let accounts_count = accounts::table
+            .filter(accounts::id.eq(data.account_id))
+            .filter(accounts::user_id.eq(user_id))
+            .count()
+            .get_result::<i64>(conn)?;
+
+        let categories_count = categories::table
+            .filter(categories::id.eq(data.category_id))
+            .filter(categories::user_id.eq(user_id))
+            .count()
+            .get_result::<i64>(conn)?;
+
+        if accounts_count == 0 {
+            return Err(AppError::field(
+                "account_id",
+                "recurring:account.errors.notFound",
+            ));
+        }
+        if categories_count == 0 {
+            return Err(AppError::field(
+                "category_id",
+                "recurring:category.errors.notFound",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Recurrence {
+    interval_unit: String,
+    interval_count: i32,
+    day_of_month: Option<i32>,
+    start_date: NaiveDate,
+}
+
+impl Recurrence {
+    pub fn first(&self) -> NaiveDate {
+        match (self.interval_unit.as_str(), self.day_of_month) {
+            ("month", Some(day)) => {
+                let candidate = Self::with_day(self.start_date, day);
+                if candidate < self.start_date {
+                    Self::with_day(self.start_date + Months::new(1), day)
+                } else {
+                    candidate
+                }
+            }
+            _ => self.start_date,
+        }
+    }
+
+    pub fn next(&self, date: NaiveDate) -> NaiveDate {
+        let count = self.interval_count.max(1);
+        match self.interval_unit.as_str() {
+            "week" => date + Duration::weeks(count as i64),
+            "month" => {
+                let day = self.day_of_month.unwrap_or(self.start_date.day() as i32);
+                Self::with_day(date.with_day(1).unwrap() + Months::new(count as u32), day)
+            }
+            _ => date + Duration::days(count as i64),
+        }
+    }
+
+    // Same month as `date`, on `day` or the last day of the month if it is shorter
+    fn with_day(date: NaiveDate, day: i32) -> NaiveDate {
+        let first = date.with_day(1).unwrap();
+        let last = (first + Months::new(1) - Duration::days(1)).day();
+        first.with_day((day as u32).clamp(1, last)).unwrap()
+    }
+}
+
+impl From<&RecurringTransaction> for Recurrence {
+    fn from(r: &RecurringTransaction) -> Self {
+        Self {
+            interval_unit: r.interval_unit.clone(),
+            interval_count: r.interval_count,
+            day_of_month: r.day_of_month,
+            start_date: r.start_date,
+        }
+    }
+}
+
+impl From<&NewRecurringTransaction> for Recurrence {
+    fn from(r: &NewRecurringTransaction) -> Self {
+        Self {
+            interval_unit: r.interval_unit.clone(),
+            interval_count: r.interval_count,
+            day_of_month: r.day_of_month,
+            start_date: r.start_date,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
+#[diesel(table_name = recurring_transactions)]
+pub struct NewRecurringTransaction {
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    pub category_id: Uuid,
+    pub transaction_type: String,
+    pub note: Option<String>,
+    pub amount: f64,
+    pub interval_unit: String,
+    pub interval_count: i32,
+    pub day_of_month: Option<i32>,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub next_date: NaiveDate,
+    pub active: bool,
+}
+
+impl NewRecurringTransaction {
+    pub fn create(data: &RecurringTransactionData, user_id: Uuid) -> Self {
+        let mut new = Self {
+            user_id,
+            account_id: data.account_id,
+            category_id: data.category_id,
+            transaction_type: data.transaction_type.clone(),
+            note: data.note.clone(),
+            amount: data.amount,
+            interval_unit: data.interval_unit.clone(),
+            interval_count: data.interval_count,
+            day_of_month: data.day_of_month,
+            start_date: data.start_date,
+            end_date: data.end_date,
+            next_date: data.start_date,
+            active: data.active.unwrap_or(true),
+        };
+        new.next_date = Recurrence::from(&new).first();
+        new
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, AsChangeset)]
+#[diesel(table_name = recurring_transactions, treat_none_as_null = true)]
+pub struct UpdateRecurringTransaction {
+    pub account_id: Uuid,
+    pub category_id: Uuid,
+    pub transaction_type: String,
+    pub note: Option<String>,
+    pub amount: f64,
+    pub interval_unit: String,
+    pub interval_count: i32,
+    pub day_of_month: Option<i32>,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub next_date: NaiveDate,
+    pub active: bool,
+}
+
+impl UpdateRecurringTransaction {
+    pub fn create(data: &RecurringTransactionData, next_date: NaiveDate) -> Self {
+        Self {
+            account_id: data.account_id,
+            category_id: data.category_id,
+            transaction_type: data.transaction_type.clone(),
+            note: data.note.clone(),
+            amount: data.amount,
+            interval_unit: data.interval_unit.clone(),
+            interval_count: data.interval_count,
+            day_of_month: data.day_of_month,
+            start_date: data.start_date,
+            end_date: data.end_date,
+            next_date,
+            active: data.active.unwrap_or(true),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecurringTransactionData {
+    pub account_id: Uuid,
+    pub category_id: Uuid,
+    pub transaction_type: String,
+    pub note: Option<String>,
+    pub amount: f64,
+    pub interval_unit: String,
+    pub interval_count: i32,
+    pub day_of_month: Option<i32>,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub active: Option<bool>,
+}
+
+impl RecurringTransactionData {
+    fn validate(&self) -> Result<(), AppError> {
+        let mut errors = vec![];
+        if self.transaction_type != "income" && self.transaction_type != "expense" {
+            errors.push(FormError {
+                field: "transaction_type",
+                message: "recurring:type.errors.invalid",
+            });
+        }
+        if !["day", "week", "month"].contains(&self.interval_unit.as_str()) {
+            errors.push(FormError {
+                field: "interval_unit",
+                message: "recurring:intervalUnit.errors.invalid",
+            });
+        }
+        if self.interval_count < 1 {
+            errors.push(FormError {
+                field: "interval_count",
+                message: "recurring:intervalCount.errors.notPositive",
+            });
+        }
+        if let Some(day) = self.day_of_month {
+            if self.interval_unit != "month" || !(1..=31).contains(&day) {
+                errors.push(FormError {
+                    field: "day_of_month",
+                    message: "recurring:dayOfMonth.errors.invalid",
+                });
+            }
+        }
+        if self.end_date.is_some_and(|end| end < self.start_date) {
+            errors.push(FormError {
+                field: "end_date",
+                message: "recurring:endDate.errors.beforeStart",
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(errors))
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpcomingTransaction {
+    pub recurring_id: Uuid,
+    pub date: NaiveDate,
+    pub account_id: Uuid,
+    pub category_id: Uuid,
+    pub transaction_type: String,
+    pub note: Option<String>,
+    pub amount: f64,
+}
+
+impl UpcomingTransaction {
+    pub fn create(r: &RecurringTransaction, date: NaiveDate) -> Self {
+        Self {
+            recurring_id: r.id,
+            date,
+            account_id: r.account_id,
+            category_id: r.category_id,
+            transaction_type: r.transaction_type.clone(),
+            note: r.note.clone(),
+            amount: r.amount,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpcomingQuery {
+    pub days: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn recurrence(
+        unit: &str,
+        count: i32,
+        day_of_month: Option<i32>,
+        start: NaiveDate,
+    ) -> Recurrence {
+        Recurrence {
+            interval_unit: unit.to_string(),
+            interval_count: count,
+            day_of_month,
+            start_date: start,
+        }
+    }
+
+    #[test]
+    fn day_of_month_31_falls_on_the_last_day_of_shorter_months() {
+        let recurrence = recurrence("month", 1, Some(31), date(2024, 1, 10));
+
+        let first = recurrence.first();
+        assert_eq!(first, date(2024, 1, 31));
+        let second = recurrence.next(first);
+        assert_eq!(second, date(2024, 2, 29));
+        let third = recurrence.next(second);
+        assert_eq!(third, date(2024, 3, 31));
+        assert_eq!(recurrence.next(third), date(2024, 4, 30));
+    }
+
+    #[test]
+    fn day_of_month_already_past_starts_next_month() {
+        let recurrence = recurrence("month", 1, Some(5), date(2023, 1, 20));
+
+        assert_eq!(recurrence.first(), date(2023, 2, 5));
+        assert_eq!(recurrence.next(date(2023, 2, 5)), date(2023, 3, 5));
+    }
+
+    #[test]
+    fn months_without_day_keep_the_start_day() {
+        // A non-leap February shortens one occurrence without shifting the others
+        let recurrence = recurrence("month", 1, None, date(2023, 1, 31));
+
+        assert_eq!(recurrence.first(), date(2023, 1, 31));
+        assert_eq!(recurrence.next(date(2023, 1, 31)), date(2023, 2, 28));
+        assert_eq!(recurrence.next(date(2023, 2, 28)), date(2023, 3, 31));
+    }
+
+    #[test]
+    fn quarterly_recurrence_skips_months() {
+        let recurrence = recurrence("month", 3, Some(30), date(2023, 11, 30));
+
+        assert_eq!(recurrence.next(date(2023, 11, 30)), date(2024, 2, 29));
+        assert_eq!(recurrence.next(date(2024, 2, 29)), date(2024, 5, 30));
+    }
+
+    #[test]
+    fn day_and_week_recurrences_add_their_interval() {
+        let start = date(2024, 2, 27);
+
+        assert_eq!(
+            recurrence("day", 3, None, start).next(start),
+            date(2024, 3, 1)
+        );
+        assert_eq!(
+            recurrence("week", 2, None, start).next(start),
+            date(2024, 3, 12)
+        );
+        // A zero count still moves forward
+        assert_eq!(
+            recurrence("day", 0, None, start).next(start),
+            date(2024, 2, 28)
+        );
+        assert_eq!(recurrence("week", 1, None, start).first(), start);
+    }
+}
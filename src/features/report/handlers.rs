@@ -0,0 +1,95 @@
+use crate::common::middlewares::auth::AuthenticationService;
+use crate::common::models::errors::AppError;
+use crate::features::balance_history::models::{BalanceHistory, HistoryQuery};
+use crate::features::report::models::{CashFlowQuery, CategoryReportQuery, DateRange, Report};
+use crate::repository::database::Database;
+use actix_web::{get, web, HttpResponse, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/reports")
+        .service(get_cash_flow)
+        .service(get_by_category)
+        .service(get_by_account)
+        .service(get_by_tag)
+        .service(get_comparison)
+        .service(get_net_worth)
+}
+
+#[get("/cash-flow")]
+async fn get_cash_flow(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<CashFlowQuery>,
+) -> Result<HttpResponse, AppError> {
+    let report = Report::cash_flow(db.clone(), user.0.id, query.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[get("/categories")]
+async fn get_by_category(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<CategoryReportQuery>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    let range = DateRange {
+        from: query.from,
+        to: query.to,
+    };
+
+    let report = Report::by_category(
+        db.clone(),
+        user.0.id,
+        range,
+        query.category_type,
+        query.rollup.unwrap_or(false),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[get("/accounts")]
+async fn get_by_account(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<DateRange>,
+) -> Result<HttpResponse, AppError> {
+    let report = Report::by_account(db.clone(), user.0.id, query.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[get("/tags")]
+async fn get_by_tag(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<DateRange>,
+) -> Result<HttpResponse, AppError> {
+    let report = Report::by_tag(db.clone(), user.0.id, query.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[get("/comparison")]
+async fn get_comparison(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<DateRange>,
+) -> Result<HttpResponse, AppError> {
+    let report = Report::comparison(db.clone(), user.0.id, query.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[get("/net-worth")]
+async fn get_net_worth(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<HistoryQuery>,
+) -> Result<HttpResponse, AppError> {
+    let report = BalanceHistory::net_worth(db.clone(), user.0.id, query.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
@@ -0,0 +1,381 @@
+use crate::common::models::errors::AppError;
+use crate::features::transaction::models::CATEGORY_LINES_SQL;
+use crate::repository::database::Database;
+use actix_web::web;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Bool, Date, Float8, Text, Timestamptz, Varchar};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl DateRange {
+    pub fn start(&self) -> DateTime<Utc> {
+        self.from.and_time(NaiveTime::MIN).and_utc()
+    }
+
+    // `to` is inclusive, so the range ends at the start of the following day
+    pub fn end(&self) -> DateTime<Utc> {
+        (self.to + Duration::days(1))
+            .and_time(NaiveTime::MIN)
+            .and_utc()
+    }
+
+    pub fn previous(&self) -> DateRange {
+        let days = (self.to - self.from).num_days() + 1;
+        DateRange {
+            from: self.from - Duration::days(days),
+            to: self.from - Duration::days(1),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportInterval {
+    Day,
+    Week,
+    #[default]
+    Month,
+}
+
+impl ReportInterval {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CashFlowQuery {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub interval: Option<ReportInterval>,
+    pub account_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryReportQuery {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    #[serde(rename = "type")]
+    pub category_type: Option<String>,
+    pub rollup: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, QueryableByName)]
+pub struct CashFlowEntry {
+    #[diesel(sql_type = Date)]
+    pub period: NaiveDate,
+    #[diesel(sql_type = Float8)]
+    pub income: f64,
+    #[diesel(sql_type = Float8)]
+    pub expense: f64,
+    #[diesel(sql_type = Float8)]
+    pub net: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, QueryableByName)]
+pub struct CategoryTotal {
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub category_id: Uuid,
+    #[diesel(sql_type = Varchar)]
+    pub name: String,
+    #[diesel(sql_type = Varchar)]
+    pub icon: String,
+    #[diesel(sql_type = Varchar)]
+    pub color: String,
+    #[diesel(sql_type = Varchar)]
+    pub category_type: String,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Uuid>)]
+    pub parent_id: Option<Uuid>,
+    #[diesel(sql_type = Float8)]
+    pub total: f64,
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, QueryableByName)]
+pub struct AccountTotal {
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub account_id: Uuid,
+    #[diesel(sql_type = Varchar)]
+    pub name: String,
+    #[diesel(sql_type = Varchar)]
+    pub currency: String,
+    #[diesel(sql_type = Float8)]
+    pub income: f64,
+    #[diesel(sql_type = Float8)]
+    pub expense: f64,
+    #[diesel(sql_type = Float8)]
+    pub net: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, QueryableByName)]
+pub struct TagTotal {
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub tag_id: Uuid,
+    #[diesel(sql_type = Varchar)]
+    pub name: String,
+    #[diesel(sql_type = Varchar)]
+    pub color: String,
+    #[diesel(sql_type = Float8)]
+    pub income: f64,
+    #[diesel(sql_type = Float8)]
+    pub expense: f64,
+    #[diesel(sql_type = Float8)]
+    pub net: f64,
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, QueryableByName)]
+pub struct PeriodTotals {
+    #[diesel(sql_type = Float8)]
+    pub income: f64,
+    #[diesel(sql_type = Float8)]
+    pub expense: f64,
+    #[diesel(sql_type = Float8)]
+    pub net: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryChange {
+    pub category_id: Uuid,
+    pub name: String,
+    pub category_type: String,
+    pub current: f64,
+    pub previous: f64,
+    pub change: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PeriodComparison {
+    pub current_range: DateRange,
+    pub previous_range: DateRange,
+    pub current: PeriodTotals,
+    pub previous: PeriodTotals,
+    pub income_change: Option<f64>,
+    pub expense_change: Option<f64>,
+    pub categories: Vec<CategoryChange>,
+}
+
+pub struct Report {}
+
+impl Report {
+    pub async fn cash_flow(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        query: CashFlowQuery,
+    ) -> Result<Vec<CashFlowEntry>, AppError> {
+        let range = DateRange {
+            from: query.from,
+            to: query.to,
+        };
+
+        sql_query(
+            "SELECT date_trunc($1, t.created_date)::date AS period,
+                    COALESCE(SUM(t.amount) FILTER (WHERE t.transaction_type = 'income'), 0) AS income,
+                    COALESCE(SUM(t.amount) FILTER (WHERE t.transaction_type = 'expense'), 0) AS expense,
+                    COALESCE(SUM(CASE WHEN t.transaction_type = 'income' THEN t.amount ELSE -t.amount END), 0) AS net
+             FROM transactions t
+             WHERE t.user_id = $2
+               AND t.created_date >= $3
+               AND t.created_date < $4
+               AND ($5::uuid IS NULL OR t.account_id = $5)
+             GROUP BY period
+             ORDER BY period",
+        )
+        .bind::<Text, _>(query.interval.unwrap_or_default().as_sql())
+        .bind::<diesel::sql_types::Uuid, _>(user_id)
+        .bind::<Timestamptz, _>(range.start())
+        .bind::<Timestamptz, _>(range.end())
+        .bind::<diesel::sql_types::Nullable<diesel::sql_types::Uuid>, _>(query.account_id)
+        .load::<CashFlowEntry>(&mut db.conn()?)
+        .map_err(AppError::from)
+    }
+
+    // With `rollup` subcategory totals are folded into their parent category
+    pub async fn by_category(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        range: DateRange,
+        category_type: Option<String>,
+        rollup: bool,
+    ) -> Result<Vec<CategoryTotal>, AppError> {
+        sql_query(format!(
+            "SELECT c.id AS category_id, c.name, c.icon, c.color, c.category_type, c.parent_id,
+                    SUM(t.amount) AS total,
+                    COUNT(DISTINCT t.id) AS count
+             FROM {} t
+             INNER JOIN categories s ON s.id = t.category_id
+             INNER JOIN categories c
+                ON c.id = CASE WHEN $5 THEN COALESCE(s.parent_id, s.id) ELSE s.id END
+             WHERE t.user_id = $1
+               AND t.created_date >= $2
+               AND t.created_date < $3
+               AND ($4::varchar IS NULL OR c.category_type = $4)
+             GROUP BY c.id, c.name, c.icon, c.color, c.category_type, c.parent_id
+             ORDER BY total DESC",
+            CATEGORY_LINES_SQL
+        ))
+        .bind::<diesel::sql_types::Uuid, _>(user_id)
+        .bind::<Timestamptz, _>(range.start())
+        .bind::<Timestamptz, _>(range.end())
+        .bind::<diesel::sql_types::Nullable<Varchar>, _>(category_type)
+        .bind::<Bool, _>(rollup)
+        .load::<CategoryTotal>(&mut db.conn()?)
+        .map_err(AppError::from)
+    }
+
+    pub async fn by_account(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        range: DateRange,
+    ) -> Result<Vec<AccountTotal>, AppError> {
+        sql_query(
+            "SELECT a.id AS account_id, a.name, a.currency,
+                    COALESCE(SUM(t.amount) FILTER (WHERE t.transaction_type = 'income'), 0) AS income,
+                    COALESCE(SUM(t.amount) FILTER (WHERE t.transaction_type = 'expense'), 0) AS expense,
+                    COALESCE(SUM(CASE WHEN t.transaction_type = 'income' THEN t.amount ELSE -t.amount END), 0) AS net
+             FROM transactions t
+             INNER JOIN accounts a ON a.id = t.account_id
+             WHERE t.user_id = $1
+               AND t.created_date >= $2
+               AND t.created_date < $3
+             GROUP BY a.id, a.name, a.currency, a.a_order
+             ORDER BY a.a_order",
+        )
+        .bind::<diesel::sql_types::Uuid, _>(user_id)
+        .bind::<Timestamptz, _>(range.start())
+        .bind::<Timestamptz, _>(range.end())
+        .load::<AccountTotal>(&mut db.conn()?)
+        .map_err(AppError::from)
+    }
+
+    // A transaction with several tags counts fully towards each of them
+    pub async fn by_tag(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        range: DateRange,
+    ) -> Result<Vec<TagTotal>, AppError> {
+        sql_query(
+            "SELECT g.id AS tag_id, g.name, g.color,
+                    COALESCE(SUM(t.amount) FILTER (WHERE t.transaction_type = 'income'), 0) AS income,
+                    COALESCE(SUM(t.amount) FILTER (WHERE t.transaction_type = 'expense'), 0) AS expense,
+                    COALESCE(SUM(CASE WHEN t.transaction_type = 'income' THEN t.amount ELSE -t.amount END), 0) AS net,
+                    COUNT(t.id) AS count
+             FROM transactions t
+             INNER JOIN transaction_tags tt ON tt.transaction_id = t.id
+             INNER JOIN tags g ON g.id = tt.tag_id
+             WHERE t.user_id = $1
+               AND t.created_date >= $2
+               AND t.created_date < $3
+             GROUP BY g.id, g.name, g.color
+             ORDER BY expense DESC, g.name",
+        )
+        .bind::<diesel::sql_types::Uuid, _>(user_id)
+        .bind::<Timestamptz, _>(range.start())
+        .bind::<Timestamptz, _>(range.end())
+        .load::<TagTotal>(&mut db.conn()?)
+        .map_err(AppError::from)
+    }
+
+    pub async fn totals(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        range: DateRange,
+    ) -> Result<PeriodTotals, AppError> {
+        sql_query(
+            "SELECT COALESCE(SUM(t.amount) FILTER (WHERE t.transaction_type = 'income'), 0) AS income,
+                    COALESCE(SUM(t.amount) FILTER (WHERE t.transaction_type = 'expense'), 0) AS expense,
+                    COALESCE(SUM(CASE WHEN t.transaction_type = 'income' THEN t.amount ELSE -t.amount END), 0) AS net
+             FROM transactions t
+             WHERE t.user_id = $1
+               AND t.created_date >= $2
+               AND t.created_date < $3",
+        )
+        .bind::<diesel::sql_types::Uuid, _>(user_id)
+        .bind::<Timestamptz, _>(range.start())
+        .bind::<Timestamptz, _>(range.end())
+        .get_result::<PeriodTotals>(&mut db.conn()?)
+        .map_err(AppError::from)
+    }
+
+    pub async fn comparison(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        range: DateRange,
+    ) -> Result<PeriodComparison, AppError> {
+        let previous_range = range.previous();
+
+        let current = Self::totals(db.clone(), user_id, range).await?;
+        let previous = Self::totals(db.clone(), user_id, previous_range).await?;
+
+        let current_categories = Self::by_category(db.clone(), user_id, range, None, false).await?;
+        let previous_categories =
+            Self::by_category(db.clone(), user_id, previous_range, None, false).await?;
+
+        let mut categories: Vec<CategoryChange> = current_categories
+            .iter()
+            .map(|c| {
+                let previous = previous_categories
+                    .iter()
+                    .find(|p| p.category_id == c.category_id)
+                    .map(|p| p.total)
+                    .unwrap_or(0.0);
+
+                CategoryChange {
+                    category_id: c.category_id,
+                    name: c.name.clone(),
+                    category_type: c.category_type.clone(),
+                    current: c.total,
+                    previous,
+                    change: Self::change(c.total, previous),
+                }
+            })
+            .collect();
+
+        for p in previous_categories.iter().filter(|p| {
+            !current_categories
+                .iter()
+                .any(|c| c.category_id == p.category_id)
+        }) {
+            categories.push(CategoryChange {
+                category_id: p.category_id,
+                name: p.name.clone(),
+                category_type: p.category_type.clone(),
+                current: 0.0,
+                previous: p.total,
+                change: Self::change(0.0, p.total),
+            });
+        }
+
+        Ok(PeriodComparison {
+            current_range: range,
+            previous_range,
+            income_change: Self::change(current.income, previous.income),
+            expense_change: Self::change(current.expense, previous.expense),
+            current,
+            previous,
+            categories,
+        })
+    }
+
+    // Percentage change, undefined when there is nothing to compare with
+    fn change(current: f64, previous: f64) -> Option<f64> {
+        if previous == 0.0 {
+            return None;
+        }
+        Some((current - previous) / previous.abs() * 100.0)
+    }
+}
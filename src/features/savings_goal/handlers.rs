@@ -0,0 +1,133 @@
+use crate::common::middlewares::auth::AuthenticationService;
+use crate::common::models::errors::AppError;
+use crate::features::savings_goal::models::{ContributionData, SavingsGoal, SavingsGoalData};
+use crate::repository::database::Database;
+use actix_web::{delete, get, post, put, web, HttpResponse, Scope};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+pub fn routes() -> Scope {
+    web::scope("/savings")
+        .service(get_goals)
+        .service(get_goal)
+        .service(create_goal)
+        .service(update_goal)
+        .service(delete_goal)
+        .service(get_contributions)
+        .service(add_contribution)
+        .service(delete_contribution)
+}
+
+#[get("")]
+async fn get_goals(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let goals = SavingsGoal::get_all(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(goals))
+}
+
+#[get("/{id}")]
+async fn get_goal(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let goal = SavingsGoal::get(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(goal))
+}
+
+#[post("")]
+async fn create_goal(
+    achievements_data: web::Data<mpsc::UnboundedSender<Vec<String>>>,
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    form: web::Json<SavingsGoalData>,
+) -> Result<HttpResponse, AppError> {
+    let goal = SavingsGoal::create(
+        db.clone(),
+        achievements_data.get_ref().clone(),
+        user.0.id,
+        form.into_inner(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(goal))
+}
+
+#[put("/{id}")]
+async fn update_goal(
+    achievements_data: web::Data<mpsc::UnboundedSender<Vec<String>>>,
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+    form: web::Json<SavingsGoalData>,
+) -> Result<HttpResponse, AppError> {
+    let goal = SavingsGoal::update(
+        db.clone(),
+        achievements_data.get_ref().clone(),
+        user.0.id,
+        path.into_inner(),
+        form.into_inner(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(goal))
+}
+
+#[delete("/{id}")]
+async fn delete_goal(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    SavingsGoal::delete(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("Savings goal deleted"))
+}
+
+#[get("/{id}/contributions")]
+async fn get_contributions(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let contributions =
+        SavingsGoal::get_contributions(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(contributions))
+}
+
+#[post("/{id}/contributions")]
+async fn add_contribution(
+    achievements_data: web::Data<mpsc::UnboundedSender<Vec<String>>>,
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+    form: web::Json<ContributionData>,
+) -> Result<HttpResponse, AppError> {
+    let goal = SavingsGoal::contribute(
+        db.clone(),
+        achievements_data.get_ref().clone(),
+        user.0.id,
+        path.into_inner(),
+        form.into_inner(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(goal))
+}
+
+#[delete("/{id}/contributions/{contribution_id}")]
+async fn delete_contribution(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, AppError> {
+    let (id, contribution_id) = path.into_inner();
+    SavingsGoal::delete_contribution(db.clone(), user.0.id, id, contribution_id).await?;
+
+    Ok(HttpResponse::Ok().body("Contribution deleted"))
+}
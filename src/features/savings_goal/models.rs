@@ -0,0 +1,485 @@
+use crate::common::models::errors::{AppError, OrNotFound};
+use crate::features::account::models::Account;
+use crate::features::achievement::models::Achievement;
+use crate::features::user::models::User;
+use crate::repository::database::Database;
+use crate::schema::{
+    accounts, savings_goal_contributions, savings_goals, tags, transaction_tags, transactions,
+};
+use actix_web::web;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    PartialEq,
+)]
+#[diesel(belongs_to(User, foreign_key = user_id))]
+#[diesel(table_name = savings_goals)]
+pub struct SavingsGoal {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub target_amount: f64,
+    pub currency: String,
+    pub deadline: Option<NaiveDate>,
+    pub account_id: Option<Uuid>, // progress is the balance of the account
+    pub tag_id: Option<Uuid>,     // progress is the sum of the tagged transactions
+    pub completed_date: Option<DateTime<Utc>>,
+    pub created_date: DateTime<Utc>,
+}
+
+impl SavingsGoal {
+    pub async fn get_all(
+        db: web::Data<Database>,
+        user_id: Uuid,
+    ) -> Result<Vec<SavingsGoalDetails>, AppError> {
+        let conn = &mut db.conn()?;
+
+        savings_goals::table
+            .filter(savings_goals::user_id.eq(user_id))
+            .order(savings_goals::created_date.asc())
+            .load::<SavingsGoal>(conn)?
+            .into_iter()
+            .map(|goal| goal.details(conn))
+            .collect()
+    }
+
+    pub async fn get(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<SavingsGoalDetails, AppError> {
+        let conn = &mut db.conn()?;
+
+        Self::get_own(conn, user_id, id)?.details(conn)
+    }
+
+    pub async fn create(
+        db: web::Data<Database>,
+        achievements_sender: mpsc::UnboundedSender<Vec<String>>,
+        user_id: Uuid,
+        data: SavingsGoalData,
+    ) -> Result<SavingsGoalDetails, AppError> {
+        let conn = &mut db.conn()?;
+        let data = data.normalized(conn, user_id)?;
+
+        let goal = diesel::insert_into(savings_goals::table)
+            .values(NewSavingsGoal::create(data, user_id))
+            .get_result::<SavingsGoal>(conn)?;
+
+        Self::check(conn, Some(&achievements_sender), goal)
+    }
+
+    // Changing a goal reopens it, it completes again once the new target is reached
+    pub async fn update(
+        db: web::Data<Database>,
+        achievements_sender: mpsc::UnboundedSender<Vec<String>>,
+        user_id: Uuid,
+        id: Uuid,
+        data: SavingsGoalData,
+    ) -> Result<SavingsGoalDetails, AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_own(conn, user_id, id)?;
+        let data = data.normalized(conn, user_id)?;
+
+        let goal = diesel::update(savings_goals::table.find(id))
+            .set((
+                &data,
+                savings_goals::completed_date.eq(None::<DateTime<Utc>>),
+            ))
+            .get_result::<SavingsGoal>(conn)?;
+
+        Self::check(conn, Some(&achievements_sender), goal)
+    }
+
+    pub async fn delete(db: web::Data<Database>, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        diesel::delete(
+            savings_goals::table
+                .filter(savings_goals::id.eq(id))
+                .filter(savings_goals::user_id.eq(user_id)),
+        )
+        .execute(&mut db.conn()?)?;
+
+        Ok(())
+    }
+
+    pub async fn get_contributions(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<Vec<SavingsGoalContribution>, AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_own(conn, user_id, id)?;
+
+        Ok(savings_goal_contributions::table
+            .filter(savings_goal_contributions::goal_id.eq(id))
+            .order(savings_goal_contributions::created_date.desc())
+            .load::<SavingsGoalContribution>(conn)?)
+    }
+
+    // Only goals without a linked account or tag take manual contributions, a negative
+    // amount is a withdrawal
+    pub async fn contribute(
+        db: web::Data<Database>,
+        achievements_sender: mpsc::UnboundedSender<Vec<String>>,
+        user_id: Uuid,
+        id: Uuid,
+        data: ContributionData,
+    ) -> Result<SavingsGoalDetails, AppError> {
+        let conn = &mut db.conn()?;
+        let goal = Self::get_own(conn, user_id, id)?;
+
+        if goal.source() != "manual" {
+            return Err(AppError::Conflict("savings:errors.notManual"));
+        }
+        if data.amount == 0.0 {
+            return Err(AppError::field("amount", "savings:amount.errors.zero"));
+        }
+
+        diesel::insert_into(savings_goal_contributions::table)
+            .values(NewSavingsGoalContribution {
+                goal_id: id,
+                amount: data.amount,
+                note: data.note,
+            })
+            .execute(conn)?;
+
+        Self::check(conn, Some(&achievements_sender), goal)
+    }
+
+    pub async fn delete_contribution(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+        contribution_id: Uuid,
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+        Self::get_own(conn, user_id, id)?;
+
+        diesel::delete(
+            savings_goal_contributions::table
+                .filter(savings_goal_contributions::id.eq(contribution_id))
+                .filter(savings_goal_contributions::goal_id.eq(id)),
+        )
+        .execute(conn)?;
+
+        Ok(())
+    }
+
+    // Account balances and tagged transactions change outside of this module, so the
+    // scheduler picks up the goals they complete. The achievements socket isn't scoped
+    // to a user, so unlocks found here are only persisted.
+    pub async fn check_all(db: web::Data<Database>) -> Result<usize, AppError> {
+        let conn = &mut db.conn()?;
+
+        let goals = savings_goals::table
+            .filter(savings_goals::completed_date.is_null())
+            .load::<SavingsGoal>(conn)?;
+
+        let mut completed = 0;
+        for goal in goals {
+            if Self::check(conn, None, goal)?.goal.completed_date.is_some() {
+                completed += 1;
+            }
+        }
+
+        Ok(completed)
+    }
+
+    // Completes the goal once its progress reaches the target and unlocks the savings
+    // achievements it earns. Newly unlocked achievements go to the achievements socket
+    // when a sender is given.
+    fn check(
+        conn: &mut PgConnection,
+        achievements_sender: Option<&mpsc::UnboundedSender<Vec<String>>>,
+        goal: SavingsGoal,
+    ) -> Result<SavingsGoalDetails, AppError> {
+        let details = goal.details(conn)?;
+        if details.goal.completed_date.is_some() || details.saved < details.goal.target_amount {
+            return Ok(details);
+        }
+
+        let now = Utc::now();
+        let user_id = details.goal.user_id;
+        let on_time = details
+            .goal
+            .deadline
+            .is_some_and(|deadline| now.date_naive() <= deadline);
+
+        let unlocked = conn.transaction::<_, AppError, _>(|conn| {
+            diesel::update(savings_goals::table.find(details.goal.id))
+                .set(savings_goals::completed_date.eq(now))
+                .execute(conn)?;
+
+            let completed_goals = savings_goals::table
+                .filter(savings_goals::user_id.eq(user_id))
+                .filter(savings_goals::completed_date.is_not_null())
+                .count()
+                .get_result::<i64>(conn)?;
+
+            let mut unlocked = vec![];
+            for achievement in SavingsAchievementEnum::get_all() {
+                let earned = match achievement {
+                    SavingsAchievementEnum::NestEgg => true,
+                    SavingsAchievementEnum::AheadOfSchedule => on_time,
+                    SavingsAchievementEnum::GoalGetter => completed_goals >= 3,
+                };
+                let key = achievement.to_string();
+
+                if earned && Achievement::complete(conn, user_id, "savings", &key)? {
+                    unlocked.push(key);
+                }
+            }
+
+            Ok(unlocked)
+        })?;
+
+        if let Some(sender) = achievements_sender.filter(|_| !unlocked.is_empty()) {
+            let _ = sender.send(unlocked);
+        }
+
+        Ok(SavingsGoalDetails {
+            goal: SavingsGoal {
+                completed_date: Some(now),
+                ..details.goal
+            },
+            ..details
+        })
+    }
+
+    fn details(self, conn: &mut PgConnection) -> Result<SavingsGoalDetails, AppError> {
+        let source = self.source();
+        let saved = match (self.account_id, self.tag_id) {
+            (Some(account_id), _) => accounts::table
+                .find(account_id)
+                .select(accounts::amount)
+                .first::<f64>(conn)?,
+            // Every tagged transaction counts as money put aside, whatever its type: a
+            // transfer to savings is usually recorded as an expense
+            (None, Some(tag_id)) => transaction_tags::table
+                .inner_join(transactions::table.inner_join(accounts::table))
+                .filter(transaction_tags::tag_id.eq(tag_id))
+                .filter(accounts::currency.eq(&self.currency))
+                .select(diesel::dsl::sum(transactions::amount))
+                .first::<Option<f64>>(conn)?
+                .unwrap_or(0.0),
+            (None, None) => savings_goal_contributions::table
+                .filter(savings_goal_contributions::goal_id.eq(self.id))
+                .select(diesel::dsl::sum(savings_goal_contributions::amount))
+                .first::<Option<f64>>(conn)?
+                .unwrap_or(0.0),
+        };
+
+        let remaining = (self.target_amount - saved).max(0.0);
+        let months_left = self.deadline.map(months_until);
+        let required_monthly = match months_left {
+            _ if remaining == 0.0 => None,
+            Some(0) => Some(round2(remaining)),
+            Some(months) => Some(round2(remaining / months as f64)),
+            None => None,
+        };
+
+        Ok(SavingsGoalDetails {
+            source: source.to_string(),
+            saved: round2(saved),
+            remaining: round2(remaining),
+            progress: round2((saved / self.target_amount * 100.0).clamp(0.0, 100.0)),
+            months_left,
+            required_monthly,
+            goal: self,
+        })
+    }
+
+    fn source(&self) -> &'static str {
+        match (self.account_id, self.tag_id) {
+            (Some(_), _) => "account",
+            (None, Some(_)) => "tag",
+            (None, None) => "manual",
+        }
+    }
+
+    fn get_own(conn: &mut PgConnection, user_id: Uuid, id: Uuid) -> Result<SavingsGoal, AppError> {
+        savings_goals::table
+            .filter(savings_goals::id.eq(id))
+            .filter(savings_goals::user_id.eq(user_id))
+            .first::<SavingsGoal>(conn)
+            .or_not_found("savings:errors.notFound")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavingsGoalDetails {
+    #[serde(flatten)]
+    pub goal: SavingsGoal,
+    pub source: String, // "account", "tag", "manual"
+    pub saved: f64,
+    pub remaining: f64,
+    pub progress: f64, // percent of the target
+    pub months_left: Option<i32>,
+    pub required_monthly: Option<f64>, // contribution per month to reach the target by the deadline
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
+#[diesel(table_name = savings_goals)]
+pub struct NewSavingsGoal {
+    pub user_id: Uuid,
+    pub name: String,
+    pub target_amount: f64,
+    pub currency: String,
+    pub deadline: Option<NaiveDate>,
+    pub account_id: Option<Uuid>,
+    pub tag_id: Option<Uuid>,
+}
+
+impl NewSavingsGoal {
+    pub fn create(data: SavingsGoalData, user_id: Uuid) -> Self {
+        Self {
+            user_id,
+            name: data.name,
+            target_amount: data.target_amount,
+            currency: data.currency,
+            deadline: data.deadline,
+            account_id: data.account_id,
+            tag_id: data.tag_id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, AsChangeset)]
+#[diesel(table_name = savings_goals, treat_none_as_null = true)]
+pub struct SavingsGoalData {
+    pub name: String,
+    pub target_amount: f64,
+    pub currency: String,
+    pub deadline: Option<NaiveDate>,
+    pub account_id: Option<Uuid>,
+    pub tag_id: Option<Uuid>,
+}
+
+impl SavingsGoalData {
+    fn normalized(self, conn: &mut PgConnection, user_id: Uuid) -> Result<Self, AppError> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() {
+            return Err(AppError::field("name", "savings:name.errors.required"));
+        }
+        if self.target_amount <= 0.0 {
+            return Err(AppError::field(
+                "target_amount",
+                "savings:targetAmount.errors.notPositive",
+            ));
+        }
+        if self.account_id.is_some() && self.tag_id.is_some() {
+            return Err(AppError::field("tag_id", "savings:tag.errors.withAccount"));
+        }
+
+        if let Some(account_id) = self.account_id {
+            let account = accounts::table
+                .filter(accounts::id.eq(account_id))
+                .filter(accounts::user_id.eq(user_id))
+                .first::<Account>(conn)
+                .optional()?
+                .ok_or(AppError::field(
+                    "account_id",
+                    "savings:account.errors.notFound",
+                ))?;
+
+            if account.currency != self.currency {
+                return Err(AppError::field(
+                    "currency",
+                    "savings:currency.errors.accountMismatch",
+                ));
+            }
+        }
+
+        if let Some(tag_id) = self.tag_id {
+            tags::table
+                .filter(tags::id.eq(tag_id))
+                .filter(tags::user_id.eq(user_id))
+                .select(tags::id)
+                .first::<Uuid>(conn)
+                .optional()?
+                .ok_or(AppError::field("tag_id", "savings:tag.errors.notFound"))?;
+        }
+
+        Ok(Self { name, ..self })
+    }
+}
+
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Queryable, Selectable, Identifiable, Associations,
+)]
+#[diesel(belongs_to(SavingsGoal, foreign_key = goal_id))]
+#[diesel(table_name = savings_goal_contributions)]
+pub struct SavingsGoalContribution {
+    pub id: Uuid,
+    pub goal_id: Uuid,
+    pub amount: f64,
+    pub note: Option<String>,
+    pub created_date: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
+#[diesel(table_name = savings_goal_contributions)]
+pub struct NewSavingsGoalContribution {
+    pub goal_id: Uuid,
+    pub amount: f64,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContributionData {
+    pub amount: f64,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Copy)]
+pub enum SavingsAchievementEnum {
+    NestEgg,         // first goal reached
+    AheadOfSchedule, // a goal reached by its deadline
+    GoalGetter,      // three goals reached
+}
+
+impl fmt::Display for SavingsAchievementEnum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl SavingsAchievementEnum {
+    pub fn get_all() -> Vec<Self> {
+        vec![Self::NestEgg, Self::AheadOfSchedule, Self::GoalGetter]
+    }
+}
+
+// Calendar months left until the deadline, a started month counts as a whole one.
+// Zero once the deadline has passed.
+fn months_until(deadline: NaiveDate) -> i32 {
+    let today = Utc::now().date_naive();
+    if deadline < today {
+        return 0;
+    }
+
+    let months =
+        (deadline.year() - today.year()) * 12 + deadline.month() as i32 - today.month() as i32;
+    if deadline.day() > today.day() {
+        months + 1
+    } else {
+        months.max(1)
+    }
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
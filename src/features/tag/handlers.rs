@@ -0,0 +1,56 @@
+use crate::common::middlewares::auth::AuthenticationService;
+use crate::common::models::errors::AppError;
+use crate::features::tag::models::{Tag, TagData};
+use crate::repository::database::Database;
+use actix_web::{delete, get, post, put, web, HttpResponse, Scope};
+use uuid::Uuid;
+
+pub fn routes() -> Scope {
+    web::scope("/tag")
+        .service(get_tags)
+        .service(create_tag)
+        .service(update_tag)
+        .service(delete_tag)
+}
+
+#[get("")]
+async fn get_tags(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(Tag::get_all(db.clone(), user.0.id).await?))
+}
+
+#[post("")]
+async fn create_tag(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    form: web::Json<TagData>,
+) -> Result<HttpResponse, AppError> {
+    let tag = Tag::create(db.clone(), user.0.id, form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(tag))
+}
+
+#[put("/{id}")]
+async fn update_tag(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+    form: web::Json<TagData>,
+) -> Result<HttpResponse, AppError> {
+    let tag = Tag::update(db.clone(), user.0.id, path.into_inner(), form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(tag))
+}
+
+#[delete("/{id}")]
+async fn delete_tag(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    Tag::delete(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("Tag deleted"))
+}
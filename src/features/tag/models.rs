@@ -0,0 +1,225 @@
+use crate::common::models::errors::{AppError, OrNotFound};
+use crate::features::user::models::User;
+use crate::repository::database::Database;
+use crate::schema::{tags, transaction_tags, transactions};
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    PartialEq,
+)]
+#[diesel(belongs_to(User, foreign_key = user_id))]
+#[diesel(table_name = tags)]
+pub struct Tag {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub color: String,
+    pub created_date: DateTime<Utc>,
+}
+
+impl Tag {
+    pub async fn get_all(db: web::Data<Database>, user_id: Uuid) -> Result<Vec<Tag>, AppError> {
+        Ok(tags::table
+            .filter(tags::user_id.eq(user_id))
+            .order(tags::name.asc())
+            .load::<Tag>(&mut db.conn()?)?)
+    }
+
+    pub async fn create(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        data: TagData,
+    ) -> Result<Tag, AppError> {
+        let data = data.normalized()?;
+
+        diesel::insert_into(tags::table)
+            .values(NewTag {
+                user_id,
+                name: data.name,
+                color: data.color,
+            })
+            .get_result::<Tag>(&mut db.conn()?)
+            .map_err(Self::write_error)
+    }
+
+    pub async fn update(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+        data: TagData,
+    ) -> Result<Tag, AppError> {
+        diesel::update(tags::table)
+            .filter(tags::id.eq(id))
+            .filter(tags::user_id.eq(user_id))
+            .set(data.normalized()?)
+            .get_result::<Tag>(&mut db.conn()?)
+            .map_err(Self::write_error)
+    }
+
+    // Removing a tag only detaches it, the tagged transactions are kept
+    pub async fn delete(db: web::Data<Database>, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        diesel::delete(
+            tags::table
+                .filter(tags::id.eq(id))
+                .filter(tags::user_id.eq(user_id)),
+        )
+        .execute(&mut db.conn()?)?;
+
+        Ok(())
+    }
+
+    // Replaces the tags of a transaction with the given set
+    pub async fn set_for_transaction(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        transaction_id: Uuid,
+        tag_ids: Vec<Uuid>,
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+
+        transactions::table
+            .filter(transactions::id.eq(transaction_id))
+            .filter(transactions::user_id.eq(user_id))
+            .select(transactions::id)
+            .first::<Uuid>(conn)
+            .or_not_found("transaction:errors.notFound")?;
+        Self::check_tags(conn, user_id, &tag_ids)?;
+
+        conn.transaction(|conn| {
+            diesel::delete(
+                transaction_tags::table.filter(transaction_tags::transaction_id.eq(transaction_id)),
+            )
+            .execute(conn)?;
+
+            Self::attach(conn, transaction_id, &tag_ids)?;
+
+            Ok(())
+        })
+    }
+
+    pub fn attach(
+        conn: &mut PgConnection,
+        transaction_id: Uuid,
+        tag_ids: &[Uuid],
+    ) -> QueryResult<()> {
+        let rows = tag_ids
+            .iter()
+            .map(|tag_id| TransactionTag {
+                transaction_id,
+                tag_id: *tag_id,
+            })
+            .collect::<Vec<TransactionTag>>();
+
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        diesel::insert_into(transaction_tags::table)
+            .values(&rows)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .map(|_| ())
+    }
+
+    pub fn check_tags(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        tag_ids: &[Uuid],
+    ) -> Result<(), AppError> {
+        if tag_ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut ids = tag_ids.to_vec();
+        ids.sort();
+        ids.dedup();
+
+        let found = tags::table
+            .filter(tags::id.eq_any(&ids))
+            .filter(tags::user_id.eq(user_id))
+            .count()
+            .get_result::<i64>(conn)?;
+
+        if found as usize != ids.len() {
+            return Err(AppError::field("tag_ids", "tag:errors.notFound"));
+        }
+
+        Ok(())
+    }
+
+    pub fn get_by_transactions(
+        conn: &mut PgConnection,
+        transaction_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, Vec<Tag>>, AppError> {
+        let rows = transaction_tags::table
+            .inner_join(tags::table)
+            .filter(transaction_tags::transaction_id.eq_any(transaction_ids))
+            .order(tags::name.asc())
+            .select((transaction_tags::transaction_id, Tag::as_select()))
+            .load::<(Uuid, Tag)>(conn)?;
+
+        let mut grouped = HashMap::<Uuid, Vec<Tag>>::new();
+        for (transaction_id, tag) in rows {
+            grouped.entry(transaction_id).or_default().push(tag);
+        }
+
+        Ok(grouped)
+    }
+
+    fn write_error(err: diesel::result::Error) -> AppError {
+        match AppError::from(err) {
+            AppError::Conflict(_) => AppError::field("name", "tag:name.errors.alreadyExists"),
+            AppError::NotFound(_) => AppError::NotFound("tag:errors.notFound"),
+            err => err,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
+#[diesel(table_name = tags)]
+pub struct NewTag {
+    pub user_id: Uuid,
+    pub name: String,
+    pub color: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, AsChangeset)]
+#[diesel(table_name = tags)]
+pub struct TagData {
+    pub name: String,
+    pub color: String,
+}
+
+impl TagData {
+    fn normalized(self) -> Result<Self, AppError> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() {
+            return Err(AppError::field("name", "tag:name.errors.required"));
+        }
+
+        Ok(Self {
+            name,
+            color: self.color,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = transaction_tags)]
+pub struct TransactionTag {
+    pub transaction_id: Uuid,
+    pub tag_id: Uuid,
+}
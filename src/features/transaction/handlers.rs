@@ -1,42 +1,63 @@
 use crate::common::middlewares::auth::AuthenticationService;
-use crate::features::account::models::Account;
-use crate::features::transaction::models::{NewTransaction, Transaction, TransactionData};
+use crate::common::middlewares::validated_json::ValidatedJson;
+use crate::common::models::errors::AppError;
+use crate::features::tag::models::Tag;
+use crate::features::transaction::models::{
+    NewTransaction, Transaction, TransactionData, TransactionFilter,
+};
+use crate::features::transaction_export;
+use crate::features::transaction_import;
 use crate::repository::database::Database;
-use actix_web::{get, post, web, HttpResponse, Scope};
+use actix_web::{get, post, put, web, HttpResponse, Scope};
+use uuid::Uuid;
 
 pub fn routes() -> Scope {
     web::scope("/transaction")
         .service(create_transaction)
         .service(get_transactions)
+        .service(set_transaction_tags)
+        .service(transaction_export::handlers::routes())
+        .service(transaction_import::handlers::routes())
 }
 
 #[get("")]
-async fn get_transactions(user: AuthenticationService, db: web::Data<Database>) -> HttpResponse {
-    let transactions = Transaction::get_all(db.clone(), user.0.id).await.unwrap();
+async fn get_transactions(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<TransactionFilter>,
+) -> Result<HttpResponse, AppError> {
+    let page = Transaction::get_page(db.clone(), user.0.id, query.into_inner()).await?;
 
-    HttpResponse::Ok().json(transactions)
+    Ok(HttpResponse::Ok().json(page))
 }
 
 #[post("")]
 async fn create_transaction(
     user: AuthenticationService,
     db: web::Data<Database>,
-    form: web::Json<TransactionData>,
-) -> HttpResponse {
-    match Transaction::create(db.clone(), NewTransaction::create(&form.clone(), user.0.id)).await {
-        Ok(_) => {
-            match Account::update_amount(
-                db.clone(),
-                form.clone().account_id,
-                form.clone().transaction_type,
-                form.clone().amount,
-            )
-            .await
-            {
-                Ok(_) => HttpResponse::Ok().body("Transaction created"),
-                Err(_) => HttpResponse::InternalServerError().body("Server error"),
-            }
-        }
-        Err(_) => HttpResponse::InternalServerError().body("Server error"),
-    }
+    form: ValidatedJson<TransactionData>,
+) -> Result<HttpResponse, AppError> {
+    let transaction_data = NewTransaction::create(&form, user.0.id)?;
+
+    Transaction::create(
+        db.clone(),
+        transaction_data,
+        form.splits.clone(),
+        form.tag_ids.clone(),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().body("Transaction created"))
+}
+
+#[put("/{id}/tags")]
+async fn set_transaction_tags(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+    form: web::Json<Vec<Uuid>>,
+) -> Result<HttpResponse, AppError> {
+    Tag::set_for_transaction(db.clone(), user.0.id, path.into_inner(), form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("Transaction tags updated"))
 }
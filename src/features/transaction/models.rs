@@ -1,14 +1,32 @@
+use crate::common::models::errors::{AppError, FormError};
+use crate::common::models::search::contains_pattern;
 use crate::features::account::models::Account;
+use crate::features::balance_history::models::BalanceSnapshot;
 use crate::features::category::models::Category;
+use crate::features::tag::models::Tag;
 use crate::features::user::models::User;
 use crate::repository::database::Database;
-use crate::schema::{accounts, categories, transactions};
+use crate::schema::{accounts, categories, transaction_splits, transaction_tags, transactions};
 use actix_web::web;
 use chrono::DateTime;
 use chrono::Utc;
+use chrono::{Duration, NaiveDate, NaiveTime};
+use diesel::dsl::{InnerJoin, IntoBoxed};
+use diesel::pg::Pg;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+// The ledger broken down into category lines for raw SQL reports: a split transaction
+// contributes one row per split, any other transaction a single row of its own
+pub const CATEGORY_LINES_SQL: &str =
+    "(SELECT t.id, t.user_id, t.account_id, t.transaction_type, t.created_date,
+        COALESCE(s.category_id, t.category_id) AS category_id,
+        COALESCE(s.amount, t.amount) AS amount
+    FROM transactions t
+    LEFT JOIN transaction_splits s ON s.transaction_id = t.id)";
 
 #[derive(
     Debug,
@@ -36,102 +54,492 @@ pub struct Transaction {
     pub created_date: DateTime<Utc>,
     pub archived: bool,
     pub deleted: bool,
+    pub recurring_id: Option<Uuid>,
+    pub recurring_date: Option<NaiveDate>,
+    pub external_id: Option<String>,
 }
 
 impl Transaction {
     pub async fn create(
         db: web::Data<Database>,
         transaction_data: NewTransaction,
-    ) -> Result<Uuid, String> {
-        diesel::insert_into(transactions::table)
-            .values(&transaction_data)
-            .get_result::<Transaction>(&mut db.pool.get().unwrap())
-            .map(|t| t.id)
-            .map_err(|err| err.to_string())
+        splits: Vec<TransactionSplitData>,
+        tag_ids: Vec<Uuid>,
+    ) -> Result<Uuid, AppError> {
+        let conn = &mut db.conn()?;
+        Self::post_with_details(conn, &transaction_data, &splits, &tag_ids)
+    }
+
+    // Inserts the transaction and applies it to the account balance atomically
+    pub fn post(
+        conn: &mut PgConnection,
+        transaction_data: &NewTransaction,
+    ) -> Result<Uuid, AppError> {
+        Self::post_with_details(conn, transaction_data, &[], &[])
+    }
+
+    pub fn post_with_details(
+        conn: &mut PgConnection,
+        transaction_data: &NewTransaction,
+        splits: &[TransactionSplitData],
+        tag_ids: &[Uuid],
+    ) -> Result<Uuid, AppError> {
+        let delta =
+            Account::balance_delta(&transaction_data.transaction_type, transaction_data.amount)?;
+        Self::check_references(conn, transaction_data)?;
+        TransactionSplit::check_categories(
+            conn,
+            transaction_data.user_id,
+            &transaction_data.transaction_type,
+            splits,
+        )?;
+        Tag::check_tags(conn, transaction_data.user_id, tag_ids)?;
+
+        conn.transaction(|conn| {
+            let id = diesel::insert_into(transactions::table)
+                .values(transaction_data)
+                .get_result::<Transaction>(conn)
+                .map(|t| t.id)?;
+
+            if !splits.is_empty() {
+                let lines = splits
+                    .iter()
+                    .enumerate()
+                    .map(|(i, split)| NewTransactionSplit::create(split, id, i as i32))
+                    .collect::<Vec<NewTransactionSplit>>();
+                diesel::insert_into(transaction_splits::table)
+                    .values(&lines)
+                    .execute(conn)?;
+            }
+
+            Tag::attach(conn, id, tag_ids)?;
+            Account::adjust_amount(conn, transaction_data.account_id, delta)?;
+            BalanceSnapshot::invalidate(
+                conn,
+                transaction_data.account_id,
+                transaction_data.created_date.date_naive(),
+            )?;
+
+            Ok(id)
+        })
+        .map_err(|err: diesel::result::Error| match AppError::from(err) {
+            AppError::Conflict(_) => AppError::Conflict("transaction:errors.alreadyExists"),
+            err => err,
+        })
     }
 
-    pub async fn get_all(
+    // Every way of posting goes through here: the account has to be the user's and still
+    // open, the category the user's and of the same type as the transaction
+    fn check_references(
+        conn: &mut PgConnection,
+        transaction_data: &NewTransaction,
+    ) -> Result<(), AppError> {
+        let archived = accounts::table
+            .filter(accounts::id.eq(transaction_data.account_id))
+            .filter(accounts::user_id.eq(transaction_data.user_id))
+            .select(accounts::archived)
+            .first::<bool>(conn)
+            .optional()?
+            .ok_or(AppError::field("account_id", "account:errors.notFound"))?;
+        if archived {
+            return Err(AppError::field("account_id", "account:errors.archived"));
+        }
+
+        let category_found = categories::table
+            .filter(categories::id.eq(transaction_data.category_id))
+            .filter(categories::user_id.eq(transaction_data.user_id))
+            .filter(categories::category_type.eq(&transaction_data.transaction_type))
+            .count()
+            .get_result::<i64>(conn)?
+            > 0;
+        if !category_found {
+            return Err(AppError::field(
+                "category_id",
+                "transaction:category.errors.notFound",
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_page(
         db: web::Data<Database>,
         user_id: Uuid,
-    ) -> Result<Vec<TransactionDetails>, String> {
-        transactions::table
-            .inner_join(accounts::table.on(accounts::id.eq(transactions::account_id)))
-            .inner_join(categories::table.on(categories::id.eq(transactions::category_id)))
-            .filter(transactions::user_id.eq(user_id))
+        filter: TransactionFilter,
+    ) -> Result<TransactionsPage, AppError> {
+        let conn = &mut db.conn()?;
+
+        let limit = filter.limit.unwrap_or(50).clamp(1, 200);
+        let (items, next_cursor) = Self::load_page(conn, user_id, &filter, limit)?;
+
+        let total_count = Self::filtered(user_id, &filter)
+            .count()
+            .get_result::<i64>(conn)?;
+
+        let total_income = Self::filtered(user_id, &filter)
+            .filter(transactions::transaction_type.eq("income"))
+            .select(diesel::dsl::sum(transactions::amount))
+            .get_result::<Option<f64>>(conn)?
+            .unwrap_or(0.0);
+
+        let total_expense = Self::filtered(user_id, &filter)
+            .filter(transactions::transaction_type.eq("expense"))
+            .select(diesel::dsl::sum(transactions::amount))
+            .get_result::<Option<f64>>(conn)?
+            .unwrap_or(0.0);
+
+        Ok(TransactionsPage {
+            items,
+            next_cursor,
+            total_count,
+            total_income,
+            total_expense,
+        })
+    }
+
+    pub fn load_page(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        filter: &TransactionFilter,
+        limit: i64,
+    ) -> Result<(Vec<TransactionDetails>, Option<String>), AppError> {
+        let sort = filter.sort.clone().unwrap_or_default();
+        let order = filter.order.clone().unwrap_or_default();
+
+        let mut query = Self::filtered(user_id, filter);
+
+        if let Some(cursor) = &filter.cursor {
+            let (key, id) = TransactionCursor::parse(cursor, &sort)?;
+            query = match (key, &order) {
+                (TransactionCursor::Date(date), SortOrder::Desc) => query.filter(
+                    transactions::created_date
+                        .lt(date)
+                        .or(transactions::created_date
+                            .eq(date)
+                            .and(transactions::id.lt(id))),
+                ),
+                (TransactionCursor::Date(date), SortOrder::Asc) => query.filter(
+                    transactions::created_date
+                        .gt(date)
+                        .or(transactions::created_date
+                            .eq(date)
+                            .and(transactions::id.gt(id))),
+                ),
+                (TransactionCursor::Amount(amount), SortOrder::Desc) => query.filter(
+                    transactions::amount
+                        .lt(amount)
+                        .or(transactions::amount.eq(amount).and(transactions::id.lt(id))),
+                ),
+                (TransactionCursor::Amount(amount), SortOrder::Asc) => query.filter(
+                    transactions::amount
+                        .gt(amount)
+                        .or(transactions::amount.eq(amount).and(transactions::id.gt(id))),
+                ),
+            };
+        }
+
+        query = match (&sort, &order) {
+            (TransactionSort::Date, SortOrder::Desc) => query
+                .order_by(transactions::created_date.desc())
+                .then_order_by(transactions::id.desc()),
+            (TransactionSort::Date, SortOrder::Asc) => query
+                .order_by(transactions::created_date.asc())
+                .then_order_by(transactions::id.asc()),
+            (TransactionSort::Amount, SortOrder::Desc) => query
+                .order_by(transactions::amount.desc())
+                .then_order_by(transactions::id.desc()),
+            (TransactionSort::Amount, SortOrder::Asc) => query
+                .order_by(transactions::amount.asc())
+                .then_order_by(transactions::id.asc()),
+        };
+
+        let mut items = query
             .select((
                 Transaction::as_select(),
                 Account::as_select(),
                 Category::as_select(),
             ))
-            .order_by(transactions::created_date.desc())
-            .load::<(Transaction, Account, Category)>(&mut db.pool.get().unwrap())
-            .map(|transactions| {
-                transactions
-                    .into_iter()
-                    .map(|t| TransactionDetails::from(t))
-                    .collect::<Vec<TransactionDetails>>()
-            })
-            .map_err(|_| "Error loading transactions".to_string())
-    }
-
-    pub async fn delete_by_category(
-        db: web::Data<Database>,
-        category_id: Uuid,
-    ) -> Result<(), String> {
-        let transaction = transactions::table.filter(transactions::category_id.eq(category_id));
-        diesel::delete(transaction)
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Error deleting transaction".to_string())
+            .limit(limit + 1)
+            .load::<(Transaction, Account, Category)>(conn)?
+            .into_iter()
+            .map(TransactionDetails::from)
+            .collect::<Vec<TransactionDetails>>();
+
+        let next_cursor = if items.len() as i64 > limit {
+            items.truncate(limit as usize);
+            items.last().map(|t| TransactionCursor::encode(t, &sort))
+        } else {
+            None
+        };
+
+        let ids = items.iter().map(|t| t.id).collect::<Vec<Uuid>>();
+        let mut splits = TransactionSplit::get_by_transactions(conn, &ids)?;
+        let mut tags = Tag::get_by_transactions(conn, &ids)?;
+        for item in items.iter_mut() {
+            item.splits = splits.remove(&item.id).unwrap_or_default();
+            item.tags = tags.remove(&item.id).unwrap_or_default();
+        }
+
+        Ok((items, next_cursor))
     }
 
-    pub async fn delete_by_account(
-        db: web::Data<Database>,
-        account_id: Uuid,
-    ) -> Result<(), String> {
-        let transaction = transactions::table.filter(transactions::account_id.eq(account_id));
-        diesel::delete(transaction)
-            .execute(&mut db.pool.get().unwrap())
-            .map(|_| ())
-            .map_err(|_| "Error deleting transaction".to_string())
+    fn filtered(user_id: Uuid, filter: &TransactionFilter) -> TransactionsQuery<'static> {
+        let mut query = transactions::table
+            .inner_join(accounts::table)
+            .inner_join(categories::table)
+            .filter(transactions::user_id.eq(user_id))
+            .into_boxed();
+
+        if let Some(account_id) = filter.account_id {
+            query = query.filter(transactions::account_id.eq(account_id));
+        }
+        if let Some(category_id) = filter.category_id {
+            query = query.filter(
+                transactions::category_id
+                    .eq(category_id)
+                    .or(transactions::id.eq_any(
+                        transaction_splits::table
+                            .filter(transaction_splits::category_id.eq(category_id))
+                            .select(transaction_splits::transaction_id),
+                    )),
+            );
+        }
+        if let Some(tag_id) = filter.tag_id {
+            query = query.filter(
+                transactions::id.eq_any(
+                    transaction_tags::table
+                        .filter(transaction_tags::tag_id.eq(tag_id))
+                        .select(transaction_tags::transaction_id),
+                ),
+            );
+        }
+        if let Some(transaction_type) = &filter.transaction_type {
+            query = query.filter(transactions::transaction_type.eq(transaction_type.clone()));
+        }
+        if let Some(from) = filter.from {
+            query = query
+                .filter(transactions::created_date.ge(from.and_time(NaiveTime::MIN).and_utc()));
+        }
+        if let Some(to) = filter.to {
+            let end = (to + Duration::days(1)).and_time(NaiveTime::MIN).and_utc();
+            query = query.filter(transactions::created_date.lt(end));
+        }
+        if let Some(min_amount) = filter.min_amount {
+            query = query.filter(transactions::amount.ge(min_amount));
+        }
+        if let Some(max_amount) = filter.max_amount {
+            query = query.filter(transactions::amount.le(max_amount));
+        }
+        if let Some(search) = &filter.search {
+            query = query.filter(transactions::note.ilike(contains_pattern(search)));
+        }
+
+        query
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
 #[diesel(table_name = transactions)]
 pub struct NewTransaction {
-    user_id: Uuid,
+    pub user_id: Uuid,
     pub account_id: Uuid,
     pub category_id: Uuid,
     pub transaction_type: String,
     pub note: Option<String>,
     pub amount: f64,
     pub created_date: DateTime<Utc>,
+    pub recurring_id: Option<Uuid>,
+    pub recurring_date: Option<NaiveDate>,
+    pub external_id: Option<String>,
 }
 
 impl NewTransaction {
-    pub fn create(data: &TransactionData, user_id: Uuid) -> Self {
-        Self {
-            user_id: user_id.clone(),
-            account_id: data.account_id.clone(),
-            category_id: data.category_id.clone(),
+    // A split transaction takes the category of its first line
+    pub fn create(data: &TransactionData, user_id: Uuid) -> Result<Self, AppError> {
+        let category_id = if data.splits.is_empty() {
+            data.category_id.ok_or(AppError::field(
+                "category_id",
+                "transaction:category.errors.required",
+            ))?
+        } else {
+            TransactionSplit::validate(&data.splits, data.amount)?;
+            data.splits[0].category_id
+        };
+
+        Ok(Self {
+            user_id,
+            account_id: data.account_id,
+            category_id,
             transaction_type: data.transaction_type.clone(),
             note: data.note.clone(),
             amount: data.amount,
             created_date: data.created_date,
-        }
+            recurring_id: None,
+            recurring_date: None,
+            external_id: None,
+        })
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
-#[diesel(table_name = transactions)]
+#[derive(Debug, Serialize, Deserialize, Clone, Validate)]
 pub struct TransactionData {
     pub account_id: Uuid,
-    category_id: Uuid,
+    category_id: Option<Uuid>,
+    #[validate(custom = "validate_transaction_type")]
     pub transaction_type: String,
+    #[validate(length(max = 1000, message = "transaction:note.errors.tooLong"))]
     note: Option<String>,
+    #[validate(custom = "validate_amount")]
     pub amount: f64,
     created_date: DateTime<Utc>,
+    #[serde(default)]
+    pub splits: Vec<TransactionSplitData>,
+    #[serde(default)]
+    pub tag_ids: Vec<Uuid>,
+}
+
+fn validate_transaction_type(transaction_type: &str) -> Result<(), ValidationError> {
+    if transaction_type == "income" || transaction_type == "expense" {
+        Ok(())
+    } else {
+        Err(ValidationError::new("transaction:type.errors.invalid"))
+    }
+}
+
+fn validate_amount(amount: f64) -> Result<(), ValidationError> {
+    if amount > 0.0 && amount.is_finite() {
+        Ok(())
+    } else {
+        Err(ValidationError::new(
+            "transaction:amount.errors.notPositive",
+        ))
+    }
+}
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    PartialEq,
+)]
+#[diesel(belongs_to(Transaction, foreign_key = transaction_id))]
+#[diesel(belongs_to(Category, foreign_key = category_id))]
+#[diesel(table_name = transaction_splits)]
+pub struct TransactionSplit {
+    pub id: Uuid,
+    pub transaction_id: Uuid,
+    pub category_id: Uuid,
+    pub amount: f64,
+    pub note: Option<String>,
+    pub s_order: i32,
+}
+
+impl TransactionSplit {
+    // Amounts are compared in cents so that float rounding doesn't reject valid splits
+    pub fn validate(splits: &[TransactionSplitData], total: f64) -> Result<(), AppError> {
+        let error = |message| {
+            AppError::Validation(vec![FormError {
+                field: "splits",
+                message,
+            }])
+        };
+
+        if splits.len() < 2 {
+            return Err(error("transaction:splits.errors.tooFew"));
+        }
+        if splits.iter().any(|s| s.amount <= 0.0) {
+            return Err(error("transaction:splits.errors.notPositive"));
+        }
+
+        let sum = splits.iter().map(|s| s.amount).sum::<f64>();
+        if (sum * 100.0).round() != (total * 100.0).round() {
+            return Err(error("transaction:splits.errors.sumMismatch"));
+        }
+
+        Ok(())
+    }
+
+    fn check_categories(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        transaction_type: &str,
+        splits: &[TransactionSplitData],
+    ) -> Result<(), AppError> {
+        if splits.is_empty() {
+            return Ok(());
+        }
+
+        let mut ids = splits.iter().map(|s| s.category_id).collect::<Vec<Uuid>>();
+        ids.sort();
+        ids.dedup();
+
+        let found = categories::table
+            .filter(categories::id.eq_any(&ids))
+            .filter(categories::user_id.eq(user_id))
+            .filter(categories::category_type.eq(transaction_type))
+            .count()
+            .get_result::<i64>(conn)?;
+
+        if found as usize != ids.len() {
+            return Err(AppError::field(
+                "splits",
+                "transaction:splits.errors.categoryNotFound",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn get_by_transactions(
+        conn: &mut PgConnection,
+        transaction_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, Vec<TransactionSplit>>, AppError> {
+        let splits = transaction_splits::table
+            .filter(transaction_splits::transaction_id.eq_any(transaction_ids))
+            .order(transaction_splits::s_order.asc())
+            .load::<TransactionSplit>(conn)?;
+
+        let mut grouped = HashMap::<Uuid, Vec<TransactionSplit>>::new();
+        for split in splits {
+            grouped.entry(split.transaction_id).or_default().push(split);
+        }
+
+        Ok(grouped)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
+#[diesel(table_name = transaction_splits)]
+pub struct NewTransactionSplit {
+    pub transaction_id: Uuid,
+    pub category_id: Uuid,
+    pub amount: f64,
+    pub note: Option<String>,
+    pub s_order: i32,
+}
+
+impl NewTransactionSplit {
+    pub fn create(data: &TransactionSplitData, transaction_id: Uuid, s_order: i32) -> Self {
+        Self {
+            transaction_id,
+            category_id: data.category_id,
+            amount: data.amount,
+            note: data.note.clone(),
+            s_order,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransactionSplitData {
+    pub category_id: Uuid,
+    pub amount: f64,
+    pub note: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -146,8 +554,12 @@ pub struct TransactionDetails {
     pub created_date: DateTime<Utc>,
     pub archived: bool,
     pub deleted: bool,
+    pub recurring_id: Option<Uuid>,
+    pub external_id: Option<String>,
     pub account: Account,
     pub category: Category,
+    pub splits: Vec<TransactionSplit>,
+    pub tags: Vec<Tag>,
 }
 
 impl From<(Transaction, Account, Category)> for TransactionDetails {
@@ -163,11 +575,98 @@ impl From<(Transaction, Account, Category)> for TransactionDetails {
             created_date: t.0.created_date,
             archived: t.0.archived,
             deleted: t.0.deleted,
+            recurring_id: t.0.recurring_id,
+            external_id: t.0.external_id,
             account: t.1,
             category: t.2,
+            splits: vec![],
+            tags: vec![],
         }
     }
 }
+
+type TransactionsQuery<'a> = IntoBoxed<
+    'a,
+    InnerJoin<InnerJoin<transactions::table, accounts::table>, categories::table>,
+    Pg,
+>;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionSort {
+    #[default]
+    Date,
+    Amount,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TransactionFilter {
+    pub account_id: Option<Uuid>,
+    pub category_id: Option<Uuid>,
+    pub tag_id: Option<Uuid>,
+    #[serde(rename = "type")]
+    pub transaction_type: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub min_amount: Option<f64>,
+    pub max_amount: Option<f64>,
+    pub search: Option<String>,
+    pub sort: Option<TransactionSort>,
+    pub order: Option<SortOrder>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+// Cursor is "<sort key>_<transaction id>", the sort key being the timestamp in
+// microseconds for date sorting and the raw amount for amount sorting.
+enum TransactionCursor {
+    Date(DateTime<Utc>),
+    Amount(f64),
+}
+
+impl TransactionCursor {
+    fn encode(t: &TransactionDetails, sort: &TransactionSort) -> String {
+        match sort {
+            TransactionSort::Date => format!("{}_{}", t.created_date.timestamp_micros(), t.id),
+            TransactionSort::Amount => format!("{}_{}", t.amount, t.id),
+        }
+    }
+
+    fn parse(cursor: &str, sort: &TransactionSort) -> Result<(Self, Uuid), AppError> {
+        let invalid = || AppError::field("cursor", "transaction:cursor.errors.invalid");
+        let (key, id) = cursor.split_once('_').ok_or_else(invalid)?;
+        let id = Uuid::parse_str(id).map_err(|_| invalid())?;
+
+        let key = match sort {
+            TransactionSort::Date => key
+                .parse::<i64>()
+                .ok()
+                .and_then(DateTime::from_timestamp_micros)
+                .map(Self::Date),
+            TransactionSort::Amount => key.parse::<f64>().ok().map(Self::Amount),
+        };
+
+        key.map(|k| (k, id)).ok_or_else(invalid)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransactionsPage {
+    pub items: Vec<TransactionDetails>,
+    pub next_cursor: Option<String>,
+    pub total_count: i64,
+    pub total_income: f64,
+    pub total_expense: f64,
+}
+
 // #[derive(Debug, Serialize, Deserialize, Clone)]
 // #[serde(rename_all = "camelCase")]
 // pub struct Transfer {
@@ -0,0 +1,32 @@
+use crate::common::middlewares::auth::AuthenticationService;
+use crate::common::models::errors::AppError;
+use crate::features::transaction_export::models::{ExportQuery, TransactionExport};
+use crate::repository::database::Database;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{get, web, HttpResponse, Scope};
+
+pub fn routes() -> Scope {
+    web::scope("/export").service(export_transactions)
+}
+
+#[get("")]
+async fn export_transactions(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    let format = query.format;
+    let stream = TransactionExport::stream(db.clone(), user.0.id, query)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "transactions.{}",
+                format.extension()
+            ))],
+        })
+        .streaming(stream))
+}
@@ -0,0 +1,326 @@
+use crate::common::models::errors::{AppError, OrNotFound};
+use crate::features::transaction::models::{
+    SortOrder, Transaction, TransactionDetails, TransactionFilter, TransactionSort,
+};
+use crate::repository::database::Database;
+use crate::schema::accounts;
+use actix_web::web;
+use chrono::{NaiveDate, Utc};
+use diesel::prelude::*;
+use futures::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const BATCH_SIZE: i64 = 500;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+    Ofx,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Ofx => "application/x-ofx",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Ofx => "ofx",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub account_id: Option<Uuid>,
+}
+
+// OFX statements belong to a single account, so it is resolved up front
+struct StatementAccount {
+    id: Uuid,
+    currency: String,
+    account_type: String,
+}
+
+struct ExportState {
+    db: web::Data<Database>,
+    user_id: Uuid,
+    query: ExportQuery,
+    account: Option<StatementAccount>,
+    cursor: Option<String>,
+    started: bool,
+    written: usize,
+    done: bool,
+}
+
+pub struct TransactionExport;
+
+impl TransactionExport {
+    // Transactions are loaded in keyset-paginated batches, so only one batch is held
+    // in memory at a time regardless of how large the ledger is
+    pub fn stream(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        query: ExportQuery,
+    ) -> Result<impl Stream<Item = Result<web::Bytes, actix_web::Error>>, AppError> {
+        if let (Some(from), Some(to)) = (query.from, query.to) {
+            if from > to {
+                return Err(AppError::field("from", "export:from.errors.afterTo"));
+            }
+        }
+
+        let account = match query.account_id {
+            Some(account_id) => {
+                let (id, currency, account_type) = accounts::table
+                    .filter(accounts::id.eq(account_id))
+                    .filter(accounts::user_id.eq(user_id))
+                    .select((accounts::id, accounts::currency, accounts::account_type))
+                    .first::<(Uuid, String, String)>(&mut db.conn()?)
+                    .or_not_found("account:errors.notFound")?;
+                Some(StatementAccount {
+                    id,
+                    currency,
+                    account_type,
+                })
+            }
+            None if query.format == ExportFormat::Ofx => {
+                return Err(AppError::field(
+                    "account_id",
+                    "export:account.errors.requiredForOfx",
+                ))
+            }
+            None => None,
+        };
+
+        let state = ExportState {
+            db,
+            user_id,
+            query,
+            account,
+            cursor: None,
+            started: false,
+            written: 0,
+            done: false,
+        };
+
+        Ok(stream::unfold(state, |mut state| async move {
+            if state.done {
+                return None;
+            }
+
+            let chunk = state.next_chunk();
+            if chunk.is_err() {
+                state.done = true;
+            }
+
+            Some((
+                chunk.map(web::Bytes::from).map_err(actix_web::Error::from),
+                state,
+            ))
+        }))
+    }
+}
+
+impl ExportState {
+    fn next_chunk(&mut self) -> Result<String, AppError> {
+        if !self.started {
+            self.started = true;
+            return self.header();
+        }
+
+        let filter = TransactionFilter {
+            account_id: self.query.account_id,
+            from: self.query.from,
+            to: self.query.to,
+            sort: Some(TransactionSort::Date),
+            order: Some(SortOrder::Asc),
+            cursor: self.cursor.take(),
+            ..Default::default()
+        };
+
+        let conn = &mut self.db.conn()?;
+        let (items, next_cursor) = Transaction::load_page(conn, self.user_id, &filter, BATCH_SIZE)?;
+
+        let mut chunk = String::new();
+        for item in &items {
+            chunk.push_str(&self.row(item)?);
+            self.written += 1;
+        }
+
+        match next_cursor {
+            Some(cursor) => self.cursor = Some(cursor),
+            None => {
+                chunk.push_str(&self.footer());
+                self.done = true;
+            }
+        }
+
+        Ok(chunk)
+    }
+
+    fn header(&self) -> Result<String, AppError> {
+        match self.query.format {
+            ExportFormat::Csv => Self::csv_record(&[
+                "id",
+                "date",
+                "type",
+                "amount",
+                "currency",
+                "account",
+                "category",
+                "note",
+                "tags",
+                "external_id",
+            ]),
+            ExportFormat::Json => Ok("[".to_string()),
+            ExportFormat::Ofx => Ok(self.ofx_header()),
+        }
+    }
+
+    fn row(&self, t: &TransactionDetails) -> Result<String, AppError> {
+        match self.query.format {
+            ExportFormat::Csv => Self::csv_record(&[
+                &t.id.to_string(),
+                &t.created_date.to_rfc3339(),
+                &t.transaction_type,
+                &t.amount.to_string(),
+                &t.account.currency,
+                &t.account.name,
+                &t.category.name,
+                t.note.as_deref().unwrap_or(""),
+                &t.tags
+                    .iter()
+                    .map(|tag| tag.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(";"),
+                t.external_id.as_deref().unwrap_or(""),
+            ]),
+            ExportFormat::Json => {
+                let separator = if self.written > 0 { "," } else { "" };
+                serde_json::to_string(t)
+                    .map(|json| format!("{}{}", separator, json))
+                    .map_err(|err| AppError::Internal(err.to_string()))
+            }
+            ExportFormat::Ofx => Ok(Self::ofx_transaction(t)),
+        }
+    }
+
+    fn footer(&self) -> String {
+        match self.query.format {
+            ExportFormat::Csv => String::new(),
+            ExportFormat::Json => "]".to_string(),
+            ExportFormat::Ofx => concat!(
+                "</BANKTRANLIST>\r\n",
+                "</STMTRS>\r\n",
+                "</STMTTRNRS>\r\n",
+                "</BANKMSGSRSV1>\r\n",
+                "</OFX>\r\n"
+            )
+            .to_string(),
+        }
+    }
+
+    fn csv_record(fields: &[&str]) -> Result<String, AppError> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer
+            .write_record(fields)
+            .map_err(|err| AppError::Internal(err.to_string()))?;
+        writer
+            .into_inner()
+            .map_err(|err| AppError::Internal(err.to_string()))
+            .and_then(|bytes| {
+                String::from_utf8(bytes).map_err(|err| AppError::Internal(err.to_string()))
+            })
+    }
+
+    // OFX 1.02 (SGML) is still the most widely accepted flavour among accounting tools
+    fn ofx_header(&self) -> String {
+        let account = self.account.as_ref().expect("OFX export without account");
+        let now = Utc::now().format("%Y%m%d%H%M%S");
+        let account_type = match account.account_type.as_str() {
+            "loan" => "CREDITLINE",
+            "deposit" => "SAVINGS",
+            _ => "CHECKING",
+        };
+        let from = self
+            .query
+            .from
+            .map(|d| format!("<DTSTART>{}\r\n", d.format("%Y%m%d")))
+            .unwrap_or_default();
+        let to = self
+            .query
+            .to
+            .map(|d| format!("<DTEND>{}\r\n", d.format("%Y%m%d")))
+            .unwrap_or_default();
+
+        format!(
+            concat!(
+                "OFXHEADER:100\r\nDATA:OFXSGML\r\nVERSION:102\r\nSECURITY:NONE\r\n",
+                "ENCODING:UTF-8\r\nCHARSET:NONE\r\nCOMPRESSION:NONE\r\n",
+                "OLDFILEUID:NONE\r\nNEWFILEUID:NONE\r\n\r\n",
+                "<OFX>\r\n",
+                "<SIGNONMSGSRSV1>\r\n<SONRS>\r\n",
+                "<STATUS>\r\n<CODE>0\r\n<SEVERITY>INFO\r\n</STATUS>\r\n",
+                "<DTSERVER>{now}\r\n<LANGUAGE>ENG\r\n",
+                "</SONRS>\r\n</SIGNONMSGSRSV1>\r\n",
+                "<BANKMSGSRSV1>\r\n<STMTTRNRS>\r\n<TRNUID>0\r\n",
+                "<STATUS>\r\n<CODE>0\r\n<SEVERITY>INFO\r\n</STATUS>\r\n",
+                "<STMTRS>\r\n<CURDEF>{currency}\r\n",
+                "<BANKACCTFROM>\r\n<BANKID>0\r\n<ACCTID>{account_id}\r\n",
+                "<ACCTTYPE>{account_type}\r\n</BANKACCTFROM>\r\n",
+                "<BANKTRANLIST>\r\n{from}{to}"
+            ),
+            now = now,
+            currency = account.currency,
+            account_id = account.id,
+            account_type = account_type,
+            from = from,
+            to = to,
+        )
+    }
+
+    fn ofx_transaction(t: &TransactionDetails) -> String {
+        let (trn_type, amount) = match t.transaction_type.as_str() {
+            "income" => ("CREDIT", t.amount),
+            _ => ("DEBIT", -t.amount),
+        };
+        let fit_id = t.external_id.clone().unwrap_or_else(|| t.id.to_string());
+        let memo = t
+            .note
+            .as_ref()
+            .map(|note| format!("<MEMO>{}\r\n", Self::ofx_escape(note)))
+            .unwrap_or_default();
+
+        format!(
+            "<STMTTRN>\r\n<TRNTYPE>{}\r\n<DTPOSTED>{}\r\n<TRNAMT>{:.2}\r\n<FITID>{}\r\n<NAME>{}\r\n{}</STMTTRN>\r\n",
+            trn_type,
+            t.created_date.format("%Y%m%d%H%M%S"),
+            amount,
+            Self::ofx_escape(&fit_id),
+            Self::ofx_escape(&t.category.name),
+            memo,
+        )
+    }
+
+    fn ofx_escape(value: &str) -> String {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace(['\r', '\n'], " ")
+    }
+}
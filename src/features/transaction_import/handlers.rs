@@ -0,0 +1,143 @@
+use crate::common::middlewares::auth::AuthenticationService;
+use crate::common::models::errors::AppError;
+use crate::features::transaction_import::models::{
+    CsvImportOptions, CsvParser, ImportRule, ImportRuleData, OfxParser, ParsedRow, QifParser,
+    ReorderImportRulesData, RowError, StatementImportOptions, TransactionImport,
+};
+use crate::repository::database::Database;
+use actix_web::{delete, get, post, put, web, HttpResponse, Scope};
+use uuid::Uuid;
+
+pub fn routes() -> Scope {
+    web::scope("/import")
+        .app_data(web::PayloadConfig::new(10 * 1024 * 1024))
+        .service(import_csv)
+        .service(import_ofx)
+        .service(import_qif)
+        .service(get_rules)
+        .service(create_rule)
+        .service(reorder_rules)
+        .service(update_rule)
+        .service(delete_rule)
+}
+
+#[post("")]
+async fn import_csv(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<CsvImportOptions>,
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    let options = query.into_inner();
+
+    let (rows, errors) = CsvParser::parse(&body, &options)?;
+
+    let options = StatementImportOptions {
+        account_id: options.account_id,
+        income_category_id: options.income_category_id,
+        expense_category_id: options.expense_category_id,
+        dry_run: options.dry_run,
+        date_format: options.date_format,
+    };
+
+    run_import(user, db, options, rows, errors).await
+}
+
+#[post("/ofx")]
+async fn import_ofx(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<StatementImportOptions>,
+    body: String,
+) -> Result<HttpResponse, AppError> {
+    let (rows, errors) = OfxParser::parse(&body)?;
+
+    run_import(user, db, query.into_inner(), rows, errors).await
+}
+
+#[post("/qif")]
+async fn import_qif(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    query: web::Query<StatementImportOptions>,
+    body: String,
+) -> Result<HttpResponse, AppError> {
+    let (rows, errors) = QifParser::parse(&body, query.date_format.as_deref())?;
+
+    run_import(user, db, query.into_inner(), rows, errors).await
+}
+
+async fn run_import(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    options: StatementImportOptions,
+    rows: Vec<ParsedRow>,
+    errors: Vec<RowError>,
+) -> Result<HttpResponse, AppError> {
+    let import = TransactionImport {
+        user_id: user.0.id,
+        account_id: options.account_id,
+        income_category_id: options.income_category_id,
+        expense_category_id: options.expense_category_id,
+        dry_run: options.dry_run.unwrap_or(false),
+    };
+
+    let result = import.run(db.clone(), rows, errors).await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[get("/rules")]
+async fn get_rules(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, AppError> {
+    let rules = ImportRule::get_all(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(rules))
+}
+
+#[post("/rules")]
+async fn create_rule(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    form: web::Json<ImportRuleData>,
+) -> Result<HttpResponse, AppError> {
+    ImportRule::create(db.clone(), user.0.id, form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(ImportRule::get_all(db.clone(), user.0.id).await?))
+}
+
+#[post("/rules/reorder")]
+async fn reorder_rules(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    form: web::Json<Vec<ReorderImportRulesData>>,
+) -> Result<HttpResponse, AppError> {
+    ImportRule::reorder(db.clone(), user.0.id, form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(ImportRule::get_all(db.clone(), user.0.id).await?))
+}
+
+#[put("/rules/{id}")]
+async fn update_rule(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+    form: web::Json<ImportRuleData>,
+) -> Result<HttpResponse, AppError> {
+    ImportRule::update(db.clone(), user.0.id, path.into_inner(), form.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("Import rule updated"))
+}
+
+#[delete("/rules/{id}")]
+async fn delete_rule(
+    user: AuthenticationService,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    ImportRule::delete(db.clone(), user.0.id, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().body("Import rule deleted"))
+}
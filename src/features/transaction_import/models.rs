@@ -0,0 +1,1049 @@
+use crate::common::models::errors::{AppError, OrNotFound};
+use crate::features::category::models::Category;
+use crate::features::transaction::models::{NewTransaction, Transaction};
+use crate::features::user::models::User;
+use crate::repository::database::Database;
+use crate::schema::{accounts, categories, import_rules, transactions};
+use actix_web::web;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Queryable,
+    Selectable,
+    Identifiable,
+    Associations,
+    PartialEq,
+)]
+#[diesel(belongs_to(User, foreign_key = user_id))]
+#[diesel(belongs_to(Category, foreign_key = category_id))]
+#[diesel(table_name = import_rules)]
+pub struct ImportRule {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub pattern: String, // case-insensitive substring of the transaction description
+    pub category_id: Uuid,
+    pub r_order: i32,
+    pub created_date: DateTime<Utc>,
+}
+
+impl ImportRule {
+    pub async fn get_all(
+        db: web::Data<Database>,
+        user_id: Uuid,
+    ) -> Result<Vec<ImportRule>, AppError> {
+        Ok(import_rules::table
+            .filter(import_rules::user_id.eq(user_id))
+            .order(import_rules::r_order.asc())
+            .load::<ImportRule>(&mut db.conn()?)?)
+    }
+
+    pub async fn create(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        data: ImportRuleData,
+    ) -> Result<Uuid, AppError> {
+        let conn = &mut db.conn()?;
+        Self::check_category(conn, user_id, data.category_id)?;
+
+        let r_order: Option<i32> = import_rules::table
+            .filter(import_rules::user_id.eq(user_id))
+            .select(diesel::dsl::max(import_rules::r_order))
+            .first(conn)?;
+
+        diesel::insert_into(import_rules::table)
+            .values(NewImportRule {
+                user_id,
+                pattern: data.pattern,
+                category_id: data.category_id,
+                r_order: r_order.map_or(0, |max_order| max_order + 1),
+            })
+            .get_result::<ImportRule>(conn)
+            .map(|r| r.id)
+            .map_err(AppError::from)
+    }
+
+    pub async fn update(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        id: Uuid,
+        data: ImportRuleData,
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+        Self::check_category(conn, user_id, data.category_id)?;
+
+        diesel::update(import_rules::table)
+            .filter(import_rules::id.eq(id))
+            .filter(import_rules::user_id.eq(user_id))
+            .set(data)
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    pub async fn reorder(
+        db: web::Data<Database>,
+        user_id: Uuid,
+        data: Vec<ReorderImportRulesData>,
+    ) -> Result<(), AppError> {
+        let conn = &mut db.conn()?;
+
+        for d in data {
+            diesel::update(import_rules::table)
+                .filter(import_rules::id.eq(d.id))
+                .filter(import_rules::user_id.eq(user_id))
+                .set(import_rules::r_order.eq(d.r_order))
+                .execute(conn)?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete(db: web::Data<Database>, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        diesel::delete(
+            import_rules::table
+                .filter(import_rules::id.eq(id))
+                .filter(import_rules::user_id.eq(user_id)),
+        )
+        .execute(&mut db.conn()?)?;
+
+        Ok(())
+    }
+
+    // Rules with the category type of their category, in order
+    fn get_typed(conn: &mut PgConnection, user_id: Uuid) -> QueryResult<Vec<(ImportRule, String)>> {
+        import_rules::table
+            .inner_join(categories::table)
+            .filter(import_rules::user_id.eq(user_id))
+            .order(import_rules::r_order.asc())
+            .select((ImportRule::as_select(), categories::category_type))
+            .load::<(ImportRule, String)>(conn)
+    }
+
+    // The first rule matching the description whose category fits the direction of
+    // the row, so that an expense never lands in an income category
+    fn category_for(rules: &[(ImportRule, String)], row: &ParsedRow) -> Option<Uuid> {
+        let note = row.note.as_deref()?;
+        rules
+            .iter()
+            .find(|(rule, category_type)| {
+                *category_type == row.transaction_type && rule.matches(note)
+            })
+            .map(|(rule, _)| rule.category_id)
+    }
+
+    pub fn matches(&self, description: &str) -> bool {
+        description
+            .to_lowercase()
+            .contains(&self.pattern.to_lowercase())
+    }
+
+    fn check_category(conn: &mut PgConnection, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+        categories::table
+            .filter(categories::id.eq(id))
+            .filter(categories::user_id.eq(user_id))
+            .select(categories::id)
+            .first::<Uuid>(conn)
+            .map(|_| ())
+            .or_not_found("category:errors.notFound")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable)]
+#[diesel(table_name = import_rules)]
+pub struct NewImportRule {
+    pub user_id: Uuid,
+    pub pattern: String,
+    pub category_id: Uuid,
+    pub r_order: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, AsChangeset)]
+#[diesel(table_name = import_rules)]
+pub struct ImportRuleData {
+    pub pattern: String,
+    pub category_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReorderImportRulesData {
+    id: Uuid,
+    r_order: i32,
+}
+
+// A statement line after parsing, before categories and duplicates are resolved
+#[derive(Debug, Clone)]
+pub struct ParsedRow {
+    pub line: usize,
+    pub date: NaiveDate,
+    pub amount: f64, // always positive, the direction is in `transaction_type`
+    pub transaction_type: String, // "income", "expense"
+    pub note: Option<String>,
+    pub external_id: Option<String>, // OFX FITID
+}
+
+impl ParsedRow {
+    // Positive amounts are money coming in, negative ones money going out
+    fn create(
+        line: usize,
+        date: NaiveDate,
+        signed_amount: f64,
+        note: Option<String>,
+        external_id: Option<String>,
+    ) -> Self {
+        Self {
+            line,
+            date,
+            amount: signed_amount.abs(),
+            transaction_type: if signed_amount > 0.0 {
+                "income".to_string()
+            } else {
+                "expense".to_string()
+            },
+            note,
+            external_id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RowError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignConvention {
+    #[default]
+    NegativeIsExpense,
+    PositiveIsExpense,
+    DebitCredit,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CsvImportOptions {
+    pub account_id: Uuid,
+    pub income_category_id: Option<Uuid>,
+    pub expense_category_id: Option<Uuid>,
+    pub dry_run: Option<bool>,
+    // Columns are header names, or zero-based indexes when the file has no header
+    pub date_column: String,
+    pub amount_column: Option<String>,
+    pub debit_column: Option<String>,
+    pub credit_column: Option<String>,
+    pub description_column: Option<String>,
+    pub sign_convention: Option<SignConvention>,
+    pub date_format: Option<String>,
+    pub delimiter: Option<char>,
+    pub decimal_separator: Option<char>,
+    pub has_header: Option<bool>,
+}
+
+pub struct CsvParser {}
+
+impl CsvParser {
+    pub fn parse(
+        data: &[u8],
+        options: &CsvImportOptions,
+    ) -> Result<(Vec<ParsedRow>, Vec<RowError>), AppError> {
+        let has_header = options.has_header.unwrap_or(true);
+        let sign_convention = options.sign_convention.unwrap_or_default();
+        let date_format = options.date_format.as_deref().unwrap_or("%Y-%m-%d");
+        let decimal_separator = options.decimal_separator.unwrap_or('.');
+
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(options.delimiter.unwrap_or(',') as u8)
+            .has_headers(has_header)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(data);
+
+        let headers: Vec<String> = if has_header {
+            reader
+                .headers()
+                .map_err(|_| AppError::field("", "import:csv.errors.invalidHeader"))?
+                .iter()
+                .map(|h| h.to_lowercase())
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let column =
+            |field: &'static str, name: &Option<String>| -> Result<Option<usize>, AppError> {
+                match name {
+                    None => Ok(None),
+                    Some(name) => match name.parse::<usize>() {
+                        Ok(index) => Ok(Some(index)),
+                        Err(_) => headers
+                            .iter()
+                            .position(|h| *h == name.to_lowercase())
+                            .map(Some)
+                            .ok_or(AppError::field(field, "import:column.errors.notFound")),
+                    },
+                }
+            };
+
+        let date_column = column("date_column", &Some(options.date_column.clone()))?.unwrap();
+        let description_column = column("description_column", &options.description_column)?;
+        let (amount_column, debit_column, credit_column) = match sign_convention {
+            SignConvention::DebitCredit => (
+                None,
+                column("debit_column", &options.debit_column)?,
+                column("credit_column", &options.credit_column)?,
+            ),
+            _ => (column("amount_column", &options.amount_column)?, None, None),
+        };
+
+        if amount_column.is_none() && debit_column.is_none() && credit_column.is_none() {
+            return Err(AppError::field(
+                "amount_column",
+                "import:column.errors.amountRequired",
+            ));
+        }
+
+        let mut rows = vec![];
+        let mut errors = vec![];
+
+        for (index, record) in reader.records().enumerate() {
+            let line = index + if has_header { 2 } else { 1 };
+
+            let record = match record {
+                Ok(record) => record,
+                Err(_) => {
+                    errors.push(RowError {
+                        line,
+                        message: "Invalid CSV row".to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            let cell = |column: Option<usize>| {
+                column.and_then(|c| record.get(c)).filter(|v| !v.is_empty())
+            };
+
+            let date = match cell(Some(date_column))
+                .and_then(|v| NaiveDate::parse_from_str(v, date_format).ok())
+            {
+                Some(date) => date,
+                None => {
+                    errors.push(RowError {
+                        line,
+                        message: "Invalid date".to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            let parse_amount = |column: Option<usize>| {
+                cell(column).map(|v| Self::parse_amount(v, decimal_separator))
+            };
+
+            // Positive for money coming in, negative for money going out
+            let signed_amount = match sign_convention {
+                SignConvention::NegativeIsExpense => parse_amount(amount_column),
+                SignConvention::PositiveIsExpense => {
+                    parse_amount(amount_column).map(|a| a.map(|a| -a))
+                }
+                SignConvention::DebitCredit => {
+                    match (parse_amount(debit_column), parse_amount(credit_column)) {
+                        (Some(Ok(debit)), _) if debit != 0.0 => Some(Ok(-debit.abs())),
+                        (_, Some(Ok(credit))) => Some(Ok(credit.abs())),
+                        (Some(Err(_)), _) | (_, Some(Err(_))) => Some(Err(())),
+                        _ => None,
+                    }
+                }
+            };
+
+            let signed_amount = match signed_amount {
+                Some(Ok(amount)) if amount != 0.0 => amount,
+                _ => {
+                    errors.push(RowError {
+                        line,
+                        message: "Invalid amount".to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            rows.push(ParsedRow::create(
+                line,
+                date,
+                signed_amount,
+                cell(description_column).map(|v| v.to_string()),
+                None,
+            ));
+        }
+
+        Ok((rows, errors))
+    }
+
+    pub fn parse_amount(value: &str, decimal_separator: char) -> Result<f64, ()> {
+        let normalized: String = value
+            .chars()
+            .filter(|c| c.is_ascii_digit() || *c == '-' || *c == decimal_separator)
+            .map(|c| if c == decimal_separator { '.' } else { c })
+            .collect();
+
+        normalized.parse::<f64>().map_err(|_| ())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StatementImportOptions {
+    pub account_id: Uuid,
+    pub income_category_id: Option<Uuid>,
+    pub expense_category_id: Option<Uuid>,
+    pub dry_run: Option<bool>,
+    pub date_format: Option<String>, // QIF only, dates in OFX are always YYYYMMDD
+}
+
+// Handles both SGML (OFX 1.x, unclosed tags) and XML (OFX 2.x) statements by reading
+// every `<STMTTRN>` block and taking the text that follows each tag
+pub struct OfxParser {}
+
+impl OfxParser {
+    pub fn parse(data: &str) -> Result<(Vec<ParsedRow>, Vec<RowError>), AppError> {
+        let upper = data.to_ascii_uppercase();
+        if !upper.contains("<OFX>") {
+            return Err(AppError::field("", "import:ofx.errors.invalidFile"));
+        }
+
+        let mut rows = vec![];
+        let mut errors = vec![];
+        let mut offset = 0;
+        let mut line = 0;
+
+        while let Some(start) = upper[offset..].find("<STMTTRN>") {
+            let start = offset + start + "<STMTTRN>".len();
+            let end = upper[start..]
+                .find("</STMTTRN>")
+                .map(|e| start + e)
+                .unwrap_or(upper.len());
+            let block = &data[start..end];
+            offset = end;
+            line += 1;
+
+            let date = Self::tag(block, "DTPOSTED")
+                .filter(|d| d.len() >= 8)
+                .and_then(|d| NaiveDate::parse_from_str(&d[..8], "%Y%m%d").ok());
+            let amount = Self::tag(block, "TRNAMT")
+                .and_then(|a| CsvParser::parse_amount(&a, '.').ok())
+                .filter(|a| *a != 0.0);
+
+            let (date, amount) = match (date, amount) {
+                (Some(date), Some(amount)) => (date, amount),
+                (None, _) => {
+                    errors.push(RowError {
+                        line,
+                        message: "Invalid date".to_string(),
+                    });
+                    continue;
+                }
+                (_, None) => {
+                    errors.push(RowError {
+                        line,
+                        message: "Invalid amount".to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            let note = match (Self::tag(block, "NAME"), Self::tag(block, "MEMO")) {
+                (Some(name), Some(memo)) if name != memo => Some(format!("{} {}", name, memo)),
+                (Some(name), _) => Some(name),
+                (None, memo) => memo,
+            };
+
+            rows.push(ParsedRow::create(
+                line,
+                date,
+                amount,
+                note,
+                Self::tag(block, "FITID"),
+            ));
+        }
+
+        Ok((rows, errors))
+    }
+
+    fn tag(block: &str, name: &str) -> Option<String> {
+        let open = format!("<{}>", name);
+        let start = block.to_ascii_uppercase().find(&open)? + open.len();
+        let value = block[start..].split('<').next().unwrap_or("").trim();
+
+        if value.is_empty() {
+            return None;
+        }
+
+        Some(
+            value
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&amp;", "&"),
+        )
+    }
+}
+
+// Quicken Interchange Format: one field per line, the first character is the field
+// code and `^` ends a record
+pub struct QifParser {}
+
+impl QifParser {
+    pub fn parse(
+        data: &str,
+        date_format: Option<&str>,
+    ) -> Result<(Vec<ParsedRow>, Vec<RowError>), AppError> {
+        let date_format = date_format.unwrap_or("%m/%d/%Y");
+
+        let mut rows = vec![];
+        let mut errors = vec![];
+
+        let mut record_line = 0;
+        let mut date: Option<String> = None;
+        let mut amount: Option<String> = None;
+        let mut payee: Option<String> = None;
+        let mut memo: Option<String> = None;
+
+        for (index, raw_line) in data.lines().enumerate() {
+            let raw_line = raw_line.trim();
+            if raw_line.is_empty() || raw_line.starts_with('!') {
+                continue;
+            }
+            if record_line == 0 {
+                record_line = index + 1;
+            }
+
+            let code = raw_line.chars().next().unwrap();
+            let value = raw_line[code.len_utf8()..].trim().to_string();
+
+            match code {
+                'D' => date = Some(value),
+                'T' | 'U' => amount = amount.or(Some(value)),
+                'P' => payee = Some(value),
+                'M' => memo = Some(value),
+                '^' => {
+                    let parsed_date = date.take().and_then(|d| Self::parse_date(&d, date_format));
+                    let parsed_amount = amount
+                        .take()
+                        .and_then(|a| CsvParser::parse_amount(&a, '.').ok())
+                        .filter(|a| *a != 0.0);
+                    let note = match (payee.take(), memo.take()) {
+                        (Some(payee), Some(memo)) if payee != memo => {
+                            Some(format!("{} {}", payee, memo))
+                        }
+                        (Some(payee), _) => Some(payee),
+                        (None, memo) => memo,
+                    };
+
+                    match (parsed_date, parsed_amount) {
+                        (Some(d), Some(a)) => {
+                            rows.push(ParsedRow::create(record_line, d, a, note, None))
+                        }
+                        (None, _) => errors.push(RowError {
+                            line: record_line,
+                            message: "Invalid date".to_string(),
+                        }),
+                        (_, None) => errors.push(RowError {
+                            line: record_line,
+                            message: "Invalid amount".to_string(),
+                        }),
+                    }
+
+                    record_line = 0;
+                }
+                _ => {}
+            }
+        }
+
+        Ok((rows, errors))
+    }
+
+    // Quicken writes years after 2000 as `1/15'26`
+    fn parse_date(value: &str, date_format: &str) -> Option<NaiveDate> {
+        let value = value.replace('\'', "/").replace(' ', "");
+
+        let date = NaiveDate::parse_from_str(&value, date_format).ok()?;
+        if date.year() < 100 {
+            return NaiveDate::parse_from_str(&value, &date_format.replace("%Y", "%y")).ok();
+        }
+
+        Some(date)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportPreviewRow {
+    pub line: usize,
+    pub date: NaiveDate,
+    pub amount: f64,
+    pub transaction_type: String,
+    pub note: Option<String>,
+    pub external_id: Option<String>,
+    pub category_id: Option<Uuid>,
+    pub duplicate: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportResult {
+    pub dry_run: bool,
+    pub total: usize,
+    pub imported: usize,
+    pub duplicates: usize,
+    pub failed: usize,
+    pub rows: Vec<ImportPreviewRow>,
+    pub errors: Vec<RowError>,
+}
+
+pub struct TransactionImport {
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    pub income_category_id: Option<Uuid>,
+    pub expense_category_id: Option<Uuid>,
+    pub dry_run: bool,
+}
+
+impl TransactionImport {
+    // Shared by every statement format: resolves categories through the user's rules,
+    // flags rows already present in the account and posts the rest
+    pub async fn run(
+        &self,
+        db: web::Data<Database>,
+        rows: Vec<ParsedRow>,
+        errors: Vec<RowError>,
+    ) -> Result<ImportResult, AppError> {
+        let conn = &mut db.conn()?;
+
+        let archived = accounts::table
+            .filter(accounts::id.eq(self.account_id))
+            .filter(accounts::user_id.eq(self.user_id))
+            .select(accounts::archived)
+            .first::<bool>(conn)
+            .or_not_found("account:errors.notFound")?;
+        if archived {
+            return Err(AppError::field("account_id", "account:errors.archived"));
+        }
+
+        for category_id in [self.income_category_id, self.expense_category_id]
+            .into_iter()
+            .flatten()
+        {
+            ImportRule::check_category(conn, self.user_id, category_id)?;
+        }
+
+        let rules = ImportRule::get_typed(conn, self.user_id)?;
+        let (mut existing, mut external_ids) = self.existing_transactions(conn, &rows)?;
+
+        let mut result = ImportResult {
+            dry_run: self.dry_run,
+            total: rows.len() + errors.len(),
+            imported: 0,
+            duplicates: 0,
+            failed: errors.len(),
+            rows: vec![],
+            errors,
+        };
+
+        for row in rows {
+            let category_id =
+                ImportRule::category_for(&rules, &row).or(match row.transaction_type.as_str() {
+                    "income" => self.income_category_id,
+                    _ => self.expense_category_id,
+                });
+
+            // Rows with a bank identifier are matched by it alone. The others are matched
+            // by date, amount and note, consuming existing rows one by one so a statement
+            // with two identical purchases on the same day still imports the second one
+            // if only one exists
+            let duplicate = match &row.external_id {
+                Some(external_id) => !external_ids.insert(external_id.clone()),
+                None => {
+                    let key = DuplicateKey::create(row.date, row.amount, &row.note);
+                    match existing.get_mut(&key) {
+                        Some(count) if *count > 0 => {
+                            *count -= 1;
+                            true
+                        }
+                        _ => false,
+                    }
+                }
+            };
+
+            let mut error = None;
+            if duplicate {
+                result.duplicates += 1;
+            } else if let Some(category_id) = category_id {
+                if !self.dry_run {
+                    let new_transaction = NewTransaction {
+                        user_id: self.user_id,
+                        account_id: self.account_id,
+                        category_id,
+                        transaction_type: row.transaction_type.clone(),
+                        note: row.note.clone(),
+                        amount: row.amount,
+                        created_date: row.date.and_time(NaiveTime::MIN).and_utc(),
+                        recurring_id: None,
+                        recurring_date: None,
+                        external_id: row.external_id.clone(),
+                    };
+
+                    if let Err(err) = Transaction::post(conn, &new_transaction) {
+                        error = Some(err.code().to_string());
+                    }
+                }
+            } else {
+                error = Some("import:errors.noCategoryMatched".to_string());
+            }
+
+            match error {
+                Some(_) => result.failed += 1,
+                None if !duplicate => result.imported += 1,
+                None => {}
+            }
+
+            result.rows.push(ImportPreviewRow {
+                line: row.line,
+                date: row.date,
+                amount: row.amount,
+                transaction_type: row.transaction_type,
+                note: row.note,
+                external_id: row.external_id,
+                category_id,
+                duplicate,
+                error,
+            });
+        }
+
+        Ok(result)
+    }
+
+    fn existing_transactions(
+        &self,
+        conn: &mut PgConnection,
+        rows: &[ParsedRow],
+    ) -> Result<(HashMap<DuplicateKey, usize>, HashSet<String>), AppError> {
+        let external_ids = transactions::table
+            .filter(transactions::account_id.eq(self.account_id))
+            .filter(
+                transactions::external_id.eq_any(rows.iter().filter_map(|r| r.external_id.clone())),
+            )
+            .select(transactions::external_id)
+            .load::<Option<String>>(conn)?
+            .into_iter()
+            .flatten()
+            .collect::<HashSet<String>>();
+
+        let (from, to) = match (
+            rows.iter().map(|r| r.date).min(),
+            rows.iter().map(|r| r.date).max(),
+        ) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Ok((HashMap::new(), external_ids)),
+        };
+
+        let existing = transactions::table
+            .filter(transactions::account_id.eq(self.account_id))
+            .filter(transactions::external_id.is_null())
+            .filter(transactions::created_date.ge(from.and_time(NaiveTime::MIN).and_utc()))
+            .filter(
+                transactions::created_date
+                    .lt((to + Duration::days(1)).and_time(NaiveTime::MIN).and_utc()),
+            )
+            .select((
+                transactions::created_date,
+                transactions::amount,
+                transactions::note,
+            ))
+            .load::<(DateTime<Utc>, f64, Option<String>)>(conn)?;
+
+        let mut map = HashMap::new();
+        for (date, amount, note) in existing {
+            *map.entry(DuplicateKey::create(date.date_naive(), amount, &note))
+                .or_insert(0) += 1;
+        }
+
+        Ok((map, external_ids))
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq)]
+struct DuplicateKey {
+    date: NaiveDate,
+    cents: i64,
+    note: String,
+}
+
+impl DuplicateKey {
+    fn create(date: NaiveDate, amount: f64, note: &Option<String>) -> Self {
+        Self {
+            date,
+            cents: (amount * 100.0).round() as i64,
+            note: note.clone().unwrap_or_default().trim().to_lowercase(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn csv_options() -> CsvImportOptions {
+        CsvImportOptions {
+            account_id: Uuid::nil(),
+            income_category_id: None,
+            expense_category_id: None,
+            dry_run: None,
+            date_column: "date".to_string(),
+            amount_column: Some("amount".to_string()),
+            debit_column: None,
+            credit_column: None,
+            description_column: Some("description".to_string()),
+            sign_convention: None,
+            date_format: None,
+            delimiter: None,
+            decimal_separator: None,
+            has_header: None,
+        }
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn csv_signs_amounts_by_default() {
+        let data = "Date,Amount,Description\n2024-05-01,-12.50,Coffee\n2024-05-02,1000,Salary\n";
+
+        let (rows, errors) = CsvParser::parse(data.as_bytes(), &csv_options()).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[0].date, date(2024, 5, 1));
+        assert_eq!(rows[0].amount, 12.5);
+        assert_eq!(rows[0].transaction_type, "expense");
+        assert_eq!(rows[0].note.as_deref(), Some("Coffee"));
+        assert_eq!(rows[1].transaction_type, "income");
+    }
+
+    #[test]
+    fn csv_reports_invalid_rows_and_keeps_the_rest() {
+        let data = "date,amount,description\nnot a date,5,A\n2024-05-01,abc,B\n2024-05-01,0,C\n2024-05-03,7,D\n";
+
+        let (rows, errors) = CsvParser::parse(data.as_bytes(), &csv_options()).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].line, 5);
+        let lines = errors.iter().map(|e| e.line).collect::<Vec<usize>>();
+        assert_eq!(lines, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn csv_reads_debit_and_credit_columns_by_index() {
+        let options = CsvImportOptions {
+            date_column: "0".to_string(),
+            amount_column: None,
+            debit_column: Some("1".to_string()),
+            credit_column: Some("2".to_string()),
+            description_column: Some("3".to_string()),
+            sign_convention: Some(SignConvention::DebitCredit),
+            date_format: Some("%d.%m.%Y".to_string()),
+            delimiter: Some(';'),
+            decimal_separator: Some(','),
+            has_header: Some(false),
+            ..csv_options()
+        };
+        let data = "01.05.2024;1 234,50;;Rent\n02.05.2024;;99,90;Refund\n";
+
+        let (rows, errors) = CsvParser::parse(data.as_bytes(), &options).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(rows[0].amount, 1234.5);
+        assert_eq!(rows[0].transaction_type, "expense");
+        assert_eq!(rows[1].amount, 99.9);
+        assert_eq!(rows[1].transaction_type, "income");
+        assert_eq!(rows[1].line, 2);
+    }
+
+    #[test]
+    fn csv_positive_is_expense_flips_the_sign() {
+        let options = CsvImportOptions {
+            sign_convention: Some(SignConvention::PositiveIsExpense),
+            ..csv_options()
+        };
+        let data = "date,amount,description\n2024-05-01,20,Card\n";
+
+        let (rows, _) = CsvParser::parse(data.as_bytes(), &options).unwrap();
+
+        assert_eq!(rows[0].transaction_type, "expense");
+    }
+
+    #[test]
+    fn csv_rejects_unknown_columns() {
+        let options = CsvImportOptions {
+            amount_column: Some("sum".to_string()),
+            ..csv_options()
+        };
+        let data = "date,amount,description\n2024-05-01,20,Card\n";
+
+        assert!(CsvParser::parse(data.as_bytes(), &options).is_err());
+    }
+
+    #[test]
+    fn parse_amount_ignores_currency_symbols_and_grouping() {
+        assert_eq!(CsvParser::parse_amount("$1,234.56", '.'), Ok(1234.56));
+        assert_eq!(CsvParser::parse_amount("-1.234,56 €", ','), Ok(-1234.56));
+        assert!(CsvParser::parse_amount("n/a", '.').is_err());
+    }
+
+    #[test]
+    fn import_rules_match_case_insensitively() {
+        let rule = ImportRule {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            pattern: "Coffee".to_string(),
+            category_id: Uuid::nil(),
+            r_order: 0,
+            created_date: Utc::now(),
+        };
+
+        assert!(rule.matches("STARBUCKS COFFEE #12"));
+        assert!(!rule.matches("Groceries"));
+    }
+
+    #[test]
+    fn import_rules_only_apply_to_their_direction() {
+        let rule = |pattern: &str, category_id: u128| ImportRule {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            pattern: pattern.to_string(),
+            category_id: Uuid::from_u128(category_id),
+            r_order: 0,
+            created_date: Utc::now(),
+        };
+        let rules = vec![
+            (rule("refund", 1), "expense".to_string()),
+            (rule("refund", 2), "income".to_string()),
+            (rule("shop", 3), "expense".to_string()),
+        ];
+        let row = |transaction_type: &str, note: &str| ParsedRow {
+            line: 1,
+            date: date(2024, 5, 1),
+            amount: 10.0,
+            transaction_type: transaction_type.to_string(),
+            note: Some(note.to_string()),
+            external_id: None,
+        };
+
+        assert_eq!(
+            ImportRule::category_for(&rules, &row("income", "Shop refund")),
+            Some(Uuid::from_u128(2))
+        );
+        assert_eq!(
+            ImportRule::category_for(&rules, &row("expense", "Shop refund")),
+            Some(Uuid::from_u128(1))
+        );
+        assert_eq!(
+            ImportRule::category_for(&rules, &row("income", "Shop")),
+            None
+        );
+    }
+
+    #[test]
+    fn ofx_reads_sgml_statements() {
+        let data = "OFXHEADER:100\n<OFX><BANKTRANLIST>\n\
+            <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20240501120000[-5:EST]<TRNAMT>-42.10<FITID>A1<NAME>Grocer &amp; Co<MEMO>Card\n</STMTTRN>\
+            <STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20240502<TRNAMT>1500.00<FITID>A2<NAME>Payroll<MEMO>Payroll\n</STMTTRN>\
+            </BANKTRANLIST></OFX>";
+
+        let (rows, errors) = OfxParser::parse(data).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].date, date(2024, 5, 1));
+        assert_eq!(rows[0].amount, 42.1);
+        assert_eq!(rows[0].transaction_type, "expense");
+        assert_eq!(rows[0].note.as_deref(), Some("Grocer & Co Card"));
+        assert_eq!(rows[0].external_id.as_deref(), Some("A1"));
+        assert_eq!(rows[1].note.as_deref(), Some("Payroll"));
+        assert_eq!(rows[1].transaction_type, "income");
+    }
+
+    #[test]
+    fn ofx_reads_xml_statements_and_reports_bad_blocks() {
+        let data = "<?xml version=\"1.0\"?><OFX>\
+            <STMTTRN><DTPOSTED>20240503</DTPOSTED><TRNAMT>-5</TRNAMT><FITID>B1</FITID></STMTTRN>\
+            <STMTTRN><DTPOSTED>bad</DTPOSTED><TRNAMT>-5</TRNAMT></STMTTRN>\
+            <STMTTRN><DTPOSTED>20240503</DTPOSTED><TRNAMT>0</TRNAMT></STMTTRN>\
+            </OFX>";
+
+        let (rows, errors) = OfxParser::parse(data).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].external_id.as_deref(), Some("B1"));
+        assert_eq!(rows[0].note, None);
+        assert_eq!(errors[0].line, 2);
+        assert_eq!(errors[0].message, "Invalid date");
+        assert_eq!(errors[1].line, 3);
+        assert_eq!(errors[1].message, "Invalid amount");
+    }
+
+    #[test]
+    fn ofx_keeps_non_ascii_text_aligned() {
+        let data = "<OFX><STMTTRN><DTPOSTED>20240504<TRNAMT>-3<NAME>Çay Ocağı<MEMO>ı</STMTTRN>\
+            <STMTTRN><DTPOSTED>20240505<TRNAMT>-4<NAME>ǅ İstanbul</STMTTRN></OFX>";
+
+        let (rows, errors) = OfxParser::parse(data).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].note.as_deref(), Some("Çay Ocağı ı"));
+        assert_eq!(rows[1].note.as_deref(), Some("ǅ İstanbul"));
+    }
+
+    #[test]
+    fn ofx_rejects_other_files() {
+        assert!(OfxParser::parse("date,amount\n").is_err());
+    }
+
+    #[test]
+    fn qif_reads_records() {
+        let data =
+            "!Type:Bank\nD05/01/2024\nT-1,250.00\nPRent\nMMay\n^\nD1/15'26\nU300\nPRefund\n^\n";
+
+        let (rows, errors) = QifParser::parse(data, None).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[0].date, date(2024, 5, 1));
+        assert_eq!(rows[0].amount, 1250.0);
+        assert_eq!(rows[0].transaction_type, "expense");
+        assert_eq!(rows[0].note.as_deref(), Some("Rent May"));
+        assert_eq!(rows[1].line, 7);
+        assert_eq!(rows[1].date, date(2026, 1, 15));
+        assert_eq!(rows[1].transaction_type, "income");
+    }
+
+    #[test]
+    fn qif_uses_the_given_date_format_and_reports_bad_records() {
+        let data = "D31.12.2024\nT-10\n^\nDsoon\nT-10\n^\n";
+
+        let (rows, errors) = QifParser::parse(data, Some("%d.%m.%Y")).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].date, date(2024, 12, 31));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 4);
+    }
+}
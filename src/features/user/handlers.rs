@@ -1,9 +1,8 @@
-use std::str::FromStr;
-
-use actix_web::{get, post, put, web, HttpRequest, HttpResponse, Scope};
-use uuid::Uuid;
+use actix_web::{get, post, put, web, HttpResponse, Scope};
 
 use crate::common::middlewares::auth::AuthenticationService;
+use crate::common::middlewares::validated_json::ValidatedJson;
+use crate::common::models::errors::AppError;
 use crate::common::services::crypto::Auth;
 use crate::common::services::hashing::hashing;
 use crate::features::achievement::models::Achievement;
@@ -20,79 +19,58 @@ pub fn routes() -> Scope {
 }
 
 #[post("/signup")]
-async fn create(db: web::Data<Database>, form: web::Json<NewUserData>) -> HttpResponse {
-    let res = User::create(db.clone(), form.clone()).await;
-
-    match res {
-        Ok(user) => {
-            let token = hashing().generate_jwt(user.id).await.unwrap();
+async fn create(
+    db: web::Data<Database>,
+    form: ValidatedJson<NewUserData>,
+) -> Result<HttpResponse, AppError> {
+    let user = User::create(db.clone(), form.clone()).await?;
+    let token = hashing().generate_jwt(user.id).await?;
 
-            tokio::spawn(Category::create_default(db.clone(), user.id));
-            tokio::spawn(Achievement::create_default(
-                db.clone(),
-                user.id,
-                "habits".to_string(),
-            ));
+    tokio::spawn(Category::create_default(db.clone(), user.id));
+    tokio::spawn(Achievement::create_default(
+        db.clone(),
+        user.id,
+        "habits".to_string(),
+    ));
+    tokio::spawn(Achievement::create_default(
+        db.clone(),
+        user.id,
+        "savings".to_string(),
+    ));
 
-            HttpResponse::Ok().json(Auth { token })
-        }
-        Err(err) => HttpResponse::BadRequest().json(err),
-    }
+    Ok(HttpResponse::Ok().json(Auth { token }))
 }
 
 #[get("/me")]
-async fn get(db: web::Data<Database>, req: HttpRequest) -> HttpResponse {
-    let token = req
-        .headers()
-        .get("Authorization")
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .replace("Bearer ", "");
-
-    let verified = hashing().verify_jwt(token.to_string()).await;
-    match verified {
-        Ok(v) => {
-            let user = User::get_by_id(db.clone(), Uuid::from_str(&v.claims.sub).unwrap())
-                .await
-                .unwrap();
-            HttpResponse::Ok().json(&user)
-        }
-        Err(_) => HttpResponse::Unauthorized().finish(),
-    }
+async fn get(user: AuthenticationService) -> HttpResponse {
+    HttpResponse::Ok().json(&user.0)
 }
 
 #[put("/me")]
 async fn update(
     user: AuthenticationService,
     db: web::Data<Database>,
-    form: web::Json<UpdateUserData>,
-) -> HttpResponse {
-    let user_id = user.0.id;
-    match User::update(db.clone(), user.0.id, form.into_inner()).await {
-        Ok(_) => match User::get_by_id(db.clone(), user_id).await {
-            Ok(u) => HttpResponse::Ok().json(&u),
-            Err(err) => HttpResponse::InternalServerError().body(err),
-        },
-        Err(err) => HttpResponse::InternalServerError().body(err),
-    }
+    form: ValidatedJson<UpdateUserData>,
+) -> Result<HttpResponse, AppError> {
+    User::update(db.clone(), user.0.id, form.into_inner()).await?;
+    let user = User::get_by_id(db.clone(), user.0.id).await?;
+
+    Ok(HttpResponse::Ok().json(&user))
 }
 
 #[post("/me/change-password")]
 async fn change_password(
     user: AuthenticationService,
     db: web::Data<Database>,
-    form: web::Json<ChangePasswordData>,
-) -> HttpResponse {
-    match User::change_password(
+    form: ValidatedJson<ChangePasswordData>,
+) -> Result<HttpResponse, AppError> {
+    User::change_password(
         db.clone(),
         user.0.id,
         form.clone().current_password,
         form.clone().new_password,
     )
-    .await
-    {
-        Ok(_) => HttpResponse::Ok().into(),
-        Err(err) => HttpResponse::BadRequest().json(err),
-    }
+    .await?;
+
+    Ok(HttpResponse::Ok().into())
 }
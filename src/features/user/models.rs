@@ -1,9 +1,12 @@
-use crate::{common::models::errors::FormError, schema::users};
+use crate::common::models::errors::{AppError, OrNotFound};
+use crate::schema::users;
 use actix_web::web;
 use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use diesel::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use validator::{Validate, ValidationError};
 
 use crate::common::services::hashing::hashing;
 use crate::repository::database::Database;
@@ -23,71 +26,77 @@ pub struct User {
     pub active: bool,
     pub created_date: DateTime<Utc>,
     pub updated_date: DateTime<Utc>,
+    pub timezone: String, // IANA name, e.g. "Europe/Madrid"
 }
 
 impl User {
-    pub async fn create(
-        db: web::Data<Database>,
-        user_data: NewUserData,
-    ) -> Result<User, FormError<'static>> {
-        let password_hash = hashing()
-            .hash_password(user_data.clone().password)
-            .await
-            .unwrap();
-
-        let user_form = NewUser::create(user_data, password_hash);
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
 
-        let existing_user =
-            Self::get_by_username(db.clone(), user_form.clone().username.unwrap()).await;
+    pub async fn create(db: web::Data<Database>, user_data: NewUserData) -> Result<User, AppError> {
+        let password_hash = hashing().hash_password(user_data.clone().password).await?;
 
-        if existing_user.is_ok() {
-            return Err(FormError {
-                field: "username",
-                message: "profile:username.errors.alreadyExists",
-            });
+        let user_form = NewUser::create(user_data, password_hash);
+        let conn = &mut db.conn()?;
+
+        let existing_user = users::table
+            .filter(users::username.eq(&user_form.username))
+            .select(users::id)
+            .first::<Uuid>(conn)
+            .optional()?;
+
+        if existing_user.is_some() {
+            return Err(AppError::field(
+                "username",
+                "profile:username.errors.alreadyExists",
+            ));
         }
 
-        let new_user = diesel::insert_into(users::table)
+        diesel::insert_into(users::table)
             .values(&user_form)
-            .get_result::<User>(&mut db.pool.get().unwrap());
-
-        match new_user {
-            Ok(new_user) => Ok(new_user),
-            Err(_) => Err(FormError {
-                field: "",
-                message: "profile:errors.failedToCreate",
-            }),
-        }
+            .get_result::<User>(conn)
+            .map_err(|err| match AppError::from(err) {
+                AppError::Conflict(_) => {
+                    AppError::field("username", "profile:username.errors.alreadyExists")
+                }
+                err => err,
+            })
     }
 
-    pub async fn get_by_id(db: web::Data<Database>, id: Uuid) -> Result<User, String> {
+    pub async fn get_by_id(db: web::Data<Database>, id: Uuid) -> Result<User, AppError> {
         users::table
             .find(id)
-            .first::<User>(&mut db.pool.get().unwrap())
-            .map_err(|_| "User not found".to_string())
+            .first::<User>(&mut db.conn()?)
+            .or_not_found("profile:errors.notFound")
     }
 
     pub async fn get_by_username(
         db: web::Data<Database>,
         username: String,
-    ) -> Result<User, String> {
+    ) -> Result<User, AppError> {
         users::table
             .filter(users::username.eq(username))
-            .first::<User>(&mut db.pool.get().unwrap())
-            .map_err(|_| "User not found".to_string())
+            .first::<User>(&mut db.conn()?)
+            .or_not_found("profile:username.errors.notFound")
     }
 
     pub async fn update(
         db: web::Data<Database>,
         id: Uuid,
         user: UpdateUserData,
-    ) -> Result<(), String> {
+    ) -> Result<(), AppError> {
         diesel::update(users::table)
             .filter(users::id.eq(id))
             .set(user)
-            .execute(&mut db.pool.get().unwrap())
+            .execute(&mut db.conn()?)
             .map(|_| ())
-            .map_err(|_| "Failed to update user".to_string())
+            .map_err(|err| match AppError::from(err) {
+                AppError::Conflict(_) => {
+                    AppError::field("username", "profile:username.errors.alreadyExists")
+                }
+                err => err,
+            })
     }
 
     pub async fn change_password(
@@ -95,50 +104,34 @@ impl User {
         id: Uuid,
         old_password: String,
         new_password: String,
-    ) -> Result<(), FormError<'static>> {
-        let user = match Self::get_by_id(db.clone(), id).await {
-            Ok(user) => user,
-            Err(_) => {
-                return Err(FormError {
-                    field: "",
-                    message: "profile:password.errors.failedToChange",
-                })
-            }
-        };
+    ) -> Result<(), AppError> {
+        let user = Self::get_by_id(db.clone(), id).await?;
 
         if !hashing()
             .verify_password(&old_password, &user.password_hash)
             .await
-            .unwrap()
         {
-            return Err(FormError {
-                field: "currentPassword",
-                message: "profile:password.errors.incorrect",
-            });
+            return Err(AppError::field(
+                "currentPassword",
+                "profile:password.errors.incorrect",
+            ));
         }
 
-        let new_password_hash = hashing().hash_password(new_password.clone()).await.unwrap();
+        let new_password_hash = hashing().hash_password(new_password.clone()).await?;
 
         if user.password_hash == new_password_hash {
-            return Err(FormError {
-                field: "newPassword",
-                message: "profile:password.errors.sameAsOld",
-            });
+            return Err(AppError::field(
+                "newPassword",
+                "profile:password.errors.sameAsOld",
+            ));
         }
 
-        match diesel::update(users::table)
+        diesel::update(users::table)
             .filter(users::id.eq(id))
             .set(users::password_hash.eq(new_password_hash))
-            .execute(&mut db.pool.get().unwrap())
-        {
-            Ok(_) => Ok(()),
-            Err(_) => {
-                return Err(FormError {
-                    field: "",
-                    message: "profile:password.errors.failedToChange",
-                })
-            }
-        }
+            .execute(&mut db.conn()?)
+            .map(|_| ())
+            .map_err(AppError::from)
     }
 }
 
@@ -156,6 +149,7 @@ pub struct NewUser {
     pub active: bool,
     pub created_date: DateTime<Utc>,
     pub updated_date: DateTime<Utc>,
+    pub timezone: Option<String>,
 }
 
 impl NewUser {
@@ -172,33 +166,76 @@ impl NewUser {
             active: true,
             created_date: Utc::now(),
             updated_date: Utc::now(),
+            timezone: new_user.timezone,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Validate)]
 pub struct NewUserData {
+    #[validate(length(min = 1, max = 50, message = "profile:name.errors.length"))]
     pub name: String,
+    #[validate(length(min = 1, max = 50, message = "profile:surname.errors.length"))]
     pub surname: String,
+    #[validate(
+        length(min = 3, max = 30, message = "profile:username.errors.length"),
+        custom = "validate_username"
+    )]
     pub username: String,
+    #[validate(email(message = "profile:email.errors.invalid"))]
     pub email: String,
+    #[validate(length(min = 8, max = 128, message = "profile:password.errors.length"))]
     pub password: String,
+    #[validate(custom = "validate_timezone")]
+    pub timezone: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset)]
+#[derive(Debug, Serialize, Deserialize, Clone, Insertable, AsChangeset, Validate)]
 #[diesel(table_name = users)]
 pub struct UpdateUserData {
+    #[validate(length(min = 1, max = 50, message = "profile:name.errors.length"))]
     pub name: Option<String>,
+    #[validate(length(min = 1, max = 50, message = "profile:surname.errors.length"))]
     pub surname: Option<String>,
+    #[validate(
+        length(min = 3, max = 30, message = "profile:username.errors.length"),
+        custom = "validate_username"
+    )]
     pub username: Option<String>,
+    #[validate(email(message = "profile:email.errors.invalid"))]
     pub email: Option<String>,
+    #[validate(length(max = 500, message = "profile:bio.errors.tooLong"))]
     pub bio: Option<String>,
     pub image: Option<String>,
+    #[validate(custom = "validate_timezone")]
+    pub timezone: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct ChangePasswordData {
     pub current_password: String,
+    #[validate(length(min = 8, max = 128, message = "profile:password.errors.length"))]
     pub new_password: String,
 }
+
+// Usernames end up in profile URLs
+fn validate_username(username: &str) -> Result<(), ValidationError> {
+    if username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new(
+            "profile:username.errors.invalidCharacters",
+        ))
+    }
+}
+
+fn validate_timezone(timezone: &str) -> Result<(), ValidationError> {
+    match timezone.parse::<Tz>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ValidationError::new("profile:timezone.errors.invalid")),
+    }
+}
@@ -11,6 +11,9 @@ use dotenv::dotenv;
 
 use tokio::sync::mpsc;
 
+use crate::common::services::notifier::{notifiers, PushNotifier};
+use crate::common::services::storage::{storage, BlobStorage};
+
 #[macro_use]
 extern crate diesel;
 
@@ -27,7 +30,7 @@ pub struct Database {
 }
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    set_var("RUST_LOG", "actix_web=info");
+    set_var("RUST_LOG", "actix_web=info,habits=info");
     env_logger::init();
     dotenv().ok();
 
@@ -41,6 +44,11 @@ async fn main() -> std::io::Result<()> {
 
     let db = repository::database::Database::new();
     let app_data = web::Data::new(db);
+    let storage_data: Data<dyn BlobStorage> = Data::from(storage());
+    let push_notifier = PushNotifier::default();
+    let notifiers_data = Data::new(notifiers(push_notifier.clone()));
+
+    common::services::scheduler::start(app_data.clone(), notifiers_data.get_ref().clone());
 
     HttpServer::new(move || {
         App::new()
@@ -58,6 +66,9 @@ async fn main() -> std::io::Result<()> {
             .app_data(app_data.clone())
             .app_data(Data::new(achievements_sender.clone()))
             .app_data(Data::new(achievements_receiver.clone()))
+            .app_data(storage_data.clone())
+            .app_data(Data::new(push_notifier.clone()))
+            .app_data(notifiers_data.clone())
             .service(routes::routes())
     })
     .bind(format!("0.0.0.0:{}", port))?
@@ -5,9 +5,29 @@
 
 
 use chrono::NaiveDate;
+use chrono::NaiveTime;
 use uuid::Uuid;
 use chrono::DateTime;
 use chrono::offset::Utc;
+#[derive(Queryable, Debug)]
+pub struct AccountBalanceSnapshot {
+    pub account_id: Uuid,
+    pub date: NaiveDate,
+    pub balance: f64,
+}
+
+#[derive(Queryable, Debug)]
+pub struct AccountTerm {
+    pub account_id: Uuid,
+    pub interest_rate: f64,
+    pub compounding: String,
+    pub term_months: i32,
+    pub start_date: NaiveDate,
+    pub auto_post: bool,
+    pub category_id: Option<Uuid>,
+    pub last_posted_date: Option<NaiveDate>,
+}
+
 #[derive(Queryable, Debug)]
 pub struct Account {
     pub id: Uuid,
@@ -18,6 +38,7 @@ pub struct Account {
     pub amount: f64,
     pub created_date: DateTime<Utc>,
     pub a_order: i32,
+    pub archived: bool,
 }
 
 #[derive(Queryable, Debug)]
@@ -32,6 +53,24 @@ pub struct Achievement {
     pub created_date: DateTime<Utc>,
 }
 
+#[derive(Queryable, Debug)]
+pub struct BudgetCategory {
+    pub id: Uuid,
+    pub budget_id: Uuid,
+    pub category_id: Uuid,
+}
+
+#[derive(Queryable, Debug)]
+pub struct Budget {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub amount: f64,
+    pub period: String,
+    pub rollover: bool,
+    pub created_date: DateTime<Utc>,
+}
+
 #[derive(Queryable, Debug)]
 pub struct Category {
     pub id: Uuid,
@@ -44,6 +83,43 @@ pub struct Category {
     pub created_date: DateTime<Utc>,
     pub modified_date: Option<DateTime<Utc>>,
     pub c_order: i32,
+    pub parent_id: Option<Uuid>,
+    pub archived: bool,
+}
+
+#[derive(Queryable, Debug)]
+pub struct HabitGroup {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub color: String,
+    pub g_order: i32,
+    pub created_date: DateTime<Utc>,
+}
+
+#[derive(Queryable, Debug)]
+pub struct HabitReminder {
+    pub id: Uuid,
+    pub habit_id: Uuid,
+    pub user_id: Uuid,
+    pub time: NaiveTime,
+    pub channel: String,
+    pub webhook_url: Option<String>,
+    pub last_sent_date: Option<NaiveDate>,
+    pub snoozed_until: Option<DateTime<Utc>>,
+    pub failed_attempts: i32,
+    pub retry_after: Option<DateTime<Utc>>,
+    pub created_date: DateTime<Utc>,
+}
+
+#[derive(Queryable, Debug)]
+pub struct HabitTimerSession {
+    pub id: Uuid,
+    pub habit_id: Uuid,
+    pub user_id: Uuid,
+    pub started_date: DateTime<Utc>,
+    pub stopped_date: Option<DateTime<Utc>>,
+    pub minutes: Option<f64>,
 }
 
 #[derive(Queryable, Debug)]
@@ -57,9 +133,14 @@ pub struct Habit {
     pub deleted: bool,
     pub color: String,
     pub icon: String,
-    pub amount: i32,
+    pub amount: f64,
     pub frequency_type: String,
     pub frequency_amount: serde_json::Value,
+    pub polarity: String,
+    pub unit: String,
+    pub goal_direction: String,
+    pub h_order: i32,
+    pub group_id: Option<Uuid>,
 }
 
 #[derive(Queryable, Debug)]
@@ -71,6 +152,67 @@ pub struct HabitsAchievement {
     pub progress: i32,
 }
 
+#[derive(Queryable, Debug)]
+pub struct ImportRule {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub pattern: String,
+    pub category_id: Uuid,
+    pub r_order: i32,
+    pub created_date: DateTime<Utc>,
+}
+
+#[derive(Queryable, Debug)]
+pub struct RecurringTransaction {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    pub category_id: Uuid,
+    pub transaction_type: String,
+    pub note: Option<String>,
+    pub amount: f64,
+    pub interval_unit: String,
+    pub interval_count: i32,
+    pub day_of_month: Option<i32>,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    pub next_date: NaiveDate,
+    pub active: bool,
+    pub created_date: DateTime<Utc>,
+}
+
+#[derive(Queryable, Debug)]
+pub struct SavingsGoalContribution {
+    pub id: Uuid,
+    pub goal_id: Uuid,
+    pub amount: f64,
+    pub note: Option<String>,
+    pub created_date: DateTime<Utc>,
+}
+
+#[derive(Queryable, Debug)]
+pub struct SavingsGoal {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub target_amount: f64,
+    pub currency: String,
+    pub deadline: Option<NaiveDate>,
+    pub account_id: Option<Uuid>,
+    pub tag_id: Option<Uuid>,
+    pub completed_date: Option<DateTime<Utc>>,
+    pub created_date: DateTime<Utc>,
+}
+
+#[derive(Queryable, Debug)]
+pub struct Tag {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub color: String,
+    pub created_date: DateTime<Utc>,
+}
+
 #[derive(Queryable, Debug)]
 pub struct Target {
     pub id: Uuid,
@@ -78,8 +220,27 @@ pub struct Target {
     pub user_id: Uuid,
     pub date: NaiveDate,
     pub created_date: DateTime<Utc>,
-    pub amount: i32,
+    pub amount: f64,
     pub deleted: bool,
+    pub note: Option<String>,
+    pub mood: Option<i32>,
+    pub attachment: Option<String>,
+}
+
+#[derive(Queryable, Debug)]
+pub struct TransactionSplit {
+    pub id: Uuid,
+    pub transaction_id: Uuid,
+    pub category_id: Uuid,
+    pub amount: f64,
+    pub note: Option<String>,
+    pub s_order: i32,
+}
+
+#[derive(Queryable, Debug)]
+pub struct TransactionTag {
+    pub transaction_id: Uuid,
+    pub tag_id: Uuid,
 }
 
 #[derive(Queryable, Debug)]
@@ -94,6 +255,9 @@ pub struct Transaction {
     pub created_date: DateTime<Utc>,
     pub archived: bool,
     pub deleted: bool,
+    pub recurring_id: Option<Uuid>,
+    pub recurring_date: Option<NaiveDate>,
+    pub external_id: Option<String>,
 }
 
 #[derive(Queryable, Debug)]
@@ -110,5 +274,6 @@ pub struct User {
     pub active: bool,
     pub created_date: DateTime<Utc>,
     pub updated_date: DateTime<Utc>,
+    pub timezone: String,
 }
 
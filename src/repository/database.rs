@@ -1,8 +1,10 @@
+use crate::common::models::errors::AppError;
 use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager};
 use dotenv::dotenv;
 
 pub type DBPool = r2d2::Pool<ConnectionManager<PgConnection>>;
+pub type DBConnection = r2d2::PooledConnection<ConnectionManager<PgConnection>>;
 
 pub struct Database {
     pub pool: DBPool,
@@ -20,4 +22,8 @@ impl Database {
 
         Database { pool: result }
     }
+
+    pub fn conn(&self) -> Result<DBConnection, AppError> {
+        Ok(self.pool.get()?)
+    }
 }
@@ -16,4 +16,9 @@ pub fn routes() -> Scope {
         .service(features::account::handlers::routes())
         .service(features::category::handlers::routes())
         .service(features::transaction::handlers::routes())
+        .service(features::report::handlers::routes())
+        .service(features::budget::handlers::routes())
+        .service(features::recurring_transaction::handlers::routes())
+        .service(features::tag::handlers::routes())
+        .service(features::savings_goal::handlers::routes())
 }
@@ -1,5 +1,30 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    use diesel::sql_types::*;
+
+    account_balance_snapshots (account_id, date) {
+        account_id -> Uuid,
+        date -> Date,
+        balance -> Float8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    account_terms (account_id) {
+        account_id -> Uuid,
+        interest_rate -> Float8,
+        compounding -> Varchar,
+        term_months -> Int4,
+        start_date -> Date,
+        auto_post -> Bool,
+        category_id -> Nullable<Uuid>,
+        last_posted_date -> Nullable<Date>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
 
@@ -12,6 +37,7 @@ diesel::table! {
         amount -> Float8,
         created_date -> Timestamptz,
         a_order -> Int4,
+        archived -> Bool,
     }
 }
 
@@ -30,6 +56,30 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+
+    budget_categories (id) {
+        id -> Uuid,
+        budget_id -> Uuid,
+        category_id -> Uuid,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    budgets (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        amount -> Float8,
+        period -> Varchar,
+        rollover -> Bool,
+        created_date -> Timestamptz,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
 
@@ -44,6 +94,52 @@ diesel::table! {
         created_date -> Timestamptz,
         modified_date -> Nullable<Timestamptz>,
         c_order -> Int4,
+        parent_id -> Nullable<Uuid>,
+        archived -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    habit_groups (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        color -> Varchar,
+        g_order -> Int4,
+        created_date -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    habit_reminders (id) {
+        id -> Uuid,
+        habit_id -> Uuid,
+        user_id -> Uuid,
+        time -> Time,
+        channel -> Varchar,
+        webhook_url -> Nullable<Varchar>,
+        last_sent_date -> Nullable<Date>,
+        snoozed_until -> Nullable<Timestamptz>,
+        failed_attempts -> Int4,
+        retry_after -> Nullable<Timestamptz>,
+        created_date -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    habit_timer_sessions (id) {
+        id -> Uuid,
+        habit_id -> Uuid,
+        user_id -> Uuid,
+        started_date -> Timestamptz,
+        stopped_date -> Nullable<Timestamptz>,
+        minutes -> Nullable<Float8>,
     }
 }
 
@@ -60,9 +156,14 @@ diesel::table! {
         deleted -> Bool,
         color -> Varchar,
         icon -> Varchar,
-        amount -> Int4,
+        amount -> Float8,
         frequency_type -> Varchar,
         frequency_amount -> Jsonb,
+        polarity -> Varchar,
+        unit -> Varchar,
+        goal_direction -> Varchar,
+        h_order -> Int4,
+        group_id -> Nullable<Uuid>,
     }
 }
 
@@ -78,6 +179,82 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+
+    import_rules (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        pattern -> Varchar,
+        category_id -> Uuid,
+        r_order -> Int4,
+        created_date -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    recurring_transactions (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        account_id -> Uuid,
+        category_id -> Uuid,
+        transaction_type -> Varchar,
+        note -> Nullable<Text>,
+        amount -> Float8,
+        interval_unit -> Varchar,
+        interval_count -> Int4,
+        day_of_month -> Nullable<Int4>,
+        start_date -> Date,
+        end_date -> Nullable<Date>,
+        next_date -> Date,
+        active -> Bool,
+        created_date -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    savings_goal_contributions (id) {
+        id -> Uuid,
+        goal_id -> Uuid,
+        amount -> Float8,
+        note -> Nullable<Varchar>,
+        created_date -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    savings_goals (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        target_amount -> Float8,
+        currency -> Varchar,
+        deadline -> Nullable<Date>,
+        account_id -> Nullable<Uuid>,
+        tag_id -> Nullable<Uuid>,
+        completed_date -> Nullable<Timestamptz>,
+        created_date -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    tags (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        color -> Varchar,
+        created_date -> Timestamptz,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
 
@@ -87,8 +264,33 @@ diesel::table! {
         user_id -> Uuid,
         date -> Date,
         created_date -> Timestamptz,
-        amount -> Int4,
+        amount -> Float8,
         deleted -> Bool,
+        note -> Nullable<Varchar>,
+        mood -> Nullable<Int4>,
+        attachment -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    transaction_splits (id) {
+        id -> Uuid,
+        transaction_id -> Uuid,
+        category_id -> Uuid,
+        amount -> Float8,
+        note -> Nullable<Varchar>,
+        s_order -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    transaction_tags (transaction_id, tag_id) {
+        transaction_id -> Uuid,
+        tag_id -> Uuid,
     }
 }
 
@@ -106,6 +308,9 @@ diesel::table! {
         created_date -> Timestamptz,
         archived -> Bool,
         deleted -> Bool,
+        recurring_id -> Nullable<Uuid>,
+        recurring_date -> Nullable<Date>,
+        external_id -> Nullable<Varchar>,
     }
 }
 
@@ -125,29 +330,71 @@ diesel::table! {
         active -> Bool,
         created_date -> Timestamptz,
         updated_date -> Timestamptz,
+        timezone -> Varchar,
     }
 }
 
+diesel::joinable!(account_balance_snapshots -> accounts (account_id));
+diesel::joinable!(account_terms -> accounts (account_id));
+diesel::joinable!(account_terms -> categories (category_id));
 diesel::joinable!(accounts -> users (user_id));
 diesel::joinable!(achievements -> users (user_id));
+diesel::joinable!(budget_categories -> budgets (budget_id));
+diesel::joinable!(budget_categories -> categories (category_id));
+diesel::joinable!(budgets -> users (user_id));
 diesel::joinable!(categories -> users (user_id));
+diesel::joinable!(habit_groups -> users (user_id));
+diesel::joinable!(habit_reminders -> habits (habit_id));
+diesel::joinable!(habit_reminders -> users (user_id));
+diesel::joinable!(habit_timer_sessions -> habits (habit_id));
+diesel::joinable!(habit_timer_sessions -> users (user_id));
+diesel::joinable!(habits -> habit_groups (group_id));
 diesel::joinable!(habits -> users (user_id));
 diesel::joinable!(habits_achievements -> achievements (achievement_id));
 diesel::joinable!(habits_achievements -> habits (habit_id));
 diesel::joinable!(habits_achievements -> users (user_id));
+diesel::joinable!(import_rules -> categories (category_id));
+diesel::joinable!(import_rules -> users (user_id));
+diesel::joinable!(recurring_transactions -> accounts (account_id));
+diesel::joinable!(recurring_transactions -> categories (category_id));
+diesel::joinable!(recurring_transactions -> users (user_id));
+diesel::joinable!(savings_goal_contributions -> savings_goals (goal_id));
+diesel::joinable!(savings_goals -> accounts (account_id));
+diesel::joinable!(savings_goals -> tags (tag_id));
+diesel::joinable!(savings_goals -> users (user_id));
+diesel::joinable!(tags -> users (user_id));
 diesel::joinable!(targets -> habits (habit_id));
 diesel::joinable!(targets -> users (user_id));
+diesel::joinable!(transaction_splits -> categories (category_id));
+diesel::joinable!(transaction_splits -> transactions (transaction_id));
+diesel::joinable!(transaction_tags -> tags (tag_id));
+diesel::joinable!(transaction_tags -> transactions (transaction_id));
 diesel::joinable!(transactions -> accounts (account_id));
 diesel::joinable!(transactions -> categories (category_id));
+diesel::joinable!(transactions -> recurring_transactions (recurring_id));
 diesel::joinable!(transactions -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    account_balance_snapshots,
+    account_terms,
     accounts,
     achievements,
+    budget_categories,
+    budgets,
     categories,
+    habit_groups,
+    habit_reminders,
+    habit_timer_sessions,
     habits,
     habits_achievements,
+    import_rules,
+    recurring_transactions,
+    savings_goal_contributions,
+    savings_goals,
+    tags,
     targets,
+    transaction_splits,
+    transaction_tags,
     transactions,
     users,
 );
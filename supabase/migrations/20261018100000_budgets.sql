@@ -0,0 +1,16 @@
+CREATE TABLE budgets (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id),
+    name VARCHAR NOT NULL,
+    amount DOUBLE PRECISION NOT NULL,
+    period VARCHAR NOT NULL,
+    rollover BOOLEAN NOT NULL DEFAULT FALSE,
+    created_date TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE budget_categories (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    budget_id UUID NOT NULL REFERENCES budgets(id) ON DELETE CASCADE,
+    category_id UUID NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+    UNIQUE (budget_id, category_id)
+);
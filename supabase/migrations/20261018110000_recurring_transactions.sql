@@ -0,0 +1,25 @@
+CREATE TABLE recurring_transactions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id),
+    account_id UUID NOT NULL REFERENCES accounts(id),
+    category_id UUID NOT NULL REFERENCES categories(id),
+    transaction_type VARCHAR NOT NULL,
+    note TEXT,
+    amount DOUBLE PRECISION NOT NULL,
+    interval_unit VARCHAR NOT NULL,
+    interval_count INTEGER NOT NULL DEFAULT 1,
+    day_of_month INTEGER,
+    start_date DATE NOT NULL,
+    end_date DATE,
+    next_date DATE NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_date TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE transactions
+    ADD COLUMN recurring_id UUID REFERENCES recurring_transactions(id) ON DELETE SET NULL,
+    ADD COLUMN recurring_date DATE;
+
+-- A template is posted at most once per occurrence, even if the scheduler restarts mid-run
+CREATE UNIQUE INDEX transactions_recurring_occurrence_idx
+    ON transactions (recurring_id, recurring_date);
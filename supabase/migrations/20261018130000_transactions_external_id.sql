@@ -0,0 +1,5 @@
+ALTER TABLE transactions ADD COLUMN external_id VARCHAR;
+
+-- Bank-provided identifier (OFX FITID), re-importing the same statement is a no-op
+CREATE UNIQUE INDEX transactions_account_external_id_idx
+    ON transactions (account_id, external_id);
@@ -0,0 +1,13 @@
+-- Optional breakdown of a transaction across several categories, the lines sum to the
+-- transaction amount and the transaction keeps the category of its first line
+CREATE TABLE transaction_splits (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    transaction_id UUID NOT NULL REFERENCES transactions(id) ON DELETE CASCADE,
+    category_id UUID NOT NULL REFERENCES categories(id),
+    amount DOUBLE PRECISION NOT NULL,
+    note VARCHAR,
+    s_order INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX transaction_splits_transaction_id_idx ON transaction_splits (transaction_id);
+CREATE INDEX transaction_splits_category_id_idx ON transaction_splits (category_id);
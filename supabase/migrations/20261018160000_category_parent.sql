@@ -0,0 +1,4 @@
+-- One level of subcategories, c_order is relative to the siblings under the same parent
+ALTER TABLE categories ADD COLUMN parent_id UUID REFERENCES categories(id) ON DELETE SET NULL;
+
+CREATE INDEX categories_parent_id_idx ON categories (parent_id);
@@ -0,0 +1,2 @@
+-- Archived categories are hidden from the category list but keep their transactions
+ALTER TABLE categories ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Archived accounts are hidden from account lists but keep their transactions
+ALTER TABLE accounts ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
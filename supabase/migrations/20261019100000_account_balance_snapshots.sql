@@ -0,0 +1,8 @@
+-- End-of-day account balances (UTC), written by the scheduler and used as starting points
+-- for balance history. Snapshots from the date of a backdated transaction onwards are dropped.
+CREATE TABLE account_balance_snapshots (
+    account_id UUID NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    date DATE NOT NULL,
+    balance DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (account_id, date)
+);
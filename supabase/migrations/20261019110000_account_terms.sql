@@ -0,0 +1,12 @@
+-- Interest terms of deposit and loan accounts. Loans hold the outstanding debt as a
+-- negative balance, so loan interest is posted as an expense and deposit interest as income.
+CREATE TABLE account_terms (
+    account_id UUID PRIMARY KEY REFERENCES accounts(id) ON DELETE CASCADE,
+    interest_rate DOUBLE PRECISION NOT NULL,
+    compounding VARCHAR NOT NULL,
+    term_months INTEGER NOT NULL,
+    start_date DATE NOT NULL,
+    auto_post BOOLEAN NOT NULL DEFAULT FALSE,
+    category_id UUID REFERENCES categories(id),
+    last_posted_date DATE
+);
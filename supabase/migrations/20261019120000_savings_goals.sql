@@ -0,0 +1,33 @@
+-- Savings goals track progress from a linked account's balance, from transactions
+-- carrying a tag, or from contributions entered by hand.
+CREATE TABLE savings_goals (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id),
+    name VARCHAR NOT NULL,
+    target_amount DOUBLE PRECISION NOT NULL,
+    currency VARCHAR NOT NULL,
+    deadline DATE,
+    account_id UUID REFERENCES accounts(id) ON DELETE SET NULL,
+    tag_id UUID REFERENCES tags(id) ON DELETE SET NULL,
+    completed_date TIMESTAMP WITH TIME ZONE,
+    created_date TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (account_id IS NULL OR tag_id IS NULL)
+);
+
+CREATE INDEX savings_goals_user_id_idx ON savings_goals (user_id);
+
+CREATE TABLE savings_goal_contributions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    goal_id UUID NOT NULL REFERENCES savings_goals(id) ON DELETE CASCADE,
+    amount DOUBLE PRECISION NOT NULL,
+    note VARCHAR,
+    created_date TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX savings_goal_contributions_goal_id_idx ON savings_goal_contributions (goal_id);
+
+-- Savings achievements for users that signed up before they existed
+INSERT INTO achievements (user_id, a_order, key, achievement_type, completed)
+SELECT u.id, a.a_order, a.key, 'savings', FALSE
+FROM users u
+CROSS JOIN (VALUES (0, 'NestEgg'), (1, 'AheadOfSchedule'), (2, 'GoalGetter')) AS a (a_order, key);
@@ -0,0 +1,5 @@
+-- Negative habits are ones to avoid: their targets are slips and streaks count the
+-- clean days in between
+ALTER TABLE habits
+    ADD COLUMN polarity VARCHAR NOT NULL DEFAULT 'positive'
+    CHECK (polarity IN ('positive', 'negative'));
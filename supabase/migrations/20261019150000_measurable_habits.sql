@@ -0,0 +1,9 @@
+-- Habits measure a quantity per day: `amount` is the daily threshold in `unit`, reached
+-- when a day's target is at least (or at most) that much
+ALTER TABLE habits ALTER COLUMN amount TYPE DOUBLE PRECISION;
+ALTER TABLE habits ADD COLUMN unit VARCHAR NOT NULL DEFAULT 'count';
+ALTER TABLE habits
+    ADD COLUMN goal_direction VARCHAR NOT NULL DEFAULT 'at_least'
+    CHECK (goal_direction IN ('at_least', 'at_most'));
+
+ALTER TABLE targets ALTER COLUMN amount TYPE DOUBLE PRECISION;
@@ -0,0 +1,14 @@
+-- Timers of duration habits run on the server so that they survive client restarts.
+-- Stopping one adds its minutes to the targets of the days it spans.
+CREATE TABLE habit_timer_sessions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    habit_id UUID NOT NULL REFERENCES habits(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id),
+    started_date TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    stopped_date TIMESTAMP WITH TIME ZONE,
+    minutes DOUBLE PRECISION
+);
+
+-- At most one running timer per habit
+CREATE UNIQUE INDEX habit_timer_sessions_running_idx
+    ON habit_timer_sessions (habit_id) WHERE stopped_date IS NULL;
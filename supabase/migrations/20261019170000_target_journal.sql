@@ -0,0 +1,6 @@
+-- Check-ins double as journal entries: an optional note, a 1-5 mood/difficulty
+-- rating and a reference to an attachment kept in the blob storage.
+ALTER TABLE targets
+    ADD COLUMN note VARCHAR,
+    ADD COLUMN mood INTEGER CHECK (mood BETWEEN 1 AND 5),
+    ADD COLUMN attachment VARCHAR;
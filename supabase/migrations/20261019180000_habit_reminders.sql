@@ -0,0 +1,25 @@
+-- Reminders fire at a local time of the user, so users carry an IANA timezone
+ALTER TABLE users ADD COLUMN timezone VARCHAR NOT NULL DEFAULT 'UTC';
+
+-- Per-habit reminder times, delivered by the scheduler through the notifier of
+-- their channel. `last_sent_date` is the user's local date of the last delivery
+-- and keeps a reminder from firing twice a day; a snoozed reminder fires again
+-- once `snoozed_until` has passed. Failed deliveries are retried after
+-- `retry_after` with a growing delay, up to a few attempts a day.
+CREATE TABLE habit_reminders (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    habit_id UUID NOT NULL REFERENCES habits(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id),
+    time TIME NOT NULL,
+    channel VARCHAR NOT NULL DEFAULT 'push'
+        CHECK (channel IN ('push', 'email', 'webhook')),
+    webhook_url VARCHAR,
+    last_sent_date DATE,
+    snoozed_until TIMESTAMP WITH TIME ZONE,
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    retry_after TIMESTAMP WITH TIME ZONE,
+    created_date TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (channel <> 'webhook' OR webhook_url IS NOT NULL)
+);
+
+CREATE INDEX habit_reminders_habit_id_idx ON habit_reminders (habit_id);
@@ -0,0 +1,22 @@
+ALTER TABLE habits ADD h_order Integer NOT NULL DEFAULT 0;
+
+-- Existing habits keep their creation order
+UPDATE habits SET h_order = ordered.position
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY created_date) - 1 AS position
+    FROM habits
+) ordered
+WHERE habits.id = ordered.id;
+
+-- User-defined groups of habits, e.g. "Morning routine" or "Health"
+CREATE TABLE habit_groups (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id),
+    name VARCHAR NOT NULL,
+    color VARCHAR NOT NULL,
+    g_order Integer NOT NULL DEFAULT 0,
+    created_date TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Deleting a group leaves its habits ungrouped
+ALTER TABLE habits ADD group_id UUID REFERENCES habit_groups(id) ON DELETE SET NULL;